@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS solana_program_builds_repository_finished_at_idx;
+ALTER TABLE solana_program_builds DROP COLUMN finished_at;
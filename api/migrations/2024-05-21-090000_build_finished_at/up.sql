@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE solana_program_builds ADD COLUMN finished_at TIMESTAMP;
+
+-- Recent finished builds of a repository, used to estimate how long a build takes
+CREATE INDEX IF NOT EXISTS solana_program_builds_repository_finished_at_idx
+    ON solana_program_builds (repository, finished_at);
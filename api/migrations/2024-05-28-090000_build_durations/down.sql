@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE solana_program_builds DROP COLUMN build_duration_seconds;
+ALTER TABLE solana_program_builds DROP COLUMN queue_wait_seconds;
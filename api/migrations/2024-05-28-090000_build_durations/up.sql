@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE solana_program_builds ADD COLUMN queue_wait_seconds DOUBLE PRECISION;
+ALTER TABLE solana_program_builds ADD COLUMN build_duration_seconds DOUBLE PRECISION;
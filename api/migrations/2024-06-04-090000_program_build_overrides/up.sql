@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE program_build_overrides (
+    program_id VARCHAR PRIMARY KEY,
+    base_image VARCHAR,
+    cargo_args TEXT[],
+    solana_verify_version VARCHAR,
+    disabled BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
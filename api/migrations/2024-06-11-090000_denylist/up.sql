@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE denylist (
+    id SERIAL PRIMARY KEY,
+    program_id VARCHAR,
+    repository VARCHAR,
+    commit_hash VARCHAR,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CHECK (program_id IS NOT NULL OR repository IS NOT NULL),
+    CHECK (commit_hash IS NULL OR repository IS NOT NULL)
+);
+
+CREATE INDEX denylist_program_id_index ON denylist (program_id);
+CREATE INDEX denylist_repository_index ON denylist (repository);
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE solana_program_builds DROP COLUMN log_purged_at;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE solana_program_builds ADD COLUMN log_purged_at TIMESTAMP;
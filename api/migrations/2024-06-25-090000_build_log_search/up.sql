@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- Paths, image names and URLs are also indexed split at their separators, so a search for a part
+-- of them, e.g. an image name without its registry, matches
+CREATE TABLE build_log_search (
+    build_id VARCHAR PRIMARY KEY,
+    excerpt TEXT NOT NULL,
+    document TSVECTOR GENERATED ALWAYS AS (
+        to_tsvector('simple', excerpt) || to_tsvector('simple', translate(excerpt, '/:@', '   '))
+    ) STORED,
+    indexed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX build_log_search_document_index ON build_log_search USING GIN (document);
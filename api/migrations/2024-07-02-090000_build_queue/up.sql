@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- Builds accepted by instances with the api role. A worker claims a build with a lease it renews
+-- while the build runs, so the build of a worker that crashed is claimed again once its lease
+-- expires.
+CREATE TABLE build_queue (
+    build_id VARCHAR PRIMARY KEY REFERENCES solana_program_builds (id) ON DELETE CASCADE,
+    queued_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    claimed_by VARCHAR,
+    lease_expires_at TIMESTAMP,
+    attempts INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX build_queue_queued_at_index ON build_queue (queued_at);
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE verification_history (
+    id SERIAL PRIMARY KEY,
+    program_id VARCHAR NOT NULL,
+    event VARCHAR(20) NOT NULL,
+    on_chain_hash VARCHAR NOT NULL,
+    executable_hash VARCHAR NOT NULL,
+    solana_build_id VARCHAR,
+    recorded_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX verification_history_recorded_at_index ON verification_history (recorded_at);
+
+INSERT INTO verification_history (program_id, event, on_chain_hash, executable_hash, solana_build_id, recorded_at)
+SELECT program_id, 'verified', on_chain_hash, executable_hash, solana_build_id, verified_at
+FROM verified_programs
+WHERE is_verified;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE dataset_snapshots (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL UNIQUE,
+    taken_at TIMESTAMP NOT NULL,
+    programs INTEGER NOT NULL,
+    size_bytes BIGINT NOT NULL
+);
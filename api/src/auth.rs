@@ -0,0 +1,463 @@
+// Authentication of mutating routes with ed25519 signed requests. Each group of routes has its
+// own list of allowed signers, so access can be scoped and every request is attributable. Keys
+// are rotated by listing the new key next to the old one until clients have switched.
+//
+// A signed request carries these headers:
+// - X-Signer: base58 public key of the signer
+// - X-Timestamp: unix time in seconds, accepted within MAX_CLOCK_SKEW_SECS of the server time
+// - X-Nonce: unique per request, used at most once
+// - X-Signature: base58 ed25519 signature of the canonical request
+//
+// The canonical request is "<METHOD>\n<path and query>\n<timestamp>\n<nonce>\n" followed by the
+// raw request body.
+
+use axum::body::{Body, Bytes};
+use axum::extract::{ConnectInfo, State};
+use axum::http::{header::AUTHORIZATION, HeaderMap, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::Utc;
+use ed25519_dalek::{Signature, VerifyingKey};
+use ipnet::IpNet;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+
+use crate::db::DbClient;
+use crate::models::{ErrorResponse, Status};
+
+const SIGNER_HEADER: &str = "x-signer";
+const TIMESTAMP_HEADER: &str = "x-timestamp";
+const NONCE_HEADER: &str = "x-nonce";
+const SIGNATURE_HEADER: &str = "x-signature";
+
+const MAX_CLOCK_SKEW_SECS: i64 = 300;
+const MAX_NONCE_LEN: usize = 64;
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+// Signers allowed on a group of routes
+#[derive(Clone)]
+pub struct Signers {
+    db: DbClient,
+    keys: Arc<Vec<VerifyingKey>>,
+}
+
+impl Signers {
+    // Signers from a comma separated list of base58 public keys in the variable `var`. Without
+    // any key the routes reject every request.
+    pub fn from_env(db: DbClient, var: &str) -> Self {
+        let keys = env::var(var)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(|key| {
+                parse_pubkey(key).unwrap_or_else(|| panic!("Invalid public key {} in {}", key, var))
+            })
+            .collect();
+        Self {
+            db,
+            keys: Arc::new(keys),
+        }
+    }
+}
+
+// Middleware rejecting requests that aren't signed by one of the allowed signers
+pub async fn require_signature(
+    State(signers): State<Signers>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let (parts, body) = request.into_parts();
+
+    let Some(headers) = SignedHeaders::parse(&parts.headers) else {
+        return unauthorized("Missing or malformed signature headers");
+    };
+    // Checked before reading the body so unknown signers can't make us buffer it
+    let Some(signer) = signers
+        .keys
+        .iter()
+        .find(|key| key.as_bytes() == headers.signer.as_bytes())
+    else {
+        return unauthorized("Signer is not allowed on this route");
+    };
+    if !within_clock_skew(headers.timestamp, Utc::now().timestamp()) {
+        return unauthorized("Request timestamp is too old or in the future");
+    }
+
+    let body = match hyper::body::to_bytes(http_body::Limited::new(body, MAX_BODY_SIZE)).await {
+        Ok(body) => body,
+        Err(_) => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Request body is too large".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    if !verify_signature(signer, parts.method.as_str(), path, &headers, &body) {
+        return unauthorized("Invalid signature");
+    }
+
+    // A nonce is remembered for as long as its timestamp is accepted
+    let signer_id = bs58::encode(signer.as_bytes()).into_string();
+    match signers
+        .db
+        .claim_nonce(&signer_id, &headers.nonce, 2 * MAX_CLOCK_SKEW_SECS as u64)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return unauthorized("Nonce was already used"),
+        Err(err) => {
+            tracing::error!("Failed to record request nonce: {}", err);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while checking the request signature".to_string(),
+                }),
+            )
+                .into_response();
+        }
+    }
+
+    tracing::info!(signer = %signer_id, "Signed request accepted");
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+// Middleware for GET /metrics. Scrapers can't sign requests, so it takes one of the comma
+// separated METRICS_TOKEN values as a bearer token instead. Listing the old and new token during
+// a rotation keeps scrapes working. Without a configured token the route is disabled.
+pub async fn require_metrics_token<B>(request: Request<B>, next: Next<B>) -> Response {
+    if !has_bearer_token(request.headers(), "METRICS_TOKEN") {
+        return unauthorized("Unauthorized");
+    }
+
+    next.run(request).await
+}
+
+// Middleware for the routes of build workers, called by the API with the BUILD_WORKER_TOKEN
+// shared between them. Comma separated tokens work the same as METRICS_TOKEN.
+pub async fn require_worker_token<B>(request: Request<B>, next: Next<B>) -> Response {
+    if !has_bearer_token(request.headers(), "BUILD_WORKER_TOKEN") {
+        return unauthorized("Unauthorized");
+    }
+
+    next.run(request).await
+}
+
+// Whether the request carries one of the comma separated bearer tokens of `var`
+fn has_bearer_token(headers: &HeaderMap, var: &str) -> bool {
+    let tokens = env::var(var).unwrap_or_default();
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer.is_some_and(|bearer| {
+        tokens
+            .split(',')
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .any(|token| constant_time_eq(bearer.as_bytes(), token.as_bytes()))
+    })
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Networks allowed to reach a group of routes, checked before any credential
+#[derive(Clone)]
+pub struct IpAllowlist {
+    allowed: Arc<Vec<IpNet>>,
+}
+
+impl IpAllowlist {
+    // Networks from a comma separated list of CIDRs or addresses in the variable `var`. Without
+    // any network every address is allowed.
+    pub fn from_env(var: &str) -> Self {
+        // Parsed here so an invalid TRUSTED_PROXY_CIDRS fails at startup
+        trusted_proxies();
+        Self {
+            allowed: Arc::new(parse_networks(var)),
+        }
+    }
+}
+
+// X-Forwarded-For is only trusted on connections from the TRUSTED_PROXY_CIDRS networks
+fn trusted_proxies() -> &'static [IpNet] {
+    static TRUSTED_PROXIES: OnceLock<Vec<IpNet>> = OnceLock::new();
+    TRUSTED_PROXIES.get_or_init(|| parse_networks("TRUSTED_PROXY_CIDRS"))
+}
+
+fn is_trusted_proxy(ip: IpAddr) -> bool {
+    trusted_proxies().iter().any(|net| net.contains(&ip))
+}
+
+// Address of the client, walking X-Forwarded-For back from the closest trusted proxy
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+    if !is_trusted_proxy(peer) {
+        return peer;
+    }
+
+    let forwarded = headers
+        .get_all(FORWARDED_FOR_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    let mut client = peer;
+    for hop in forwarded.into_iter().rev() {
+        let Ok(ip) = hop.parse() else {
+            break;
+        };
+        client = ip;
+        if !is_trusted_proxy(ip) {
+            break;
+        }
+    }
+    client
+}
+
+// Middleware rejecting requests from addresses outside the allowlist
+pub async fn require_allowed_ip<B>(
+    State(allowlist): State<IpAllowlist>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if allowlist.allowed.is_empty() {
+        return next.run(request).await;
+    }
+
+    let client = client_ip(peer.ip(), request.headers());
+    if !allowlist.allowed.iter().any(|net| net.contains(&client)) {
+        tracing::warn!(%client, "Request from an address outside the allowlist");
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                status: Status::Error,
+                error: "Forbidden".to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+pub(crate) fn parse_networks(var: &str) -> Vec<IpNet> {
+    env::var(var)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|net| !net.is_empty())
+        .map(|net| {
+            net.parse::<IpNet>()
+                .or_else(|_| net.parse::<IpAddr>().map(IpNet::from))
+                .unwrap_or_else(|_| panic!("Invalid network {} in {}", net, var))
+        })
+        .collect()
+}
+
+struct SignedHeaders {
+    signer: VerifyingKey,
+    timestamp: i64,
+    nonce: String,
+    signature: Signature,
+}
+
+impl SignedHeaders {
+    fn parse(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        let nonce = header(NONCE_HEADER)?;
+        if nonce.is_empty() || nonce.len() > MAX_NONCE_LEN {
+            return None;
+        }
+        let signature: [u8; 64] = bs58::decode(header(SIGNATURE_HEADER)?)
+            .into_vec()
+            .ok()?
+            .try_into()
+            .ok()?;
+
+        Some(Self {
+            signer: parse_pubkey(header(SIGNER_HEADER)?)?,
+            timestamp: header(TIMESTAMP_HEADER)?.parse().ok()?,
+            nonce: nonce.to_string(),
+            signature: Signature::from_bytes(&signature),
+        })
+    }
+}
+
+fn parse_pubkey(key: &str) -> Option<VerifyingKey> {
+    let bytes: [u8; 32] = bs58::decode(key).into_vec().ok()?.try_into().ok()?;
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+// Timestamps are client supplied, so the difference is computed without overflowing
+fn within_clock_skew(timestamp: i64, now: i64) -> bool {
+    now.abs_diff(timestamp) <= MAX_CLOCK_SKEW_SECS as u64
+}
+
+fn verify_signature(
+    signer: &VerifyingKey,
+    method: &str,
+    path: &str,
+    headers: &SignedHeaders,
+    body: &Bytes,
+) -> bool {
+    let message = canonical_request(method, path, headers, body);
+    signer.verify_strict(&message, &headers.signature).is_ok()
+}
+
+fn canonical_request(method: &str, path: &str, headers: &SignedHeaders, body: &Bytes) -> Vec<u8> {
+    let mut message = format!(
+        "{}\n{}\n{}\n{}\n",
+        method, path, headers.timestamp, headers.nonce
+    )
+    .into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+fn unauthorized(error: &str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(ErrorResponse {
+            status: Status::Error,
+            error: error.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn signed_headers(key: &SigningKey, method: &str, path: &str, body: &[u8]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let pubkey = bs58::encode(key.verifying_key().as_bytes()).into_string();
+        headers.insert(SIGNER_HEADER, HeaderValue::from_str(&pubkey).unwrap());
+        headers.insert(TIMESTAMP_HEADER, HeaderValue::from_static("1700000000"));
+        headers.insert(NONCE_HEADER, HeaderValue::from_static("nonce-1"));
+
+        let message = format!("{}\n{}\n1700000000\nnonce-1\n", method, path);
+        let mut message = message.into_bytes();
+        message.extend_from_slice(body);
+        let signature = bs58::encode(key.sign(&message).to_bytes()).into_string();
+        headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(&signature).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_canonical_request() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let headers = SignedHeaders::parse(&signed_headers(&key, "POST", "/verify", b"")).unwrap();
+        let message = canonical_request(
+            "POST",
+            "/verify?sync=true",
+            &headers,
+            &Bytes::from_static(b"{\"program_id\":\"x\"}"),
+        );
+        assert_eq!(
+            message,
+            b"POST\n/verify?sync=true\n1700000000\nnonce-1\n{\"program_id\":\"x\"}"
+        );
+    }
+
+    #[test]
+    fn test_signed_headers_parse() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let headers = signed_headers(&key, "POST", "/verify", b"");
+        let parsed = SignedHeaders::parse(&headers).unwrap();
+        assert_eq!(parsed.signer, key.verifying_key());
+        assert_eq!(parsed.timestamp, 1700000000);
+        assert_eq!(parsed.nonce, "nonce-1");
+
+        let mut missing = headers.clone();
+        missing.remove(NONCE_HEADER);
+        assert!(SignedHeaders::parse(&missing).is_none());
+
+        let mut long_nonce = headers.clone();
+        let nonce = "n".repeat(MAX_NONCE_LEN + 1);
+        long_nonce.insert(NONCE_HEADER, HeaderValue::from_str(&nonce).unwrap());
+        assert!(SignedHeaders::parse(&long_nonce).is_none());
+
+        let mut bad_signature = headers;
+        bad_signature.insert(SIGNATURE_HEADER, HeaderValue::from_static("not-base58"));
+        assert!(SignedHeaders::parse(&bad_signature).is_none());
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let body = Bytes::from_static(b"{}");
+        let headers =
+            SignedHeaders::parse(&signed_headers(&key, "POST", "/verify", &body)).unwrap();
+        let signer = key.verifying_key();
+        assert!(verify_signature(
+            &signer, "POST", "/verify", &headers, &body
+        ));
+
+        // Any change to the signed request invalidates the signature
+        assert!(!verify_signature(
+            &signer, "PUT", "/verify", &headers, &body
+        ));
+        assert!(!verify_signature(
+            &signer,
+            "POST",
+            "/verify?x=1",
+            &headers,
+            &body
+        ));
+        assert!(!verify_signature(
+            &signer,
+            "POST",
+            "/verify",
+            &headers,
+            &Bytes::from_static(b"{ }")
+        ));
+
+        let other = SigningKey::from_bytes(&[2; 32]).verifying_key();
+        assert!(!verify_signature(
+            &other, "POST", "/verify", &headers, &body
+        ));
+    }
+
+    #[test]
+    fn test_within_clock_skew() {
+        let now = 1700000000;
+        assert!(within_clock_skew(now, now));
+        assert!(within_clock_skew(now - MAX_CLOCK_SKEW_SECS, now));
+        assert!(within_clock_skew(now + MAX_CLOCK_SKEW_SECS, now));
+        assert!(!within_clock_skew(now - MAX_CLOCK_SKEW_SECS - 1, now));
+        assert!(!within_clock_skew(now + MAX_CLOCK_SKEW_SECS + 1, now));
+        assert!(!within_clock_skew(i64::MIN, now));
+        assert!(!within_clock_skew(i64::MAX, now));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
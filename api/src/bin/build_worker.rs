@@ -0,0 +1,30 @@
+// Build worker for BUILD_BACKEND=remote. Runs the builds the API dispatches on this machine,
+// which needs solana-verify and Docker like an API host running builds itself.
+
+use dotenv::dotenv;
+use std::env;
+use std::net::SocketAddr;
+use verified_programs_api::routes::create_worker_router;
+use verified_programs_api::{container, egress, images, telemetry};
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+    telemetry::init();
+    env::var("BUILD_WORKER_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .expect("BUILD_WORKER_TOKEN not set in .env file");
+    container::init();
+    egress::init();
+    images::spawn_maintenance(None);
+    let app = create_worker_router();
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], 3001));
+    tracing::info!("Build worker listening on {}", addr);
+
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
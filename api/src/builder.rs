@@ -1,176 +1,311 @@
 use std::env;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use tokio::process::Command;
+use tokio::sync::Notify;
 
 use crate::errors::ApiError;
-use crate::models::{SolanaProgramBuild, SolanaProgramBuildParams, VerifiedProgram};
+use crate::logging;
+use crate::metrics::{self, BuildTimings, QueuedBuild};
+use crate::models::{
+    ProgramBuildOverride, SolanaProgramBuild, SolanaProgramBuildParams, VerifiedProgram,
+};
 use crate::Result;
-use libc::{c_ulong, getrlimit, rlimit, setrlimit, RLIMIT_AS};
 
-fn get_last_line(output: &str) -> Option<String> {
-    output.lines().last().map(ToOwned::to_owned)
-}
+pub mod backend;
+pub mod kubernetes;
+pub mod remote;
+
+use backend::BuildRequest;
 
 /// Create a URL for the repository of the program
 /// Arguments:
 /// * `res`: The `res` parameter is a `SolanaProgramBuild` struct that contains the repository
-/// and the commit hash of the program.
+///   and the commit hash of the program.
+///
 /// Returns: A string that represents the URL of the repository.
 ///
 pub fn get_repo_url(build_params: &SolanaProgramBuild) -> String {
-    build_params.commit_hash.as_ref().map_or_else(
-        || build_params.repository.clone(),
-        |hash| format!("{}/commit/{}", build_params.repository, hash),
+    build_repository_url(
+        &build_params.repository,
+        build_params.commit_hash.as_deref(),
     )
 }
 
-fn extract_hash(output: &str, prefix: &str) -> Option<String> {
-    output
-        .lines()
-        .find(|line| line.starts_with(prefix))
-        .map(|line| {
-            let hash = line.trim_start_matches(prefix.trim()).trim();
-            hash.to_owned()
-        })
-}
-
-/// The `verify_build` function verifies a Solana program build by executing the `solana-verify` command
-/// and parsing the output to determine if the program hash matches and storing the verified build
-/// information in a database.
-///
-/// Arguments:
-///
-/// * `pool`: `pool` is an Arc of a connection pool to a PostgreSQL database. It is used to interact
-/// with the database and perform database operations.
-/// * `payload`: The `payload` parameter is of type `SolanaProgramBuildParams`
+/// Link to a commit of a repository, using the commit URL layout of the repository host.
+/// Hosts other than GitLab and Bitbucket, e.g. GitHub and Gitea, use `<repo>/commit/<sha>`.
+/// Self-hosted GitLab instances are recognized by a `gitlab` in their domain.
 ///
-/// Returns:
-///
-/// The function `verify_build` returns a `Result` with the success case containing a `VerifiedProgram`
-/// struct and the error case containing an `ApiError`.
-pub async fn verify_build(
-    payload: SolanaProgramBuildParams,
-    build_id: &str,
-) -> Result<VerifiedProgram> {
-    tracing::info!("Verifying build..");
-
-    // Original R limit
-    let mut original_rlimit = rlimit {
-        rlim_cur: 0,
-        rlim_max: 0,
+/// Returns: The repository URL itself when no commit hash is given.
+pub fn build_repository_url(repository: &str, commit_hash: Option<&str>) -> String {
+    let repository = repository.trim_end_matches('/');
+    let Some(hash) = commit_hash else {
+        return repository.to_string();
     };
-    // 1 GB memory limit
-    let max_ram_usage_bytes: c_ulong = 1024 * 1024 * 1024;
-    unsafe {
-        getrlimit(RLIMIT_AS, &mut original_rlimit);
-        setrlimit(
-            libc::RLIMIT_AS,
-            &libc::rlimit {
-                rlim_cur: max_ram_usage_bytes,
-                rlim_max: max_ram_usage_bytes,
-            },
-        );
-    }
-    // Run solana-verify command
-    let mut cmd = Command::new("solana-verify");
-    cmd.arg("verify-from-repo").arg("-um");
+    let repository = repository.trim_end_matches(".git");
 
-    // Add optional arguments
-    if let Some(commit) = payload.commit_hash {
-        cmd.arg("--commit-hash").arg(commit);
+    let domain = repository
+        .split_once("://")
+        .map_or(repository, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if domain.contains("gitlab") {
+        format!("{}/-/commit/{}", repository, hash)
+    } else if domain.trim_start_matches("www.") == "bitbucket.org" {
+        format!("{}/commits/{}", repository, hash)
+    } else {
+        format!("{}/commit/{}", repository, hash)
     }
+}
 
-    if let Some(library_name) = payload.lib_name {
-        cmd.arg("--library-name").arg(library_name);
-    }
+/// Normalize a repository URL so different spellings of the same repository compare equal:
+/// lowercase, without a trailing slash or `.git` suffix.
+pub fn normalize_repository_url(repository: &str) -> String {
+    repository
+        .trim()
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .to_lowercase()
+}
+
+// Builds allowed to run at the same time, read from MAX_CONCURRENT_BUILDS. Unlimited by default.
+fn max_concurrent_builds() -> usize {
+    env::var("MAX_CONCURRENT_BUILDS")
+        .ok()
+        .and_then(|permits| permits.parse::<usize>().ok())
+        .filter(|permits| *permits > 0)
+        .unwrap_or(usize::MAX)
+}
 
-    if let Some(base_image) = payload.base_image {
-        cmd.arg("--base-image").arg(base_image);
+// Slots of the running builds. Unlike a semaphore the limit can change while builds run, builds
+// over a lowered limit finish and new ones wait until the running builds are under it.
+struct BuildSlots {
+    // Running builds and the limit
+    state: Mutex<(usize, usize)>,
+    released: Notify,
+}
+
+struct BuildSlot(&'static BuildSlots);
+
+impl BuildSlots {
+    async fn acquire(&'static self) -> BuildSlot {
+        loop {
+            // Created before checking so a release in between isn't missed
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.0 < state.1 {
+                    state.0 += 1;
+                    return BuildSlot(self);
+                }
+            }
+            released.await;
+        }
     }
+}
 
-    if let Some(mount_path) = payload.mount_path {
-        cmd.arg("--mount-path").arg(mount_path);
+impl Drop for BuildSlot {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().0 -= 1;
+        self.0.released.notify_waiters();
     }
+}
 
-    if let Some(bpf_flag) = payload.bpf_flag {
-        if bpf_flag {
-            cmd.arg("--bpf");
+impl BuildSlots {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new((0, max_concurrent_builds())),
+            released: Notify::new(),
         }
     }
 
-    cmd.arg("--program-id")
-        .arg(&payload.program_id)
-        .arg(payload.repository);
+    fn set_limit(&self, limit: usize) {
+        self.state.lock().unwrap().1 = limit;
+        self.released.notify_waiters();
+    }
+}
+
+fn build_slots() -> &'static BuildSlots {
+    static SLOTS: OnceLock<BuildSlots> = OnceLock::new();
+    SLOTS.get_or_init(BuildSlots::new)
+}
+
+// Builds a worker took from the queue, counted apart from the build slots since a build only
+// takes its build slot once it starts
+fn worker_slots() -> &'static BuildSlots {
+    static SLOTS: OnceLock<BuildSlots> = OnceLock::new();
+    SLOTS.get_or_init(BuildSlots::new)
+}
 
-    if let Some(cargo_args) = payload.cargo_args {
-        cmd.arg("--").args(&cargo_args);
+/// A build a worker took from the queue, see [`acquire_worker_slot`].
+pub struct WorkerSlot {
+    _slot: BuildSlot,
+}
+
+/// Waits until this worker runs fewer builds than MAX_CONCURRENT_BUILDS, so it only takes builds
+/// from the queue it can start right away and leaves the others to the other workers.
+pub async fn acquire_worker_slot() -> WorkerSlot {
+    WorkerSlot {
+        _slot: worker_slots().acquire().await,
     }
+}
 
-    tracing::info!("Running command: {:?}", cmd);
+/// Apply a new MAX_CONCURRENT_BUILDS. Running builds are unaffected.
+pub fn reload_build_limit() {
+    let limit = max_concurrent_builds();
+    build_slots().set_limit(limit);
+    worker_slots().set_limit(limit);
+}
 
-    let output = cmd.output().await?;
-    let result = String::from_utf8(output.stdout)?;
-    if !output.status.success() {
-        return Err(ApiError::Build(result));
+/// Waits for a build slot and runs [`run_build`], recording the queue and build metrics.
+/// The build override of the program, if any, is merged over the params of the request.
+///
+/// Returns: The result of the build, and how long it waited and ran when it got to run.
+#[tracing::instrument(skip(payload, build_override))]
+pub async fn verify_build(
+    mut payload: SolanaProgramBuildParams,
+    build_override: Option<&ProgramBuildOverride>,
+    build_id: &str,
+) -> (Result<VerifiedProgram>, Option<BuildTimings>) {
+    let mut solana_verify_version = None;
+    if let Some(build_override) = build_override {
+        build_override.apply(&mut payload);
+        solana_verify_version = build_override.solana_verify_version.as_deref();
     }
 
-    let onchain_hash = extract_hash(&result, "On-chain Program Hash:").unwrap_or_default();
-    let build_hash =
-        extract_hash(&result, "Executable Program Hash from repo:").unwrap_or_default();
+    let queued = QueuedBuild::enqueue();
+    let _slot = build_slots().acquire().await;
+    let active = queued.start();
 
-    // last line of output has the result
-    let last_line = get_last_line(&result).ok_or_else(|| {
-        ApiError::Build("Failed to build and get output from program".to_string())
-    })?;
+    let result = run_build(payload, solana_verify_version, build_id).await;
+    let timings = active.finish(result.is_ok());
+    (result, Some(timings))
+}
+
+/// The `run_build` function verifies a Solana program build by running it on the configured
+/// [`backend::Builder`], storing its log and comparing the hash of the build with the on-chain
+/// program.
+///
+/// Arguments:
+///
+/// * `payload`: The `payload` parameter is of type `SolanaProgramBuildParams`
+/// * `solana_verify_version`: Version of solana-verify pinned for the program, the default
+///   version is used otherwise.
+///
+/// Returns:
+///
+/// The function `run_build` returns a `Result` with the success case containing a `VerifiedProgram`
+/// struct and the error case containing an `ApiError`.
+async fn run_build(
+    payload: SolanaProgramBuildParams,
+    solana_verify_version: Option<&str>,
+    build_id: &str,
+) -> Result<VerifiedProgram> {
+    tracing::info!("Verifying build..");
+
+    let started = Instant::now();
+    let outcome = backend::get()
+        .execute(BuildRequest {
+            build_id,
+            params: &payload,
+            solana_verify_version,
+        })
+        .await?;
+    metrics::record_stage("build", started);
+    logging::store_build_log(build_id, &outcome.stdout, &outcome.stderr).await;
+
+    let Some(hashes) = outcome.hashes else {
+        logging::store_build_errors(build_id, &outcome.stdout, &outcome.stderr).await;
+        return Err(ApiError::Build(
+            String::from_utf8_lossy(&outcome.stdout).into_owned(),
+        ));
+    };
 
     tracing::info!(
         "{} build hash {} On chain hash {}",
         payload.program_id,
-        build_hash,
-        onchain_hash
+        hashes.executable_hash,
+        hashes.on_chain_hash
     );
 
-    let verified_build = VerifiedProgram {
+    Ok(VerifiedProgram {
         id: uuid::Uuid::new_v4().to_string(),
         program_id: payload.program_id,
-        is_verified: last_line.contains("Program hash matches"),
-        on_chain_hash: onchain_hash,
-        executable_hash: build_hash,
+        is_verified: hashes.matches,
+        on_chain_hash: hashes.on_chain_hash,
+        executable_hash: hashes.executable_hash,
         verified_at: chrono::Utc::now().naive_utc(),
         solana_build_id: build_id.to_string(),
-    };
-
-    // Reset R limit
-    unsafe {
-        setrlimit(RLIMIT_AS, &original_rlimit);
-    }
+    })
+}
 
-    Ok(verified_build)
-    // let _ = self.insert_or_update_verified_build(&verified_build).await;
+pub fn rpc_url() -> String {
+    env::var("RPC_URL").unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string())
 }
 
-pub async fn get_on_chain_hash(program_id: &str) -> Result<String> {
-    let rpc_url =
-        env::var("RPC_URL").unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
-    let mut cmd = Command::new("solana-verify");
-    cmd.arg("get-program-hash").arg(program_id);
-    cmd.arg("--url").arg(rpc_url);
+const LS_REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
 
-    let output = cmd
-        .output()
-        .await
-        .map_err(|_| ApiError::Custom("Failed to run process get-program-hash".to_string()))?;
+/// Resolve the branch, tag or missing commit of a verification request to the commit it points
+/// at right now, so the stored build params describe a reproducible build.
+///
+/// Arguments:
+///
+/// * `repository`: URL of the git repository.
+/// * `commit`: A branch, a tag, a commit hash, or `None` for the default branch.
+///
+/// Returns: The full commit hash. Abbreviated hashes that match no ref are returned unchanged
+/// since `git ls-remote` can't expand them.
+#[tracing::instrument]
+pub async fn resolve_commit(repository: &str, commit: Option<&str>) -> Result<String> {
+    if let Some(commit) = commit.filter(|commit| is_full_commit_hash(commit)) {
+        return Ok(commit.to_string());
+    }
 
+    let git_ref = commit.unwrap_or("HEAD");
+    let mut cmd = Command::new("git");
+    cmd.arg("ls-remote")
+        .arg("--")
+        .arg(repository)
+        .arg(git_ref)
+        // Fail instead of waiting for credentials of private repositories
+        .env("GIT_TERMINAL_PROMPT", "0");
+    let output = tokio::time::timeout(LS_REMOTE_TIMEOUT, cmd.output())
+        .await
+        .map_err(|_| ApiError::Custom(format!("Timed out resolving {}", git_ref)))??;
     if !output.status.success() {
-        tracing::error!(
-            "Failed to get on-chain hash {}",
-            String::from_utf8(output.stderr)?
-        );
-        return Err(ApiError::Custom("Failed to get on-chain hash".to_string()));
+        return Err(ApiError::Custom(format!(
+            "Failed to list the refs of {}",
+            repository
+        )));
+    }
+
+    let stdout = String::from_utf8(output.stdout)?;
+    let refs = stdout
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .collect::<Vec<_>>();
+    // Annotated tags are listed twice, the `^{}` entry points at the commit
+    let resolved = refs
+        .iter()
+        .find(|(_, name)| name.ends_with("^{}"))
+        .or_else(|| refs.first())
+        .map(|(hash, _)| hash.to_string());
+
+    match (resolved, commit) {
+        (Some(hash), _) => Ok(hash),
+        (None, Some(commit)) if commit.chars().all(|c| c.is_ascii_hexdigit()) => {
+            Ok(commit.to_string())
+        }
+        (None, _) => Err(ApiError::Custom(format!(
+            "No branch, tag or commit named {} in {}",
+            git_ref, repository
+        ))),
     }
-    let result = String::from_utf8(output.stdout)?;
-    let hash = get_last_line(&result).ok_or_else(|| {
-        ApiError::Custom("Failed to build and get output from program".to_string())
-    })?;
-    Ok(hash)
+}
+
+pub fn is_full_commit_hash(commit: &str) -> bool {
+    commit.len() == 40 && commit.chars().all(|c| c.is_ascii_hexdigit())
 }
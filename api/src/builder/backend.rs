@@ -0,0 +1,250 @@
+// Backends that run the builds of the verification pipeline.
+//
+// The backend is selected with BUILD_BACKEND:
+// - cli (default): runs solana-verify on this host
+// - remote: dispatches builds to build workers, see builder::remote
+// - kubernetes: runs each build as a Kubernetes Job, see builder::kubernetes
+//
+// Services embedding the pipeline, and tests, can install their own backend with `set`.
+
+use async_trait::async_trait;
+use libc::{c_ulong, getrlimit, rlimit, setrlimit, RLIMIT_AS};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::sync::{Arc, OnceLock};
+use tokio::process::Command;
+
+use super::kubernetes::KubernetesBuilder;
+use super::remote::RemoteBuilder;
+use crate::container;
+use crate::egress;
+use crate::errors::ApiError;
+use crate::models::SolanaProgramBuildParams;
+use crate::Result;
+
+// 1 GB memory limit of the solana-verify process
+const MAX_RAM_USAGE_BYTES: c_ulong = 1024 * 1024 * 1024;
+
+/// A build to run, with the build override of the program already applied to `params`.
+#[derive(Debug)]
+pub struct BuildRequest<'a> {
+    pub build_id: &'a str,
+    pub params: &'a SolanaProgramBuildParams,
+    /// Version of solana-verify pinned for the program, `None` for the default version.
+    pub solana_verify_version: Option<&'a str>,
+}
+
+/// Hashes of a build that ran to completion.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildHashes {
+    pub on_chain_hash: String,
+    pub executable_hash: String,
+    /// Whether the executable built from the repository matches the on-chain program.
+    pub matches: bool,
+}
+
+/// What a backend reports back about a build.
+#[derive(Debug, Default)]
+pub struct BuildOutcome {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// `None` when the build failed, the reason is in the output.
+    pub hashes: Option<BuildHashes>,
+}
+
+impl BuildOutcome {
+    /// Outcome of a solana-verify run, with the hashes from its output when it succeeded.
+    /// Output without the hashes fails the build, with the reason added to `stderr`.
+    pub(crate) fn from_output(stdout: Vec<u8>, mut stderr: Vec<u8>, succeeded: bool) -> Self {
+        let mut hashes = None;
+        if succeeded {
+            match parse_hashes(&String::from_utf8_lossy(&stdout)) {
+                Ok(parsed) => hashes = Some(parsed),
+                Err(err) => {
+                    tracing::error!("{}", err);
+                    stderr.extend_from_slice(format!("\n{}\n", err).as_bytes());
+                }
+            }
+        }
+        Self {
+            stdout,
+            stderr,
+            hashes,
+        }
+    }
+}
+
+/// Runs builds. A backend returns an error when it can't run the build at all, a build that ran
+/// and failed is a `BuildOutcome` without hashes.
+#[async_trait]
+pub trait Builder: Send + Sync {
+    async fn execute(&self, request: BuildRequest<'_>) -> Result<BuildOutcome>;
+}
+
+/// Runs `solana-verify verify-from-repo` on this host, or `solana-verify-<version>` when a
+/// version is pinned.
+pub struct CliBuilder;
+
+#[async_trait]
+impl Builder for CliBuilder {
+    async fn execute(&self, request: BuildRequest<'_>) -> Result<BuildOutcome> {
+        let command = solana_verify_command(&request);
+        let mut cmd = Command::new(&command[0]);
+        cmd.args(&command[1..]);
+        container::get().configure(&mut cmd);
+        let egress = egress::start_proxy(request.build_id).await?;
+        if let Some(egress) = &egress {
+            egress.configure(&mut cmd);
+        }
+
+        tracing::info!("Running command: {:?}", cmd);
+
+        // Original R limit
+        let mut original_rlimit = rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        unsafe {
+            getrlimit(RLIMIT_AS, &mut original_rlimit);
+            setrlimit(
+                RLIMIT_AS,
+                &rlimit {
+                    rlim_cur: MAX_RAM_USAGE_BYTES,
+                    rlim_max: MAX_RAM_USAGE_BYTES,
+                },
+            );
+        }
+        let output = cmd.output().await;
+        // Reset R limit
+        unsafe {
+            setrlimit(RLIMIT_AS, &original_rlimit);
+        }
+        let mut output = output?;
+        if let Some(egress) = egress {
+            output.stderr.extend_from_slice(egress.report().as_bytes());
+        }
+
+        Ok(BuildOutcome::from_output(
+            output.stdout,
+            output.stderr,
+            output.status.success(),
+        ))
+    }
+}
+
+// solana-verify invocation running a build, starting with the binary
+pub(crate) fn solana_verify_command(request: &BuildRequest<'_>) -> Vec<String> {
+    let mut command = vec![match request.solana_verify_version {
+        Some(version) => format!("solana-verify-{}", version),
+        None => "solana-verify".to_string(),
+    }];
+    command.extend(verify_from_repo_args(request.params));
+    command
+}
+
+// Arguments of solana-verify building and verifying the program of `params` against mainnet
+pub(crate) fn verify_from_repo_args(params: &SolanaProgramBuildParams) -> Vec<String> {
+    let mut command = vec!["verify-from-repo".to_string(), "-um".to_string()];
+    let mut arg = |name: &str, value: &Option<String>| {
+        if let Some(value) = value {
+            command.extend([name.to_string(), value.clone()]);
+        }
+    };
+
+    // Add optional arguments
+    arg("--commit-hash", &params.commit_hash);
+    arg("--library-name", &params.lib_name);
+    arg("--base-image", &params.base_image);
+    arg("--mount-path", &params.mount_path);
+
+    if params.bpf_flag.unwrap_or(false) {
+        command.push("--bpf".to_string());
+    }
+
+    command.extend([
+        "--program-id".to_string(),
+        params.program_id.clone(),
+        params.repository.clone(),
+    ]);
+
+    if let Some(cargo_args) = &params.cargo_args {
+        command.push("--".to_string());
+        command.extend(cargo_args.iter().cloned());
+    }
+    command
+}
+
+// Hash printed after `label` in the output of solana-verify. Output that doesn't have it is an
+// error, so a change of the output format fails builds instead of storing empty hashes.
+fn extract_hash(output: &str, label: &str) -> Result<String> {
+    output
+        .lines()
+        .find_map(|line| line.split_once(label).map(|(_, rest)| rest))
+        .and_then(|rest| rest.split_whitespace().next())
+        .filter(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_lowercase)
+        .ok_or_else(|| {
+            ApiError::Build(format!(
+                "Unrecognized solana-verify output, no hash after {:?}",
+                label
+            ))
+        })
+}
+
+// Hashes printed by a successful solana-verify run. They are read from its output because
+// solana-verify is only published as a binary, so it can't be called as a library.
+fn parse_hashes(output: &str) -> Result<BuildHashes> {
+    let on_chain_hash = extract_hash(output, "On-chain Program Hash:")?;
+    let executable_hash = extract_hash(output, "Executable Program Hash from repo:")?;
+    Ok(BuildHashes {
+        matches: on_chain_hash == executable_hash,
+        on_chain_hash,
+        executable_hash,
+    })
+}
+
+static BACKEND: OnceLock<Arc<dyn Builder>> = OnceLock::new();
+
+// Whether builds run on this host, and need solana-verify and a container runtime here
+pub fn builds_locally() -> bool {
+    matches!(
+        env::var("BUILD_BACKEND").unwrap_or_default().as_str(),
+        "" | "cli"
+    )
+}
+
+// Build the configured backend. Panics on an invalid configuration so it surfaces at startup.
+pub fn init() {
+    BACKEND.get_or_init(|| from_env().expect("Invalid build backend configuration"));
+}
+
+pub fn get() -> Arc<dyn Builder> {
+    BACKEND
+        .get_or_init(|| from_env().expect("Invalid build backend configuration"))
+        .clone()
+}
+
+/// Use `backend` for all builds instead of the one configured by BUILD_BACKEND.
+///
+/// Returns: An error when a backend is already in use.
+pub fn set(backend: Arc<dyn Builder>) -> Result<()> {
+    BACKEND
+        .set(backend)
+        .map_err(|_| ApiError::Custom("A build backend is already in use".to_string()))
+}
+
+fn from_env() -> Result<Arc<dyn Builder>> {
+    let backend = env::var("BUILD_BACKEND").unwrap_or_default();
+    let builder: Arc<dyn Builder> = match backend.as_str() {
+        "" | "cli" => Arc::new(CliBuilder),
+        "remote" => Arc::new(RemoteBuilder::from_env()?),
+        "kubernetes" => Arc::new(KubernetesBuilder::from_env()?),
+        backend => {
+            return Err(ApiError::Custom(format!(
+                "BUILD_BACKEND must be one of cli, remote or kubernetes, got {}",
+                backend
+            )))
+        }
+    };
+    Ok(builder)
+}
@@ -0,0 +1,301 @@
+// Build backend running each build as a Kubernetes Job, for deployments on Kubernetes that want
+// builds isolated in their own pods and scaled by the cluster.
+//
+// The API talks to the Kubernetes API server with its service account, which needs to create and
+// get jobs and get pods and their logs in the build namespace. Configured with:
+// - K8S_BUILD_NAMESPACE: namespace of the build jobs, the namespace of the API by default
+// - K8S_BUILD_JOB_TEMPLATE: path of a Job manifest in JSON used as template for the build jobs.
+//   Its first container runs the build. Without a template the job is built from:
+//   - K8S_BUILD_IMAGE: image with solana-verify and access to a container runtime
+//   - K8S_BUILD_CPU_LIMIT, K8S_BUILD_MEMORY_LIMIT: resource limits of the build container
+// - K8S_BUILD_TIMEOUT_SECS: deadline of a build job, 2 hours by default
+// - K8S_BUILD_JOB_TTL_SECS: how long finished jobs are kept before Kubernetes deletes them, one
+//   hour by default
+
+use async_trait::async_trait;
+use reqwest::{Certificate, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::env;
+use std::time::{Duration, Instant};
+
+use super::backend::{solana_verify_command, BuildOutcome, BuildRequest, Builder};
+use crate::errors::ApiError;
+use crate::Result;
+
+const SERVICE_ACCOUNT_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+const DEFAULT_TIMEOUT_SECS: u64 = 2 * 60 * 60;
+const DEFAULT_JOB_TTL_SECS: u64 = 60 * 60;
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// Time the cluster gets past the job deadline to report the job as failed
+const DEADLINE_GRACE: Duration = Duration::from_secs(5 * 60);
+const JOB_LABEL: &str = "solana-verified-programs-build";
+
+pub struct KubernetesBuilder {
+    client: reqwest::Client,
+    api_url: String,
+    namespace: String,
+    template: Value,
+    timeout: Duration,
+}
+
+#[derive(Debug, Deserialize)]
+struct Job {
+    #[serde(default)]
+    status: JobStatus,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct JobStatus {
+    #[serde(default)]
+    succeeded: u32,
+    #[serde(default)]
+    failed: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct PodList {
+    items: Vec<Pod>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Pod {
+    metadata: PodMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+struct PodMetadata {
+    name: String,
+}
+
+impl KubernetesBuilder {
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+        let secs = |name: &str, default: u64| {
+            var(name)
+                .and_then(|secs| secs.parse::<u64>().ok())
+                .filter(|secs| *secs > 0)
+                .unwrap_or(default)
+        };
+
+        let host = var("KUBERNETES_SERVICE_HOST").ok_or_else(|| {
+            ApiError::Custom("The kubernetes build backend must run in a cluster".to_string())
+        })?;
+        let port = var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|| "443".to_string());
+        let api_url = match host.contains(':') {
+            true => format!("https://[{}]:{}", host, port),
+            false => format!("https://{}:{}", host, port),
+        };
+        let namespace = match var("K8S_BUILD_NAMESPACE") {
+            Some(namespace) => namespace,
+            None => std::fs::read_to_string(format!("{}/namespace", SERVICE_ACCOUNT_PATH))?
+                .trim()
+                .to_string(),
+        };
+        let ca = std::fs::read(format!("{}/ca.crt", SERVICE_ACCOUNT_PATH))?;
+        let client = reqwest::Client::builder()
+            .add_root_certificate(Certificate::from_pem(&ca)?)
+            .build()?;
+
+        let timeout = secs("K8S_BUILD_TIMEOUT_SECS", DEFAULT_TIMEOUT_SECS);
+        let ttl = secs("K8S_BUILD_JOB_TTL_SECS", DEFAULT_JOB_TTL_SECS);
+        let mut template = match var("K8S_BUILD_JOB_TEMPLATE") {
+            Some(path) => serde_json::from_slice(&std::fs::read(path)?)?,
+            None => {
+                let image = var("K8S_BUILD_IMAGE").ok_or_else(|| {
+                    ApiError::Custom(
+                        "K8S_BUILD_IMAGE or K8S_BUILD_JOB_TEMPLATE must be set".to_string(),
+                    )
+                })?;
+                let mut limits = json!({});
+                if let Some(cpu) = var("K8S_BUILD_CPU_LIMIT") {
+                    limits["cpu"] = json!(cpu);
+                }
+                if let Some(memory) = var("K8S_BUILD_MEMORY_LIMIT") {
+                    limits["memory"] = json!(memory);
+                }
+                json!({
+                    "apiVersion": "batch/v1",
+                    "kind": "Job",
+                    "spec": {
+                        "template": {
+                            "spec": {
+                                "containers": [{
+                                    "name": "build",
+                                    "image": image,
+                                    "resources": { "limits": limits },
+                                }],
+                            },
+                        },
+                    },
+                })
+            }
+        };
+
+        if template
+            .pointer("/spec/template/spec/containers/0")
+            .is_none()
+        {
+            return Err(ApiError::Custom(
+                "The build job template must have a container".to_string(),
+            ));
+        }
+        // Settings every build job needs, whatever the template says
+        template["spec"]["backoffLimit"] = json!(0);
+        template["spec"]["activeDeadlineSeconds"] = json!(timeout);
+        if template["spec"].get("ttlSecondsAfterFinished").is_none() {
+            template["spec"]["ttlSecondsAfterFinished"] = json!(ttl);
+        }
+        template["spec"]["template"]["spec"]["restartPolicy"] = json!("Never");
+
+        Ok(Self {
+            client,
+            api_url,
+            namespace,
+            template,
+            timeout: Duration::from_secs(timeout),
+        })
+    }
+
+    fn job_manifest(&self, name: &str, request: &BuildRequest<'_>) -> Value {
+        let mut manifest = self.template.clone();
+        manifest["metadata"]["name"] = json!(name);
+        manifest["metadata"]["labels"]["app"] = json!(JOB_LABEL);
+        manifest["metadata"]["labels"]["build-id"] = json!(request.build_id);
+        manifest["spec"]["template"]["metadata"]["labels"]["app"] = json!(JOB_LABEL);
+        manifest["spec"]["template"]["spec"]["containers"][0]["command"] =
+            json!(solana_verify_command(request));
+        manifest
+    }
+
+    // Service account tokens are rotated, so the token is read for every request
+    async fn token(&self) -> Result<String> {
+        let token = tokio::fs::read_to_string(format!("{}/token", SERVICE_ACCOUNT_PATH)).await?;
+        Ok(token.trim().to_string())
+    }
+
+    async fn get(&self, path: &str) -> Result<reqwest::Response> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.api_url, path))
+            .bearer_auth(self.token().await?)
+            .send()
+            .await?;
+        check_status(response).await
+    }
+
+    async fn create_job(&self, manifest: &Value) -> Result<()> {
+        let response = self
+            .client
+            .post(format!(
+                "{}/apis/batch/v1/namespaces/{}/jobs",
+                self.api_url, self.namespace
+            ))
+            .bearer_auth(self.token().await?)
+            .json(manifest)
+            .send()
+            .await?;
+        check_status(response).await?;
+        Ok(())
+    }
+
+    // Wait for the job to finish, returns whether it succeeded
+    async fn wait_for_job(&self, name: &str) -> Result<bool> {
+        let path = format!("/apis/batch/v1/namespaces/{}/jobs/{}", self.namespace, name);
+        let deadline = Instant::now() + self.timeout + DEADLINE_GRACE;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            // A build runs for minutes, so it outlives a hiccup of the API server
+            match self.get(&path).await {
+                Ok(response) => {
+                    let job = response.json::<Job>().await?;
+                    if job.status.succeeded > 0 {
+                        return Ok(true);
+                    }
+                    if job.status.failed > 0 {
+                        return Ok(false);
+                    }
+                }
+                Err(err) => tracing::warn!("Failed to get the status of job {}: {}", name, err),
+            }
+            if Instant::now() > deadline {
+                return Err(ApiError::Custom(format!(
+                    "Timed out waiting for job {}",
+                    name
+                )));
+            }
+        }
+    }
+
+    // Output of the build container of the job, empty when its pod never ran
+    async fn job_log(&self, name: &str) -> Result<Vec<u8>> {
+        let pods = self
+            .get(&format!(
+                "/api/v1/namespaces/{}/pods?labelSelector=job-name%3D{}",
+                self.namespace, name
+            ))
+            .await?
+            .json::<PodList>()
+            .await?;
+        let Some(pod) = pods.items.into_iter().next() else {
+            return Ok(Vec::new());
+        };
+        let container = self.template["spec"]["template"]["spec"]["containers"][0]["name"]
+            .as_str()
+            .unwrap_or_default();
+        let log = self
+            .get(&format!(
+                "/api/v1/namespaces/{}/pods/{}/log?container={}",
+                self.namespace, pod.metadata.name, container
+            ))
+            .await?
+            .bytes()
+            .await?;
+        Ok(log.to_vec())
+    }
+}
+
+// Turn error responses of the API server into errors with the message it returned
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let message = response
+        .json::<Value>()
+        .await
+        .ok()
+        .and_then(|body| body["message"].as_str().map(ToOwned::to_owned))
+        .unwrap_or_default();
+    Err(ApiError::Custom(match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => format!(
+            "The service account is not allowed to manage build jobs: {}",
+            message
+        ),
+        status => format!(
+            "Kubernetes API server responded with {}: {}",
+            status, message
+        ),
+    }))
+}
+
+#[async_trait]
+impl Builder for KubernetesBuilder {
+    async fn execute(&self, request: BuildRequest<'_>) -> Result<BuildOutcome> {
+        // A build is re-run under the same id, so each run gets its own job. The suffix keeps the
+        // name within the 63 characters Kubernetes allows for the job-name label.
+        let attempt = uuid::Uuid::new_v4().simple().to_string();
+        let name = format!(
+            "verify-{}-{}",
+            request.build_id.to_lowercase(),
+            &attempt[..8]
+        );
+        self.create_job(&self.job_manifest(&name, &request)).await?;
+        tracing::info!("Started build job {}", name);
+
+        let succeeded = self.wait_for_job(&name).await?;
+        // Kubernetes keeps a single stream for stdout and stderr of a container
+        let log = self.job_log(&name).await?;
+        Ok(BuildOutcome::from_output(log, Vec::new(), succeeded))
+    }
+}
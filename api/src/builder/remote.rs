@@ -0,0 +1,175 @@
+// Build backend dispatching builds to build workers, so build capacity scales separately from
+// the API hosts. A worker is the build_worker binary on a machine with solana-verify and Docker.
+//
+// Protocol: the API sends `POST <worker>/builds` with a `WorkerBuildRequest` and the shared
+// BUILD_WORKER_TOKEN as bearer token. The worker runs the build with the cli backend and answers
+// with a `WorkerBuildResponse` once it's done. A worker with no free build slot answers 503.
+//
+// Configured with:
+// - BUILD_WORKER_URLS: comma separated base URLs of the workers, used in turns
+// - BUILD_WORKER_TOKEN: token shared with the workers
+// - BUILD_WORKER_TIMEOUT_SECS: how long a build may take on a worker, 2 hours by default
+
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::backend::{BuildHashes, BuildOutcome, BuildRequest, Builder};
+use crate::errors::ApiError;
+use crate::models::{ErrorResponse, SolanaProgramBuildParams};
+use crate::Result;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 2 * 60 * 60;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkerBuildRequest {
+    pub build_id: String,
+    pub params: SolanaProgramBuildParams,
+    pub solana_verify_version: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkerBuildResponse {
+    pub stdout: String,
+    pub stderr: String,
+    pub hashes: Option<BuildHashes>,
+}
+
+impl From<BuildOutcome> for WorkerBuildResponse {
+    fn from(outcome: BuildOutcome) -> Self {
+        Self {
+            stdout: String::from_utf8_lossy(&outcome.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&outcome.stderr).into_owned(),
+            hashes: outcome.hashes,
+        }
+    }
+}
+
+impl From<WorkerBuildResponse> for BuildOutcome {
+    fn from(response: WorkerBuildResponse) -> Self {
+        Self {
+            stdout: response.stdout.into_bytes(),
+            stderr: response.stderr.into_bytes(),
+            hashes: response.hashes,
+        }
+    }
+}
+
+pub struct RemoteBuilder {
+    client: reqwest::Client,
+    workers: Vec<String>,
+    token: String,
+    // Worker the next build is offered to first
+    next: AtomicUsize,
+}
+
+impl RemoteBuilder {
+    pub fn from_env() -> Result<Self> {
+        let workers = env::var("BUILD_WORKER_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty())
+            .collect::<Vec<_>>();
+        if workers.is_empty() {
+            return Err(ApiError::Custom(
+                "BUILD_WORKER_URLS must list at least one build worker".to_string(),
+            ));
+        }
+        let token = env::var("BUILD_WORKER_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| ApiError::Custom("BUILD_WORKER_TOKEN must be set".to_string()))?;
+        let timeout = env::var("BUILD_WORKER_TIMEOUT_SECS")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+
+        let client = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(Duration::from_secs(timeout))
+            .build()
+            .map_err(|err| ApiError::Custom(format!("Failed to create HTTP client: {}", err)))?;
+        Ok(Self {
+            client,
+            workers,
+            token,
+            next: AtomicUsize::new(0),
+        })
+    }
+}
+
+#[async_trait]
+impl Builder for RemoteBuilder {
+    async fn execute(&self, request: BuildRequest<'_>) -> Result<BuildOutcome> {
+        let body = WorkerBuildRequest {
+            build_id: request.build_id.to_string(),
+            params: request.params.clone(),
+            solana_verify_version: request.solana_verify_version.map(ToOwned::to_owned),
+        };
+
+        // Offer the build to each worker once, moving on from unreachable and busy workers
+        let first = self.next.fetch_add(1, Ordering::Relaxed);
+        for offset in 0..self.workers.len() {
+            let worker = &self.workers[(first + offset) % self.workers.len()];
+            let response = self
+                .client
+                .post(format!("{}/builds", worker))
+                .bearer_auth(&self.token)
+                .json(&body)
+                .send()
+                .await;
+            let response = match response {
+                Ok(response) => response,
+                Err(err) if err.is_connect() => {
+                    tracing::warn!("Build worker {} is unreachable: {}", worker, err);
+                    continue;
+                }
+                // The build may still be running there, so it isn't offered to another worker
+                Err(err) => {
+                    return Err(ApiError::Custom(format!(
+                        "Build on worker {} failed: {}",
+                        worker, err
+                    )))
+                }
+            };
+
+            match response.status() {
+                StatusCode::OK => {
+                    let response = response
+                        .json::<WorkerBuildResponse>()
+                        .await
+                        .map_err(|err| {
+                            ApiError::Custom(format!(
+                                "Invalid response from worker {}: {}",
+                                worker, err
+                            ))
+                        })?;
+                    tracing::info!("Build {} ran on worker {}", request.build_id, worker);
+                    return Ok(response.into());
+                }
+                StatusCode::SERVICE_UNAVAILABLE => {
+                    tracing::info!("Build worker {} is busy", worker);
+                }
+                status => {
+                    let error = response
+                        .json::<ErrorResponse>()
+                        .await
+                        .map(|response| response.error)
+                        .unwrap_or_default();
+                    return Err(ApiError::Custom(format!(
+                        "Build worker {} responded with {}: {}",
+                        worker, status, error
+                    )));
+                }
+            }
+        }
+
+        Err(ApiError::Custom("No build worker available".to_string()))
+    }
+}
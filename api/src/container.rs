@@ -0,0 +1,301 @@
+// Container runtime the solana-verify builds run their containers with, selected with
+// CONTAINER_RUNTIME:
+// - docker (default)
+// - podman, including rootless podman
+// - containerd, through its nerdctl CLI
+//
+// solana-verify always runs `docker`, so builds on other runtimes run with a PATH where `docker`
+// is a link to the docker compatible CLI of the runtime.
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::process::Command;
+
+use crate::errors::ApiError;
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeKind {
+    Docker,
+    Podman,
+    Containerd,
+}
+
+impl RuntimeKind {
+    fn cli(self) -> &'static str {
+        match self {
+            RuntimeKind::Docker => "docker",
+            RuntimeKind::Podman => "podman",
+            RuntimeKind::Containerd => "nerdctl",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ContainerRuntime {
+    kind: RuntimeKind,
+    cli: PathBuf,
+    // PATH of the builds, None to keep the inherited one
+    build_path: Option<OsString>,
+}
+
+impl ContainerRuntime {
+    pub fn kind(&self) -> RuntimeKind {
+        self.kind
+    }
+
+    // Path of the CLI of the runtime
+    pub fn cli(&self) -> &Path {
+        &self.cli
+    }
+
+    // PATH the builds run with
+    pub fn build_path(&self) -> OsString {
+        self.build_path
+            .clone()
+            .unwrap_or_else(|| env::var_os("PATH").unwrap_or_default())
+    }
+
+    // Command running the CLI of the runtime
+    pub fn command(&self) -> Command {
+        Command::new(&self.cli)
+    }
+
+    // Make `cmd` run its containers with this runtime
+    pub fn configure(&self, cmd: &mut Command) {
+        if let Some(path) = &self.build_path {
+            cmd.env("PATH", path);
+        }
+    }
+
+    // Version of the runtime, fails when the CLI can't reach the runtime
+    pub async fn version(&self) -> std::result::Result<String, String> {
+        let format = match self.kind {
+            RuntimeKind::Docker | RuntimeKind::Containerd => "{{.ServerVersion}}",
+            RuntimeKind::Podman => "{{.Version.Version}}",
+        };
+        let output = self
+            .command()
+            .args(["info", "--format", format])
+            .output()
+            .await
+            .map_err(|err| format!("failed to run {}: {}", self.cli.display(), err))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} is not reachable: {}",
+                self.kind.cli(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(format!(
+            "{} {}",
+            self.kind.cli(),
+            String::from_utf8_lossy(&output.stdout).trim()
+        ))
+    }
+
+    // Run the CLI of the runtime, returns its output or an error with what it printed on stderr
+    async fn run(&self, args: &[&str]) -> Result<String> {
+        let output = self.command().args(args).output().await?;
+        if !output.status.success() {
+            return Err(ApiError::Custom(format!(
+                "{} {} failed: {}",
+                self.kind.cli(),
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    pub async fn pull(&self, image: &str) -> Result<()> {
+        self.run(&["pull", "--quiet", image]).await?;
+        Ok(())
+    }
+
+    // Images of the runtime, newest first
+    pub async fn images(&self) -> Result<Vec<Image>> {
+        let output = self
+            .run(&[
+                "image",
+                "ls",
+                "--digests",
+                "--format",
+                "{{.ID}}\t{{.Repository}}\t{{.Tag}}\t{{.Digest}}",
+            ])
+            .await?;
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t').map(|field| match field {
+                    "" | "<none>" => None,
+                    field => Some(field.to_string()),
+                });
+                Some(Image {
+                    id: fields.next()??,
+                    repository: fields.next()?,
+                    tag: fields.next()?,
+                    digest: fields.next().flatten(),
+                })
+            })
+            .collect())
+    }
+
+    // Remove an image, fails when a container uses it
+    pub async fn remove_image(&self, image: &Image) -> Result<()> {
+        self.run(&["image", "rm", &image.reference()]).await?;
+        Ok(())
+    }
+
+    pub async fn prune_dangling_images(&self) -> Result<()> {
+        self.run(&["image", "prune", "--force"]).await?;
+        Ok(())
+    }
+
+    // Disk space taken by the images in bytes, layers shared between images counted once
+    pub async fn image_disk_usage(&self) -> Result<u64> {
+        let output = self
+            .run(&["system", "df", "--format", "{{.Type}}\t{{.Size}}"])
+            .await?;
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix("Images\t"))
+            .and_then(parse_size)
+            .ok_or_else(|| ApiError::Custom(format!("Unexpected disk usage output: {}", output)))
+    }
+}
+
+#[derive(Debug)]
+pub struct Image {
+    pub id: String,
+    pub repository: Option<String>,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl Image {
+    // Reference naming the image, by tag when it has one
+    pub fn reference(&self) -> String {
+        match (&self.repository, &self.tag, &self.digest) {
+            (Some(repository), Some(tag), _) => format!("{}:{}", repository, tag),
+            (Some(repository), None, Some(digest)) => format!("{}@{}", repository, digest),
+            _ => self.id.clone(),
+        }
+    }
+
+    // Whether `reference`, as given to a pull, names this image
+    pub fn matches(&self, reference: &str) -> bool {
+        let Some(repository) = &self.repository else {
+            return false;
+        };
+        let reference = normalize_reference(reference);
+        let repository = strip_default_registry(repository);
+        match (&self.tag, &self.digest) {
+            (Some(tag), _) if reference == format!("{}:{}", repository, tag) => true,
+            (_, Some(digest)) => reference == format!("{}@{}", repository, digest),
+            _ => false,
+        }
+    }
+}
+
+// Docker Hub images are listed without their registry by docker and with it by podman
+fn strip_default_registry(reference: &str) -> &str {
+    reference
+        .strip_prefix("docker.io/library/")
+        .or_else(|| reference.strip_prefix("docker.io/"))
+        .unwrap_or(reference)
+}
+
+// Reference without the default registry, and with the default tag when it has neither a tag nor
+// a digest
+fn normalize_reference(reference: &str) -> String {
+    let reference = strip_default_registry(reference);
+    let name = reference.rsplit('/').next().unwrap_or(reference);
+    match name.contains(':') || name.contains('@') {
+        true => reference.to_string(),
+        false => format!("{}:latest", reference),
+    }
+}
+
+// Size printed by the runtimes, such as 1.5GB, in bytes. The units are powers of 1000.
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (value, unit) = size.split_at(split);
+    let factor = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        _ => return None,
+    };
+    Some((value.parse::<f64>().ok()? * factor) as u64)
+}
+
+static RUNTIME: OnceLock<ContainerRuntime> = OnceLock::new();
+
+// Set up the configured runtime. Panics on an invalid configuration so it surfaces at startup.
+pub fn init() {
+    get();
+}
+
+pub fn get() -> &'static ContainerRuntime {
+    RUNTIME.get_or_init(|| from_env().expect("Invalid container runtime configuration"))
+}
+
+fn from_env() -> Result<ContainerRuntime> {
+    let kind = match env::var("CONTAINER_RUNTIME").unwrap_or_default().as_str() {
+        "" | "docker" => RuntimeKind::Docker,
+        "podman" => RuntimeKind::Podman,
+        "containerd" => RuntimeKind::Containerd,
+        runtime => {
+            return Err(ApiError::Custom(format!(
+                "CONTAINER_RUNTIME must be one of docker, podman or containerd, got {}",
+                runtime
+            )))
+        }
+    };
+    let path = env::var_os("PATH").unwrap_or_default();
+    let cli = env::split_paths(&path)
+        .map(|dir| dir.join(kind.cli()))
+        .find(|cli| cli.is_file());
+
+    // A missing docker is reported by the startup checks, hosts that don't build don't need it
+    let (cli, build_path) = match (kind, cli) {
+        (RuntimeKind::Docker, cli) => (cli.unwrap_or_else(|| PathBuf::from(kind.cli())), None),
+        (_, Some(cli)) => {
+            let build_path = docker_shim(kind, &cli, &path)?;
+            (cli, Some(build_path))
+        }
+        (_, None) => {
+            return Err(ApiError::Custom(format!(
+                "{} was not found in PATH",
+                kind.cli()
+            )))
+        }
+    };
+    Ok(ContainerRuntime {
+        kind,
+        cli,
+        build_path,
+    })
+}
+
+// PATH with a directory holding a `docker` link to `cli` in front
+fn docker_shim(kind: RuntimeKind, cli: &Path, path: &OsString) -> Result<OsString> {
+    let dir = env::temp_dir().join(format!("solana-verify-{}", kind.cli()));
+    std::fs::create_dir_all(&dir)?;
+    let link = dir.join("docker");
+    if link.symlink_metadata().is_ok() {
+        std::fs::remove_file(&link)?;
+    }
+    std::os::unix::fs::symlink(cli, &link)?;
+
+    env::join_paths(std::iter::once(dir).chain(env::split_paths(path)))
+        .map_err(|err| ApiError::Custom(format!("Invalid PATH: {}", err)))
+}
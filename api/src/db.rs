@@ -1,16 +1,60 @@
-use diesel::{expression_methods::ExpressionMethods, query_dsl::QueryDsl};
+use diesel::{
+    expression_methods::ExpressionMethods, query_dsl::QueryDsl, NullableExpressionMethods,
+};
 use diesel_async::pooled_connection::AsyncDieselConnectionManager;
 use diesel_async::RunQueryDsl;
 use diesel_async::{pooled_connection::deadpool::Pool, AsyncPgConnection};
 use r2d2_redis::redis::{Commands, FromRedisValue, Value};
 use r2d2_redis::{r2d2, RedisConnectionManager};
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
 
-use crate::builder::{self, get_on_chain_hash};
+use crate::builder;
+use crate::disk;
 use crate::errors::ApiError;
+use crate::events;
+use crate::metrics::BuildTimings;
 use crate::models::{
-    JobStatus, SolanaProgramBuild, SolanaProgramBuildParams, VerificationResponse, VerifiedProgram,
+    AuthorityChange, BuildClaim, BuildDurationEstimate, BuildId, CrawlRequest, CrawlerRun,
+    DatasetSnapshot, DenylistEntry, DenylistParams, HistoryEvent, JobStatus, LogSearchResult,
+    MainnetProgram, ProgramBuildOverride, SolanaProgramBuild, SolanaProgramBuildParams,
+    TimeSeriesPoint, VerificationHistoryEntry, VerificationResponse, VerifiedProgram,
 };
+use crate::program_hash::get_on_chain_hash;
+use crate::role;
+use crate::shutdown;
+use crate::telemetry;
 use crate::Result;
+use tracing::Instrument;
+
+// Statuses are cached for STATUS_CACHE_TTL_SECS, 60 seconds by default
+const DEFAULT_STATUS_CACHE_TTL_SECS: usize = 60;
+
+fn status_cache_ttl() -> usize {
+    env::var("STATUS_CACHE_TTL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<usize>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_STATUS_CACHE_TTL_SECS)
+}
+
+// Redis set of the builds to resume, see shutdown.rs
+const INTERRUPTED_BUILDS_KEY: &str = "interrupted_builds";
+
+// How often a /verify_sync request checks on its queued build
+const QUEUED_BUILD_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// A program is rebuilt at most once per REVERIFY_COOLDOWN_SECS, 10 minutes by default
+const DEFAULT_REVERIFY_COOLDOWN_SECS: u64 = 10 * 60;
+
+fn reverify_cooldown() -> u64 {
+    env::var("REVERIFY_COOLDOWN_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_REVERIFY_COOLDOWN_SECS)
+}
 
 #[derive(Clone)]
 pub struct DbClient {
@@ -37,6 +81,7 @@ impl DbClient {
         }
     }
 
+    #[tracing::instrument(skip_all)]
     pub async fn insert_build_params(&self, payload: &SolanaProgramBuild) -> Result<usize> {
         use crate::schema::solana_program_builds::dsl::*;
 
@@ -48,6 +93,7 @@ impl DbClient {
             .map_err(Into::into)
     }
 
+    #[tracing::instrument(skip_all)]
     pub async fn insert_or_update_verified_build(
         &self,
         payload: &VerifiedProgram,
@@ -65,6 +111,7 @@ impl DbClient {
             .map_err(Into::into)
     }
 
+    #[tracing::instrument(skip_all)]
     pub async fn check_for_dupliate(
         &self,
         payload: &SolanaProgramBuildParams,
@@ -114,17 +161,38 @@ impl DbClient {
             .map_err(Into::into)
     }
 
+    // Most recent build of a program
+    #[tracing::instrument(skip(self))]
     pub async fn get_build_params(&self, program_address: &str) -> Result<SolanaProgramBuild> {
         use crate::schema::solana_program_builds::dsl::*;
 
         let conn = &mut self.db_pool.get().await?;
         solana_program_builds
             .filter(crate::schema::solana_program_builds::program_id.eq(program_address))
+            .order(created_at.desc())
             .first::<SolanaProgramBuild>(conn)
             .await
             .map_err(Into::into)
     }
 
+    // Status of the most recent build of a program, None when none was ever submitted
+    #[tracing::instrument(skip(self))]
+    pub async fn get_latest_build_status(&self, program_address: &str) -> Result<Option<String>> {
+        use crate::schema::solana_program_builds::dsl::*;
+        use diesel::OptionalExtension;
+
+        let conn = &mut self.db_pool.get().await?;
+        solana_program_builds
+            .filter(program_id.eq(program_address))
+            .order(created_at.desc())
+            .select(status)
+            .first::<String>(conn)
+            .await
+            .optional()
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
     pub async fn get_verified_build(&self, program_address: &str) -> Result<VerifiedProgram> {
         use crate::schema::verified_programs::dsl::*;
 
@@ -136,6 +204,7 @@ impl DbClient {
             .map_err(Into::into)
     }
 
+    #[tracing::instrument(skip(self))]
     pub async fn update_onchain_hash(
         &self,
         program_address: &str,
@@ -156,7 +225,8 @@ impl DbClient {
             .map_err(Into::into)
     }
 
-    // Redis cache SET and Value expiring in 60 seconds
+    // Redis cache SET and Value expiring after the status cache TTL
+    #[tracing::instrument(skip(self, value))]
     pub async fn set_cache(&self, program_address: &str, value: &str) -> Result<()> {
         let cache_res = self.redis_pool.get();
         let mut redis_conn = match cache_res {
@@ -167,7 +237,7 @@ impl DbClient {
             }
         };
         redis_conn
-            .set_ex(program_address, value, 60)
+            .set_ex::<_, _, ()>(program_address, value, status_cache_ttl())
             .map_err(|err| {
                 tracing::error!("Redis SET failed: {}", err);
                 ApiError::from(err)
@@ -177,6 +247,7 @@ impl DbClient {
     }
 
     // Redis cache GET program_hash and return the value
+    #[tracing::instrument(skip(self))]
     pub async fn get_cache(&self, program_address: &str) -> Result<String> {
         let cache_res = self.redis_pool.get().map_err(|err| {
             tracing::error!("Redis connection error: {}", err);
@@ -202,6 +273,127 @@ impl DbClient {
         }
     }
 
+    // Set `key` for `ttl` seconds unless it is already set. Returns false when it was.
+    async fn claim(&self, key: String, ttl: u64) -> Result<bool> {
+        let mut redis_conn = self.redis_pool.get()?;
+        let claimed: Option<String> = r2d2_redis::redis::cmd("SET")
+            .arg(key)
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl)
+            .query(&mut *redis_conn)?;
+        Ok(claimed.is_some())
+    }
+
+    // Record a nonce of a signed request for `ttl` seconds. Returns false when it was already used.
+    pub async fn claim_nonce(&self, signer: &str, nonce: &str, ttl: u64) -> Result<bool> {
+        self.claim(format!("nonce:{}:{}", signer, nonce), ttl).await
+    }
+
+    // Claim the reverification of a program for `cooldown` seconds. Returns false when it was
+    // already claimed within the cooldown, by this or another instance.
+    pub async fn claim_reverification(&self, program_id: &str, cooldown: u64) -> Result<bool> {
+        self.claim(format!("reverify:{}", program_id), cooldown)
+            .await
+    }
+
+    // Claim the snapshot `name` for `ttl` seconds, so a single instance takes it
+    pub async fn claim_snapshot(&self, name: &str, ttl: u64) -> Result<bool> {
+        self.claim(format!("snapshot:{}", name), ttl).await
+    }
+
+    // Claim the run of the periodic job `job` for `ttl` seconds, so it runs once per interval across
+    // instances and restarts
+    pub async fn claim_job(&self, job: &str, ttl: u64) -> Result<bool> {
+        self.claim(format!("jobs:{}", job), ttl).await
+    }
+
+    // Release the claim of the snapshot `name`, so another attempt can take it
+    pub async fn release_snapshot(&self, name: &str) -> Result<()> {
+        let mut redis_conn = self.redis_pool.get()?;
+        redis_conn.del::<_, ()>(format!("snapshot:{}", name))?;
+        Ok(())
+    }
+
+    // Record builds interrupted by a shutdown, for the next instance to resume them
+    pub async fn record_interrupted_builds(&self, build_ids: &[String]) -> Result<()> {
+        if build_ids.is_empty() {
+            return Ok(());
+        }
+        let mut redis_conn = self.redis_pool.get()?;
+        redis_conn.sadd::<_, _, ()>(INTERRUPTED_BUILDS_KEY, build_ids)?;
+        Ok(())
+    }
+
+    // Take one of the interrupted builds, None once they have all been taken
+    pub async fn take_interrupted_build(&self) -> Result<Option<String>> {
+        let mut redis_conn = self.redis_pool.get()?;
+        let build_id = r2d2_redis::redis::cmd("SPOP")
+            .arg(INTERRUPTED_BUILDS_KEY)
+            .query(&mut *redis_conn)?;
+        Ok(build_id)
+    }
+
+    // Store a proof of work challenge and its difficulty for `ttl` seconds
+    pub async fn store_challenge(&self, challenge: &str, difficulty: u32, ttl: u64) -> Result<()> {
+        let mut redis_conn = self.redis_pool.get()?;
+        redis_conn.set_ex::<_, _, ()>(format!("pow:{}", challenge), difficulty, ttl as usize)?;
+        Ok(())
+    }
+
+    // Remove a proof of work challenge, returning its difficulty when it was still valid
+    pub async fn take_challenge(&self, challenge: &str) -> Result<Option<u32>> {
+        let mut redis_conn = self.redis_pool.get()?;
+        let key = format!("pow:{}", challenge);
+        let (difficulty,): (Option<u32>,) = r2d2_redis::redis::pipe()
+            .atomic()
+            .get(&key)
+            .del(&key)
+            .ignore()
+            .query(&mut *redis_conn)?;
+        Ok(difficulty)
+    }
+
+    // Add `entry` to each rolling window of `windows`, a key and the most entries it may hold in the
+    // last `window_ms` milliseconds. The windows are trimmed, counted and added to in one script,
+    // so concurrent claims from several instances can't exceed a limit. Returns the index of the
+    // first full window and the time of its oldest entry, in milliseconds, without adding the
+    // entry to any window.
+    pub async fn claim_window_entry(
+        &self,
+        windows: &[(String, u64)],
+        entry: &str,
+        window_ms: i64,
+        now_ms: i64,
+    ) -> Result<Option<(usize, i64)>> {
+        let script = r2d2_redis::redis::Script::new(
+            r"
+            for i, key in ipairs(KEYS) do
+                redis.call('ZREMRANGEBYSCORE', key, '-inf', ARGV[1] - ARGV[2])
+                if redis.call('ZCARD', key) >= tonumber(ARGV[3 + i]) then
+                    local oldest = redis.call('ZRANGE', key, 0, 0, 'WITHSCORES')
+                    return {i, tonumber(oldest[2] or ARGV[1])}
+                end
+            end
+            for _, key in ipairs(KEYS) do
+                redis.call('ZADD', key, ARGV[1], ARGV[3])
+                redis.call('PEXPIRE', key, ARGV[2])
+            end
+            return {0, 0}
+            ",
+        );
+        let mut invocation = script.prepare_invoke();
+        invocation.arg(now_ms).arg(window_ms).arg(entry);
+        for (key, limit) in windows {
+            invocation.key(key).arg(*limit);
+        }
+
+        let mut redis_conn = self.redis_pool.get()?;
+        let (exceeded, oldest): (usize, i64) = invocation.invoke(&mut *redis_conn)?;
+        Ok((exceeded > 0).then(|| (exceeded - 1, oldest)))
+    }
+
     pub async fn check_cache(&self, hash: &str, program_address: &str) -> Result<bool> {
         // Try to get the program from the cache and check if the hash matches
         let cache_res = self.get_cache(program_address).await;
@@ -236,9 +428,10 @@ impl DbClient {
     /// Arguments:
     ///
     /// * `program_address`: The `program_address` parameter is a string that represents the address of a
-    /// program. It is used to query the database and check if the program is verified.
+    ///   program. It is used to query the database and check if the program is verified.
     ///
     /// Returns: Whether the program is verified or not.
+    #[tracing::instrument(skip(self))]
     pub async fn check_is_verified(self, program_address: String) -> Result<VerificationResponse> {
         let res = self.get_verified_build(&program_address).await;
         match res {
@@ -247,7 +440,9 @@ impl DbClient {
                     .check_cache(&res.executable_hash, &program_address)
                     .await;
 
-                let build_params = self.get_build_params(&program_address).await?;
+                // The build that produced the verified row, newer builds may be of another
+                // repository or commit, or have failed
+                let build_params = self.get_job(&res.solana_build_id).await?;
 
                 if let Ok(matched) = cache_result {
                     if matched {
@@ -272,6 +467,12 @@ impl DbClient {
                         tracing::info!("On chain hash matches. Returning the cached value.");
                     } else {
                         tracing::info!("On chain hash doesn't match.");
+                        events::hash_drifted(
+                            &program_address,
+                            &res.on_chain_hash,
+                            &on_chain_hash,
+                            &res.executable_hash,
+                        );
                         self.update_onchain_hash(
                             &program_address,
                             &on_chain_hash,
@@ -321,6 +522,7 @@ impl DbClient {
     }
 
     // Get solana_program_builds status by id
+    #[tracing::instrument(skip(self))]
     pub async fn get_job(&self, uid: &str) -> Result<SolanaProgramBuild> {
         use crate::schema::solana_program_builds::dsl::*;
 
@@ -332,19 +534,97 @@ impl DbClient {
             .map_err(Into::into)
     }
 
-    // Update solana_program_builds by id and set status
+    // Update solana_program_builds by id and set status. The first time a build finishes its
+    // finish time is recorded, reverifications of the same build keep it.
+    #[tracing::instrument(skip(self))]
     pub async fn update_build_status(&self, uid: &str, job_status: String) -> Result<usize> {
         use crate::schema::solana_program_builds::dsl::*;
         let conn = &mut self.db_pool.get().await?;
-        diesel::update(solana_program_builds)
+        let finished = job_status != String::from(JobStatus::InProgress);
+        let updated = diesel::update(solana_program_builds)
             .filter(id.eq(uid))
             .set(crate::schema::solana_program_builds::status.eq(job_status))
             .execute(conn)
+            .await?;
+        if finished {
+            diesel::update(solana_program_builds)
+                .filter(id.eq(uid))
+                .filter(finished_at.is_null())
+                .set(finished_at.eq(chrono::Utc::now().naive_utc()))
+                .execute(conn)
+                .await?;
+        }
+        Ok(updated)
+    }
+
+    // Record how long a build waited for a slot and how long it ran
+    #[tracing::instrument(skip(self))]
+    pub async fn record_build_timings(&self, uid: &str, timings: &BuildTimings) -> Result<usize> {
+        use crate::schema::solana_program_builds::dsl::*;
+        let conn = &mut self.db_pool.get().await?;
+        diesel::update(solana_program_builds)
+            .filter(id.eq(uid))
+            .set((
+                queue_wait_seconds.eq(timings.queue_wait.as_secs_f64()),
+                build_duration_seconds.eq(timings.build_duration.as_secs_f64()),
+            ))
+            .execute(conn)
             .await
             .map_err(Into::into)
     }
 
+    // Estimate how long a build takes from the median of recent completed builds of the same
+    // repository, or of builds of the same kind (Anchor or native) when the repository has too
+    // few of them.
+    #[tracing::instrument(skip(self))]
+    pub async fn estimate_build_duration(
+        &self,
+        build_repository: &str,
+        anchor: bool,
+    ) -> Result<Option<f64>> {
+        use diesel::sql_types::{Bool, Text};
+
+        const MIN_REPOSITORY_SAMPLES: i64 = 3;
+        let completed: String = JobStatus::Completed.into();
+        let conn = &mut self.db_pool.get().await?;
+
+        let by_repository = diesel::sql_query(
+            "SELECT percentile_cont(0.5) WITHIN GROUP
+                    (ORDER BY EXTRACT(EPOCH FROM finished_at - created_at)) AS seconds,
+                COUNT(*) AS samples
+            FROM (
+                SELECT created_at, finished_at FROM solana_program_builds
+                WHERE status = $1 AND finished_at IS NOT NULL AND repository = $2
+                ORDER BY finished_at DESC LIMIT 200
+            ) recent",
+        )
+        .bind::<Text, _>(&completed)
+        .bind::<Text, _>(build_repository)
+        .get_result::<BuildDurationEstimate>(conn)
+        .await?;
+        if by_repository.samples >= MIN_REPOSITORY_SAMPLES {
+            return Ok(by_repository.seconds);
+        }
+
+        let by_kind = diesel::sql_query(
+            "SELECT percentile_cont(0.5) WITHIN GROUP
+                    (ORDER BY EXTRACT(EPOCH FROM finished_at - created_at)) AS seconds,
+                COUNT(*) AS samples
+            FROM (
+                SELECT created_at, finished_at FROM solana_program_builds
+                WHERE status = $1 AND finished_at IS NOT NULL AND bpf_flag = $2
+                ORDER BY finished_at DESC LIMIT 200
+            ) recent",
+        )
+        .bind::<Text, _>(&completed)
+        .bind::<Bool, _>(anchor)
+        .get_result::<BuildDurationEstimate>(conn)
+        .await?;
+        Ok(by_kind.seconds.or(by_repository.seconds))
+    }
+
     // get all verified programs from verified_programs table
+    #[tracing::instrument(skip_all)]
     pub async fn get_verified_programs(&self) -> Result<Vec<VerifiedProgram>> {
         use crate::schema::verified_programs::dsl::*;
 
@@ -357,39 +637,832 @@ impl DbClient {
             .map_err(Into::into)
     }
 
-    pub fn reverify_program(self, build_params: SolanaProgramBuild) {
-        let payload = SolanaProgramBuildParams {
-            program_id: build_params.program_id,
-            repository: build_params.repository,
-            commit_hash: build_params.commit_hash,
-            lib_name: build_params.lib_name,
-            base_image: build_params.base_docker_image,
-            mount_path: build_params.mount_path,
-            bpf_flag: Some(build_params.bpf_flag),
-            cargo_args: build_params.cargo_args,
+    // Ids of finished builds whose log is still stored and which finished before `cutoff`. Only
+    // failed builds when `failed_only` is set.
+    pub async fn get_builds_with_logs_before(
+        &self,
+        cutoff: chrono::NaiveDateTime,
+        failed_only: bool,
+    ) -> Result<Vec<String>> {
+        use crate::schema::solana_program_builds::dsl::*;
+        use diesel::BoolExpressionMethods;
+
+        let conn = &mut self.db_pool.get().await?;
+        let mut query = solana_program_builds
+            .select(id)
+            .filter(log_purged_at.is_null())
+            .filter(status.ne(String::from(JobStatus::InProgress)))
+            // Builds from before finished_at was recorded count from their creation
+            .filter(
+                finished_at
+                    .lt(cutoff)
+                    .or(finished_at.is_null().and(created_at.lt(cutoff))),
+            )
+            .into_boxed();
+        if failed_only {
+            query = query.filter(status.eq(String::from(JobStatus::Failed)));
+        }
+
+        query.load::<String>(conn).await.map_err(Into::into)
+    }
+
+    // Program ids and build ids of finished builds whose log is still stored, newest first per
+    // program
+    pub async fn get_builds_with_logs(&self) -> Result<Vec<(String, String)>> {
+        use crate::schema::solana_program_builds::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        solana_program_builds
+            .select((program_id, id))
+            .filter(log_purged_at.is_null())
+            .filter(status.ne(String::from(JobStatus::InProgress)))
+            .order((program_id, created_at.desc()))
+            .load::<(String, String)>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn mark_logs_purged(&self, build_ids: &[String]) -> Result<usize> {
+        use crate::schema::solana_program_builds::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::update(solana_program_builds.filter(id.eq_any(build_ids)))
+            .set(log_purged_at.eq(chrono::Utc::now().naive_utc()))
+            .execute(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    // Every program of verified_programs with the build it was verified with
+    pub async fn get_snapshot_dataset(&self) -> Result<Vec<(VerifiedProgram, SolanaProgramBuild)>> {
+        use crate::schema::solana_program_builds;
+        use crate::schema::verified_programs::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        verified_programs
+            .inner_join(solana_program_builds::table)
+            .order(program_id.asc())
+            .load::<(VerifiedProgram, SolanaProgramBuild)>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn insert_snapshot(&self, snapshot: &DatasetSnapshot) -> Result<usize> {
+        use crate::schema::dataset_snapshots::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::insert_into(dataset_snapshots)
+            .values((
+                name.eq(&snapshot.name),
+                taken_at.eq(snapshot.taken_at),
+                programs.eq(snapshot.programs),
+                size_bytes.eq(snapshot.size_bytes),
+            ))
+            .on_conflict(name)
+            .do_nothing()
+            .execute(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    // Snapshots of the dataset, newest first
+    pub async fn get_snapshots(&self) -> Result<Vec<DatasetSnapshot>> {
+        use crate::schema::dataset_snapshots::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        dataset_snapshots
+            .order(taken_at.desc())
+            .select((name, taken_at, programs, size_bytes))
+            .load::<DatasetSnapshot>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn get_snapshot(&self, snapshot_name: &str) -> Result<Option<DatasetSnapshot>> {
+        use crate::schema::dataset_snapshots::dsl::*;
+        use diesel::OptionalExtension;
+
+        let conn = &mut self.db_pool.get().await?;
+        dataset_snapshots
+            .filter(name.eq(snapshot_name))
+            .select((name, taken_at, programs, size_bytes))
+            .first::<DatasetSnapshot>(conn)
+            .await
+            .optional()
+            .map_err(Into::into)
+    }
+
+    // Whether the crawler found the mainnet program closed
+    #[tracing::instrument(skip(self))]
+    pub async fn is_program_closed(&self, address: &str) -> Result<bool> {
+        use crate::schema::mainnet_programs::dsl::*;
+        use diesel::OptionalExtension;
+
+        let conn = &mut self.db_pool.get().await?;
+        let closed = mainnet_programs
+            .filter(program_address.eq(address))
+            .filter(cluster.eq("mainnet"))
+            .select(is_closed)
+            .first::<bool>(conn)
+            .await
+            .optional()?;
+
+        Ok(closed.unwrap_or(false))
+    }
+
+    // Upgrade authority changes of a mainnet program, newest first
+    pub async fn get_authority_history(&self, address: &str) -> Result<Vec<AuthorityChange>> {
+        use crate::schema::authority_history::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        authority_history
+            .filter(program_address.eq(address))
+            .filter(cluster.eq("mainnet"))
+            .order((observed_at.desc(), id.desc()))
+            .select((change, old_authority, new_authority, slot, observed_at))
+            .load::<AuthorityChange>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn record_history_event(
+        &self,
+        program_address: &str,
+        history_event: HistoryEvent,
+        on_chainhash: &str,
+        executablehash: &str,
+        build_id: Option<&str>,
+    ) -> Result<usize> {
+        use crate::schema::verification_history::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::insert_into(verification_history)
+            .values((
+                program_id.eq(program_address),
+                event.eq(String::from(history_event)),
+                on_chain_hash.eq(on_chainhash),
+                executable_hash.eq(executablehash),
+                solana_build_id.eq(build_id),
+            ))
+            .execute(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    // Latest entries of the verification history, newest first
+    pub async fn get_recent_history(&self, limit: i64) -> Result<Vec<VerificationHistoryEntry>> {
+        use crate::schema::verification_history::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        verification_history
+            .order((recorded_at.desc(), id.desc()))
+            .limit(limit)
+            .load::<VerificationHistoryEntry>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    // Builds with the given status per interval, the `buckets` last intervals up to the current
+    // one. Intervals without builds are included with a count of 0.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_build_time_series(
+        &self,
+        build_status: &str,
+        interval: &str,
+        buckets: i32,
+    ) -> Result<Vec<TimeSeriesPoint>> {
+        self.get_time_series(
+            "SELECT created_at AS at FROM solana_program_builds WHERE status = $3",
+            build_status,
+            interval,
+            buckets,
+        )
+        .await
+    }
+
+    // Programs verified per interval, counting only the builds that matched the on-chain program
+    #[tracing::instrument(skip(self))]
+    pub async fn get_verification_time_series(
+        &self,
+        interval: &str,
+        buckets: i32,
+    ) -> Result<Vec<TimeSeriesPoint>> {
+        self.get_time_series(
+            "SELECT recorded_at AS at FROM verification_history WHERE event = $3",
+            &String::from(HistoryEvent::Verified),
+            interval,
+            buckets,
+        )
+        .await
+    }
+
+    // Counts the rows of `events`, a query selecting an `at` timestamp filtered on `filter`, per
+    // interval
+    async fn get_time_series(
+        &self,
+        events: &str,
+        filter: &str,
+        interval: &str,
+        buckets: i32,
+    ) -> Result<Vec<TimeSeriesPoint>> {
+        use diesel::sql_types::{Integer, Text};
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::sql_query(format!(
+            "WITH params AS (
+                SELECT date_trunc($1, now() AT TIME ZONE 'utc') AS current_bucket,
+                    ('1 ' || $1)::interval AS step
+            )
+            SELECT series.bucket, COUNT(events.at) AS count
+            FROM params,
+                generate_series(current_bucket - ($2 - 1) * step, current_bucket, step)
+                    AS series(bucket)
+            LEFT JOIN ({}) events ON date_trunc($1, events.at) = series.bucket
+            GROUP BY series.bucket
+            ORDER BY series.bucket",
+            events
+        ))
+        .bind::<Text, _>(interval)
+        .bind::<Integer, _>(buckets)
+        .bind::<Text, _>(filter)
+        .load::<TimeSeriesPoint>(conn)
+        .await
+        .map_err(Into::into)
+    }
+
+    // Finished builds with a stored log that isn't in the search index yet, newest first
+    pub async fn get_unindexed_builds(&self, limit: i64) -> Result<Vec<String>> {
+        use diesel::sql_types::BigInt;
+
+        let conn = &mut self.db_pool.get().await?;
+        let builds = diesel::sql_query(
+            "SELECT builds.id
+            FROM solana_program_builds builds
+            WHERE builds.status <> 'in_progress'
+                AND builds.log_purged_at IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM build_log_search search WHERE search.build_id = builds.id
+                )
+            ORDER BY builds.created_at DESC
+            LIMIT $1",
+        )
+        .bind::<BigInt, _>(limit)
+        .load::<BuildId>(conn)
+        .await?;
+        Ok(builds.into_iter().map(|build| build.id).collect())
+    }
+
+    pub async fn index_build_log(&self, build_id: &str, excerpt: &str) -> Result<usize> {
+        use diesel::sql_types::Text;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::sql_query(
+            "INSERT INTO build_log_search (build_id, excerpt) VALUES ($1, $2)
+            ON CONFLICT (build_id) DO UPDATE SET excerpt = $2, indexed_at = NOW()",
+        )
+        .bind::<Text, _>(build_id)
+        .bind::<Text, _>(excerpt)
+        .execute(conn)
+        .await
+        .map_err(Into::into)
+    }
+
+    pub async fn delete_build_log_index(&self, build_ids: &[String]) -> Result<usize> {
+        use diesel::sql_types::{Array, Text};
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::sql_query("DELETE FROM build_log_search WHERE build_id = ANY($1)")
+            .bind::<Array<Text>, _>(build_ids)
+            .execute(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    // Queue a build for the workers, see queue.rs
+    pub async fn enqueue_build(&self, build_id: &str) -> Result<usize> {
+        use diesel::sql_types::Text;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::sql_query(
+            "INSERT INTO build_queue (build_id) VALUES ($1) ON CONFLICT (build_id) DO NOTHING",
+        )
+        .bind::<Text, _>(build_id)
+        .execute(conn)
+        .await
+        .map_err(Into::into)
+    }
+
+    // Claim the oldest queued build that isn't claimed or whose lease expired, for `lease` seconds.
+    // Workers skip the builds others are claiming instead of waiting for them.
+    pub async fn claim_queued_build(&self, worker: &str, lease: u64) -> Result<Option<BuildClaim>> {
+        use diesel::sql_types::{BigInt, Text};
+        use diesel::OptionalExtension;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::sql_query(
+            "UPDATE build_queue
+            SET claimed_by = $1, lease_expires_at = NOW() + $2 * INTERVAL '1 second',
+                attempts = attempts + 1
+            WHERE build_id = (
+                SELECT build_id FROM build_queue
+                WHERE lease_expires_at IS NULL OR lease_expires_at < NOW()
+                ORDER BY queued_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING build_id, attempts",
+        )
+        .bind::<Text, _>(worker)
+        .bind::<BigInt, _>(lease as i64)
+        .get_result::<BuildClaim>(conn)
+        .await
+        .optional()
+        .map_err(Into::into)
+    }
+
+    // Extend the lease of a claimed build, false when the worker no longer holds the claim
+    pub async fn renew_build_lease(
+        &self,
+        build_id: &str,
+        worker: &str,
+        lease: u64,
+    ) -> Result<bool> {
+        use diesel::sql_types::{BigInt, Text};
+
+        let conn = &mut self.db_pool.get().await?;
+        let renewed = diesel::sql_query(
+            "UPDATE build_queue SET lease_expires_at = NOW() + $3 * INTERVAL '1 second'
+            WHERE build_id = $1 AND claimed_by = $2",
+        )
+        .bind::<Text, _>(build_id)
+        .bind::<Text, _>(worker)
+        .bind::<BigInt, _>(lease as i64)
+        .execute(conn)
+        .await?;
+        Ok(renewed > 0)
+    }
+
+    // Release the claims of a worker on some of its builds so other workers take them right away,
+    // returns the builds released
+    pub async fn release_queued_builds(
+        &self,
+        worker: &str,
+        build_ids: &[String],
+    ) -> Result<Vec<String>> {
+        use diesel::sql_types::{Array, Text};
+
+        let conn = &mut self.db_pool.get().await?;
+        let released = diesel::sql_query(
+            "UPDATE build_queue SET claimed_by = NULL, lease_expires_at = NULL
+            WHERE build_id = ANY($1) AND claimed_by = $2
+            RETURNING build_id AS id",
+        )
+        .bind::<Array<Text>, _>(build_ids)
+        .bind::<Text, _>(worker)
+        .load::<BuildId>(conn)
+        .await?;
+        Ok(released.into_iter().map(|build| build.id).collect())
+    }
+
+    pub async fn remove_queued_build(&self, build_id: &str) -> Result<usize> {
+        use diesel::sql_types::Text;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::sql_query("DELETE FROM build_queue WHERE build_id = $1")
+            .bind::<Text, _>(build_id)
+            .execute(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    // Builds whose log matches a web search style query, best matches first
+    pub async fn search_build_logs(&self, query: &str, limit: i64) -> Result<Vec<LogSearchResult>> {
+        use diesel::sql_types::{BigInt, Text};
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::sql_query(
+            "SELECT builds.id AS build_id, builds.program_id, builds.repository, builds.status,
+                builds.created_at,
+                ts_headline('simple', search.excerpt, query,
+                    'StartSel=<<, StopSel=>>, MaxFragments=3') AS headline
+            FROM build_log_search search
+            JOIN solana_program_builds builds ON builds.id = search.build_id,
+                websearch_to_tsquery('simple', $1) AS query
+            WHERE search.document @@ query
+            ORDER BY ts_rank(search.document, query) DESC, builds.created_at DESC
+            LIMIT $2",
+        )
+        .bind::<Text, _>(query)
+        .bind::<BigInt, _>(limit)
+        .load::<LogSearchResult>(conn)
+        .await
+        .map_err(Into::into)
+    }
+
+    // Display names of mainnet programs, from the name in their security.txt
+    #[tracing::instrument(skip_all)]
+    pub async fn get_program_names(&self, addresses: &[String]) -> Result<HashMap<String, String>> {
+        use crate::schema::mainnet_programs::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        let names = mainnet_programs
+            .filter(program_address.eq_any(addresses))
+            .filter(cluster.eq("mainnet"))
+            .filter(project_name.is_not_null())
+            .select((program_address, project_name.assume_not_null()))
+            .load::<(String, String)>(conn)
+            .await?;
+
+        Ok(names
+            .into_iter()
+            .map(|(address, name)| (address, name.trim().to_string()))
+            .filter(|(_, name)| !name.is_empty())
+            .collect())
+    }
+
+    // get the most recent crawler runs, newest first
+    pub async fn get_crawler_runs(
+        &self,
+        run_cluster: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<CrawlerRun>> {
+        use crate::schema::crawler_runs::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        let mut query = crawler_runs.into_boxed();
+        if let Some(run_cluster) = run_cluster {
+            query = query.filter(cluster.eq(run_cluster));
+        }
+
+        query
+            .order(started_at.desc())
+            .limit(limit)
+            .load::<CrawlerRun>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    // Queue a program id or repository URL for the crawler of the given cluster
+    pub async fn insert_crawl_request(
+        &self,
+        crawl_target: &str,
+        crawl_cluster: &str,
+    ) -> Result<CrawlRequest> {
+        use crate::schema::crawl_requests::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::insert_into(crawl_requests)
+            .values((target.eq(crawl_target), cluster.eq(crawl_cluster)))
+            .get_result::<CrawlRequest>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn get_crawl_request(&self, request_id: i32) -> Result<Option<CrawlRequest>> {
+        use crate::schema::crawl_requests::dsl::*;
+        use diesel::OptionalExtension;
+
+        let conn = &mut self.db_pool.get().await?;
+        crawl_requests
+            .filter(id.eq(request_id))
+            .first::<CrawlRequest>(conn)
+            .await
+            .optional()
+            .map_err(Into::into)
+    }
+
+    // get crawled programs by their last error, most recent errors first
+    pub async fn get_programs_with_errors(
+        &self,
+        program_cluster: Option<&str>,
+        category: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<MainnetProgram>> {
+        use crate::schema::mainnet_programs::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        let mut query = mainnet_programs
+            .filter(last_error.is_not_null())
+            .into_boxed();
+        if let Some(program_cluster) = program_cluster {
+            query = query.filter(cluster.eq(program_cluster));
+        }
+        if let Some(category) = category {
+            query = query.filter(last_error.eq(category));
+        }
+
+        query
+            .order(last_error_at.desc())
+            .limit(limit)
+            .load::<MainnetProgram>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn get_build_override(&self, address: &str) -> Result<Option<ProgramBuildOverride>> {
+        use crate::schema::program_build_overrides::dsl::*;
+        use diesel::OptionalExtension;
+
+        let conn = &mut self.db_pool.get().await?;
+        program_build_overrides
+            .filter(program_id.eq(address))
+            .first::<ProgramBuildOverride>(conn)
+            .await
+            .optional()
+            .map_err(Into::into)
+    }
+
+    pub async fn get_build_overrides(&self) -> Result<Vec<ProgramBuildOverride>> {
+        use crate::schema::program_build_overrides::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        program_build_overrides
+            .order(updated_at.desc())
+            .load::<ProgramBuildOverride>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn upsert_build_override(
+        &self,
+        build_override: &ProgramBuildOverride,
+    ) -> Result<ProgramBuildOverride> {
+        use crate::schema::program_build_overrides::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::insert_into(program_build_overrides)
+            .values(build_override)
+            .on_conflict(program_id)
+            .do_update()
+            .set(build_override)
+            .get_result::<ProgramBuildOverride>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn delete_build_override(&self, address: &str) -> Result<usize> {
+        use crate::schema::program_build_overrides::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::delete(program_build_overrides.filter(program_id.eq(address)))
+            .execute(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    // Find the denylist entry blocking the program, repository or commit of a verification request
+    pub async fn find_denylist_entry(
+        &self,
+        payload: &SolanaProgramBuildParams,
+    ) -> Result<Option<DenylistEntry>> {
+        use crate::schema::denylist::dsl::*;
+        use diesel::{BoolExpressionMethods, OptionalExtension};
+
+        let conn = &mut self.db_pool.get().await?;
+        let mut query = denylist
+            .filter(program_id.is_null().or(program_id.eq(&payload.program_id)))
+            .filter(
+                repository
+                    .is_null()
+                    .or(repository.eq(builder::normalize_repository_url(&payload.repository))),
+            )
+            .into_boxed();
+        query = match &payload.commit_hash {
+            Some(commit) => query.filter(commit_hash.is_null().or(commit_hash.eq(commit))),
+            None => query.filter(commit_hash.is_null()),
         };
 
-        let build_id = build_params.id;
+        query
+            .first::<DenylistEntry>(conn)
+            .await
+            .optional()
+            .map_err(Into::into)
+    }
+
+    pub async fn get_denylist(&self) -> Result<Vec<DenylistEntry>> {
+        use crate::schema::denylist::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        denylist
+            .order(created_at.desc())
+            .load::<DenylistEntry>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn insert_denylist_entry(&self, entry: &DenylistParams) -> Result<DenylistEntry> {
+        use crate::schema::denylist::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::insert_into(denylist)
+            .values((
+                program_id.eq(&entry.program_id),
+                repository.eq(entry
+                    .repository
+                    .as_deref()
+                    .map(builder::normalize_repository_url)),
+                commit_hash.eq(entry.commit_hash.as_deref().map(str::to_lowercase)),
+                reason.eq(entry.reason.trim()),
+            ))
+            .get_result::<DenylistEntry>(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn delete_denylist_entry(&self, entry_id: i32) -> Result<usize> {
+        use crate::schema::denylist::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::delete(denylist.filter(id.eq(entry_id)))
+            .execute(conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub fn reverify_program(self, build_params: SolanaProgramBuild) {
+        let build_id = build_params.id.clone();
+        let payload = SolanaProgramBuildParams::from(build_params);
+        let program_id = payload.program_id.clone();
 
         //run task in background
-        tokio::spawn(async move {
-            match builder::verify_build(payload, &build_id).await {
-                Ok(res) => {
-                    let _ = self.insert_or_update_verified_build(&res).await;
-                    let _ = self
-                        .update_build_status(&build_id, JobStatus::Completed.into())
-                        .await;
+        let span = telemetry::background_span(&build_id);
+        tokio::spawn(
+            async move {
+                if shutdown::is_draining() || disk::is_low() {
+                    return;
                 }
-                Err(err) => {
-                    let _ = self
-                        .update_build_status(&build_id, JobStatus::Failed.into())
-                        .await;
-                    tracing::error!("Error verifying build: {:?}", err);
-                    tracing::error!(
-                        "We encountered an unexpected error during the verification process."
-                    );
+                // Every status check of a drifted program asks for a rebuild, one is enough
+                match self
+                    .claim_reverification(&program_id, reverify_cooldown())
+                    .await
+                {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        tracing::info!("{} was reverified recently, skipping", program_id);
+                        return;
+                    }
+                    Err(err) => {
+                        tracing::error!("Error claiming the reverification: {}", err);
+                        return;
+                    }
+                }
+                match self.find_denylist_entry(&payload).await {
+                    Ok(None) => {}
+                    Ok(Some(entry)) => {
+                        tracing::info!(
+                            "Verification of {} is denied: {}",
+                            program_id,
+                            entry.reason
+                        );
+                        return;
+                    }
+                    Err(err) => {
+                        tracing::error!("Error checking the denylist: {}", err);
+                        return;
+                    }
+                }
+                let build_override = match self.get_build_override(&program_id).await {
+                    Ok(Some(build_override)) if build_override.disabled => {
+                        tracing::info!("Verification of {} is disabled", program_id);
+                        return;
+                    }
+                    Ok(build_override) => build_override,
+                    Err(err) => {
+                        tracing::error!("Error getting the build override: {}", err);
+                        return;
+                    }
+                };
+                let _ = self
+                    .submit_build(payload, build_override.as_ref(), &build_id)
+                    .await;
+            }
+            .instrument(span),
+        );
+    }
+
+    // Run the build `build_id` of `payload` when this instance runs builds, otherwise queue it for
+    // the workers. Returns None when the build was queued.
+    pub async fn submit_build(
+        &self,
+        payload: SolanaProgramBuildParams,
+        build_override: Option<&ProgramBuildOverride>,
+        build_id: &str,
+    ) -> Result<Option<VerifiedProgram>> {
+        if role::get().runs_builds() {
+            return self
+                .run_verification(payload, build_override, build_id)
+                .await
+                .map(Some);
+        }
+        if let Err(err) = self.enqueue_build(build_id).await {
+            tracing::error!("Error queueing build {}: {}", build_id, err);
+            let _ = self
+                .update_build_status(build_id, JobStatus::Failed.into())
+                .await;
+            return Err(err);
+        }
+        Ok(None)
+    }
+
+    // Wait for a worker to finish a queued build
+    pub async fn wait_for_build(&self, build_id: &str) -> Result<VerifiedProgram> {
+        let mut ticker = tokio::time::interval(QUEUED_BUILD_POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let build = self.get_job(build_id).await?;
+            match JobStatus::from(build.status) {
+                JobStatus::InProgress => {}
+                JobStatus::Completed => return self.get_verified_build(&build.program_id).await,
+                JobStatus::Failed => {
+                    return Err(ApiError::Custom(format!("Build {} failed", build_id)))
                 }
             }
-        });
+        }
+    }
+
+    // Run the build `build_id` of `payload` and record its outcome
+    pub async fn run_verification(
+        &self,
+        payload: SolanaProgramBuildParams,
+        build_override: Option<&ProgramBuildOverride>,
+        build_id: &str,
+    ) -> Result<VerifiedProgram> {
+        let _running = shutdown::track_build(build_id);
+        let program_id = payload.program_id.clone();
+        let (result, timings) = builder::verify_build(payload, build_override, build_id).await;
+        if let Some(timings) = timings {
+            let _ = self.record_build_timings(build_id, &timings).await;
+        }
+        events::build_finished(&program_id, build_id, &result);
+        match &result {
+            Ok(res) => {
+                let _ = self.insert_or_update_verified_build(res).await;
+                if res.is_verified {
+                    if let Err(err) = self
+                        .record_history_event(
+                            &program_id,
+                            HistoryEvent::Verified,
+                            &res.on_chain_hash,
+                            &res.executable_hash,
+                            Some(build_id),
+                        )
+                        .await
+                    {
+                        tracing::error!(
+                            "Error recording the verification of {}: {}",
+                            program_id,
+                            err
+                        );
+                    }
+                }
+                let _ = self
+                    .update_build_status(build_id, JobStatus::Completed.into())
+                    .await;
+            }
+            Err(err) => {
+                let _ = self
+                    .update_build_status(build_id, JobStatus::Failed.into())
+                    .await;
+                tracing::error!("Error verifying build: {:?}", err);
+            }
+        }
+        result
+    }
+
+    // Run a stored build, queued by an instance with the api role or interrupted by a shutdown,
+    // unless it finished in the meantime
+    pub async fn run_stored_build(&self, build_id: &str) {
+        let build = match self.get_job(build_id).await {
+            Ok(build) if build.status == String::from(JobStatus::InProgress) => build,
+            Ok(_) => return,
+            Err(err) => {
+                tracing::error!("Error getting the build {}: {}", build_id, err);
+                return;
+            }
+        };
+        let build_override = match self.get_build_override(&build.program_id).await {
+            Ok(Some(build_override)) if build_override.disabled => {
+                tracing::info!("Verification of {} is disabled", build.program_id);
+                let _ = self
+                    .update_build_status(build_id, JobStatus::Failed.into())
+                    .await;
+                return;
+            }
+            Ok(build_override) => build_override,
+            Err(err) => {
+                tracing::error!("Error getting the build override: {}", err);
+                return;
+            }
+        };
+
+        tracing::info!("Running build {}", build_id);
+        let _ = self
+            .run_verification(
+                SolanaProgramBuildParams::from(build),
+                build_override.as_ref(),
+                build_id,
+            )
+            .await;
     }
 }
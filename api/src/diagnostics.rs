@@ -0,0 +1,98 @@
+// Compiler errors extracted from the output of failed builds, so users don't have to search
+// megabytes of build output for the one error that failed the build
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+// Errors kept per build, the first ones are the ones that matter
+const MAX_ERRORS: usize = 50;
+// Lines after an error line searched for its location
+const LOCATION_LOOKAHEAD: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildError {
+    // rustc error code, e.g. E0425
+    pub code: Option<String>,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "error[{}]: {}", code, self.message)?,
+            None => write!(f, "error: {}", self.message)?,
+        }
+        if let Some(file) = &self.file {
+            write!(f, " ({}", file)?;
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+struct Patterns {
+    ansi: Regex,
+    error: Regex,
+    location: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        ansi: Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").expect("ANSI pattern is valid"),
+        error: Regex::new(r"^error(?:\[(E\d{4})\])?: (.+)$").expect("Error pattern is valid"),
+        location: Regex::new(r"^\s*--> (.+?):(\d+):\d+$").expect("Location pattern is valid"),
+    })
+}
+
+// Summaries cargo prints after the actual errors
+fn is_summary(message: &str) -> bool {
+    message.starts_with("could not compile")
+        || message.starts_with("aborting due to")
+        || message.starts_with("Compilation failed")
+}
+
+// cargo and rustc errors of a build output, in order of appearance and without duplicates
+pub fn parse_build_errors(output: &str) -> Vec<BuildError> {
+    let patterns = patterns();
+    let lines = output
+        .lines()
+        .map(|line| patterns.ansi.replace_all(line, "").into_owned())
+        .collect::<Vec<_>>();
+
+    let mut errors: Vec<BuildError> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let Some(captures) = patterns.error.captures(line.trim_end()) else {
+            continue;
+        };
+        let message = captures[2].trim().to_string();
+        if is_summary(&message) {
+            continue;
+        }
+
+        let location = lines
+            .iter()
+            .skip(index + 1)
+            .take(LOCATION_LOOKAHEAD)
+            .find_map(|line| patterns.location.captures(line.trim_end()));
+        let error = BuildError {
+            code: captures.get(1).map(|code| code.as_str().to_string()),
+            message,
+            file: location.as_ref().map(|location| location[1].to_string()),
+            line: location.and_then(|location| location[2].parse().ok()),
+        };
+        if !errors.contains(&error) {
+            errors.push(error);
+        }
+        if errors.len() == MAX_ERRORS {
+            break;
+        }
+    }
+    errors
+}
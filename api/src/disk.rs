@@ -0,0 +1,147 @@
+// Free space of the volumes the API writes to, checked every DISK_CHECK_INTERVAL_SECS, 30 seconds
+// by default:
+// - build, the temporary directory the builds run in, on hosts that build locally
+// - logs, STORAGE_PATH with the local storage backend
+// - docker, DOCKER_DATA_PATH when set, the Docker data root mounted into the API container
+// The free space is exported as the disk_free_bytes metric and reported by GET /health/ready.
+// While a volume has less than MIN_FREE_DISK_GB, 5 GB by default, free, new builds are turned
+// away so a full disk doesn't fail them halfway, and admissions resume once space is freed.
+
+use axum::{http::StatusCode, Json};
+use std::env;
+use std::ffi::CString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use crate::builder;
+use crate::metrics;
+use crate::models::{ApiResponse, ErrorResponse, Status, VolumeSpace};
+use crate::storage;
+
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+const DEFAULT_MIN_FREE_GB: f64 = 5.0;
+
+// Result of the last check
+fn last_check() -> &'static RwLock<Vec<VolumeSpace>> {
+    static LAST_CHECK: OnceLock<RwLock<Vec<VolumeSpace>>> = OnceLock::new();
+    LAST_CHECK.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+pub fn spawn_monitor() {
+    let interval = env::var("DISK_CHECK_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_INTERVAL_SECS);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            ticker.tick().await;
+            let was_low = is_low();
+            let space = tokio::task::spawn_blocking(check).await.unwrap_or_default();
+            for volume in &space {
+                metrics::record_disk_free(&volume.volume, volume.free_bytes);
+            }
+            *last_check().write().unwrap() = space;
+
+            match (was_low, is_low()) {
+                (false, true) => tracing::warn!("Low disk space, pausing new builds"),
+                (true, false) => tracing::info!("Disk space recovered, resuming new builds"),
+                _ => {}
+            }
+        }
+    });
+}
+
+// Free space of the monitored volumes as of the last check
+pub fn status() -> Vec<VolumeSpace> {
+    last_check().read().unwrap().clone()
+}
+
+// Whether a monitored volume is low on space, new builds are turned away while it is
+pub fn is_low() -> bool {
+    last_check().read().unwrap().iter().any(|volume| volume.low)
+}
+
+// Response of the verification routes while the disk is low on space
+pub(crate) fn unavailable() -> (StatusCode, Json<ApiResponse>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(
+            ErrorResponse {
+                status: Status::Error,
+                error: "The server is low on disk space, please retry later".to_string(),
+            }
+            .into(),
+        ),
+    )
+}
+
+fn volumes() -> Vec<(&'static str, PathBuf)> {
+    let mut volumes = Vec::new();
+    if builder::backend::builds_locally() {
+        volumes.push(("build", env::temp_dir()));
+    }
+    if let Some(dir) = storage::local_dir() {
+        volumes.push(("logs", dir));
+    }
+    if let Some(dir) = env::var("DOCKER_DATA_PATH")
+        .ok()
+        .filter(|path| !path.is_empty())
+    {
+        volumes.push(("docker", PathBuf::from(dir)));
+    }
+    volumes
+}
+
+fn check() -> Vec<VolumeSpace> {
+    let min_free = env::var("MIN_FREE_DISK_GB")
+        .ok()
+        .and_then(|gb| gb.parse::<f64>().ok())
+        .filter(|gb| *gb >= 0.0)
+        .unwrap_or(DEFAULT_MIN_FREE_GB);
+    let min_free = (min_free * 1e9) as u64;
+
+    volumes()
+        .into_iter()
+        .filter_map(|(volume, path)| {
+            // The logs directory is only created with the first log
+            let existing = path.ancestors().find(|dir| dir.exists()).unwrap_or(&path);
+            match free_space(existing) {
+                Ok(free_bytes) => Some(VolumeSpace {
+                    volume: volume.to_string(),
+                    path: path.display().to_string(),
+                    free_bytes,
+                    low: free_bytes < min_free,
+                }),
+                Err(err) => {
+                    tracing::error!(
+                        "Failed to check the free space of {}: {}",
+                        path.display(),
+                        err
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+// Bytes available to unprivileged users on the filesystem of `path`
+fn free_space(path: &Path) -> io::Result<u64> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // statvfs fills `stat` when it succeeds
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
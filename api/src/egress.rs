@@ -0,0 +1,376 @@
+// Egress policy of the build containers, so the build scripts of a repository can't send data out
+// or download inputs the repository doesn't declare. Enabled with BUILD_EGRESS_NETWORK, the name
+// of a Docker network created with `--internal`, which has no route out of the host:
+// - build containers run on that network, through a `docker` wrapper in front of the runtime
+// - each build gets a proxy on this host, reached at BUILD_EGRESS_PROXY_HOST from the network,
+//   which only connects to the hosts of BUILD_EGRESS_ALLOWLIST and their subdomains
+// - connections the proxy denies are added to the log of the build
+// Images are pulled by the runtime itself, outside of the network. Builds on Kubernetes need a
+// NetworkPolicy instead.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+
+use crate::container;
+use crate::errors::ApiError;
+use crate::Result;
+
+const DEFAULT_ALLOWLIST: &str = "github.com,githubusercontent.com,crates.io,static.rust-lang.org,\
+                                 docker.io,docker.com,ghcr.io";
+
+// Longest request head the proxy reads before giving up on a connection
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+
+// Variables the wrapper reads, set on the solana-verify process of each build
+const NETWORK_VAR: &str = "SOLANA_VERIFY_EGRESS_NETWORK";
+const PROXY_VAR: &str = "SOLANA_VERIFY_EGRESS_PROXY";
+
+struct EgressPolicy {
+    network: String,
+    proxy_host: String,
+    allowlist: Vec<String>,
+    // Directory holding the `docker` wrapper
+    wrapper_dir: PathBuf,
+}
+
+impl EgressPolicy {
+    fn allows(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.allowlist.iter().any(|allowed| {
+            host == *allowed
+                || host
+                    .strip_suffix(allowed.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+}
+
+static POLICY: OnceLock<Option<EgressPolicy>> = OnceLock::new();
+
+// Set up the configured policy. Panics on an invalid configuration so it surfaces at startup.
+pub fn init() {
+    policy();
+}
+
+fn policy() -> Option<&'static EgressPolicy> {
+    POLICY
+        .get_or_init(|| from_env().expect("Invalid build egress configuration"))
+        .as_ref()
+}
+
+fn from_env() -> Result<Option<EgressPolicy>> {
+    let Some(network) = env::var("BUILD_EGRESS_NETWORK")
+        .ok()
+        .filter(|network| !network.is_empty())
+    else {
+        return Ok(None);
+    };
+    let proxy_host = env::var("BUILD_EGRESS_PROXY_HOST")
+        .ok()
+        .filter(|host| !host.is_empty())
+        .ok_or_else(|| {
+            ApiError::Custom(
+                "BUILD_EGRESS_PROXY_HOST must be set with BUILD_EGRESS_NETWORK".to_string(),
+            )
+        })?;
+    let allowlist = env::var("BUILD_EGRESS_ALLOWLIST")
+        .ok()
+        .filter(|allowlist| !allowlist.is_empty())
+        .unwrap_or_else(|| DEFAULT_ALLOWLIST.to_string())
+        .split(',')
+        .map(|host| host.trim().trim_end_matches('.').to_ascii_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect();
+
+    Ok(Some(EgressPolicy {
+        network,
+        proxy_host,
+        allowlist,
+        wrapper_dir: write_wrapper()?,
+    }))
+}
+
+// Write the `docker` wrapper running the containers of `docker run` on the network, with the
+// proxy of the build
+fn write_wrapper() -> Result<PathBuf> {
+    let dir = env::temp_dir().join("solana-verify-egress");
+    std::fs::create_dir_all(&dir)?;
+    let cli = container::get().cli().display().to_string();
+    let cli = format!("'{}'", cli.replace('\'', r"'\''"));
+    let script = format!(
+        r#"#!/bin/sh
+if [ "$1" = "run" ]; then
+    shift
+    exec {cli} run --network "${network}" \
+        -e HTTP_PROXY="${proxy}" -e HTTPS_PROXY="${proxy}" \
+        -e http_proxy="${proxy}" -e https_proxy="${proxy}" \
+        -e CARGO_HTTP_PROXY="${proxy}" "$@"
+fi
+exec {cli} "$@"
+"#,
+        cli = cli,
+        network = NETWORK_VAR,
+        proxy = PROXY_VAR,
+    );
+    let wrapper = dir.join("docker");
+    std::fs::write(&wrapper, script)?;
+    std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755))?;
+    Ok(dir)
+}
+
+// Check that the network of the builds exists and has no route out
+pub async fn check() -> Option<std::result::Result<String, String>> {
+    let policy = policy()?;
+    let output = container::get()
+        .command()
+        .args(["network", "inspect", "--format", "{{.Internal}}"])
+        .arg(&policy.network)
+        .output()
+        .await;
+    Some(match output {
+        Ok(output) if !output.status.success() => Err(format!(
+            "network {} not found: {}",
+            policy.network,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+        Ok(output) if String::from_utf8_lossy(&output.stdout).trim() != "true" => Err(format!(
+            "network {} must be created with --internal",
+            policy.network
+        )),
+        Ok(_) => Ok(format!(
+            "builds run on {} and may reach {}",
+            policy.network,
+            policy.allowlist.join(", ")
+        )),
+        Err(err) => Err(format!(
+            "failed to inspect network {}: {}",
+            policy.network, err
+        )),
+    })
+}
+
+// Proxy of a build, stopped when dropped
+pub struct EgressProxy {
+    url: String,
+    // Denied destinations and how many times they were
+    denied: Arc<Mutex<BTreeMap<String, usize>>>,
+    accept: JoinHandle<()>,
+}
+
+// Start the proxy of a build, None when the policy isn't enabled
+pub async fn start_proxy(build_id: &str) -> Result<Option<EgressProxy>> {
+    let Some(policy) = policy() else {
+        return Ok(None);
+    };
+    let listener = TcpListener::bind(("0.0.0.0", 0)).await?;
+    let url = format!(
+        "http://{}:{}",
+        policy.proxy_host,
+        listener.local_addr()?.port()
+    );
+    let denied = Arc::new(Mutex::new(BTreeMap::new()));
+
+    let build_id = build_id.to_string();
+    let accept_denied = denied.clone();
+    let accept = tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let build_id = build_id.clone();
+            let denied = accept_denied.clone();
+            tokio::spawn(async move {
+                if let Err(err) = proxy(policy, stream, &build_id, &denied).await {
+                    tracing::debug!(
+                        "Egress proxy connection of build {} failed: {}",
+                        build_id,
+                        err
+                    );
+                }
+            });
+        }
+    });
+    Ok(Some(EgressProxy {
+        url,
+        denied,
+        accept,
+    }))
+}
+
+impl EgressProxy {
+    // Make the containers `cmd` runs go through this proxy
+    pub fn configure(&self, cmd: &mut Command) {
+        let Some(policy) = policy() else {
+            return;
+        };
+        let build_path = container::get().build_path();
+        let path = std::iter::once(policy.wrapper_dir.clone()).chain(env::split_paths(&build_path));
+        if let Ok(path) = env::join_paths(path) {
+            cmd.env("PATH", path);
+        }
+        cmd.env(NETWORK_VAR, &policy.network);
+        cmd.env(PROXY_VAR, &self.url);
+    }
+
+    // Lines for the build log about the denied connections, empty when there were none
+    pub fn report(&self) -> String {
+        self.denied
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(destination, count)| {
+                format!(
+                    "Egress policy denied connections to {}, attempts: {}\n",
+                    destination, count
+                )
+            })
+            .collect()
+    }
+}
+
+impl Drop for EgressProxy {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
+
+// Serve a connection to the proxy: CONNECT tunnels and plain HTTP requests to allowed hosts
+async fn proxy(
+    policy: &EgressPolicy,
+    mut client: TcpStream,
+    build_id: &str,
+    denied: &Mutex<BTreeMap<String, usize>>,
+) -> std::io::Result<()> {
+    let head = read_head(&mut client).await?;
+    let request_line = String::from_utf8_lossy(&head);
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let destination = if method == "CONNECT" {
+        split_host_port(target, 443)
+    } else {
+        reqwest::Url::parse(target).ok().and_then(|url| {
+            Some((
+                url.host_str()?.trim_matches(['[', ']']).to_string(),
+                url.port_or_known_default()?,
+            ))
+        })
+    };
+    let Some((host, port)) = destination.filter(|(host, _)| policy.allows(host)) else {
+        tracing::warn!("Build {} denied egress to {}", build_id, target);
+        *denied
+            .lock()
+            .unwrap()
+            .entry(target.to_string())
+            .or_default() += 1;
+        client
+            .write_all(b"HTTP/1.1 403 Forbidden\r\nConnection: close\r\n\r\n")
+            .await?;
+        return Ok(());
+    };
+
+    let mut upstream = match TcpStream::connect((host.as_str(), port)).await {
+        Ok(upstream) => upstream,
+        Err(err) => {
+            client
+                .write_all(b"HTTP/1.1 502 Bad Gateway\r\nConnection: close\r\n\r\n")
+                .await?;
+            return Err(err);
+        }
+    };
+    if method == "CONNECT" {
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+    } else {
+        // Every request of the connection goes to the host of the first one
+        upstream.write_all(&head).await?;
+    }
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+// Read up to the end of the head of the request, along with what was sent after it
+async fn read_head(client: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut buf = [0; 4096];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_HEAD_SIZE {
+            return Err(std::io::Error::other("request head too large"));
+        }
+        let read = client.read(&mut buf).await?;
+        if read == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    Ok(head)
+}
+
+// Split `host:port`, or `[ipv6]:port`, with `default_port` when there's no port
+fn split_host_port(target: &str, default_port: u16) -> Option<(String, u16)> {
+    let (host, port) = match target.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse().ok()?),
+        _ => (target, default_port),
+    };
+    let host = host.trim_matches(['[', ']']);
+    (!host.is_empty()).then(|| (host.to_string(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowlist: &[&str]) -> EgressPolicy {
+        EgressPolicy {
+            network: "builds".to_string(),
+            proxy_host: "host.docker.internal".to_string(),
+            allowlist: allowlist.iter().map(|host| host.to_string()).collect(),
+            wrapper_dir: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn test_allows() {
+        let policy = policy(&["github.com", "crates.io"]);
+        assert!(policy.allows("github.com"));
+        assert!(policy.allows("codeload.github.com"));
+        assert!(policy.allows("GitHub.com."));
+        assert!(policy.allows("index.crates.io"));
+
+        assert!(!policy.allows("evilgithub.com"));
+        assert!(!policy.allows("github.com.evil.com"));
+        assert!(!policy.allows("com"));
+        assert!(!policy.allows(""));
+        assert!(!policy.allows("169.254.169.254"));
+    }
+
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(
+            split_host_port("github.com:443", 80),
+            Some(("github.com".to_string(), 443))
+        );
+        assert_eq!(
+            split_host_port("github.com", 443),
+            Some(("github.com".to_string(), 443))
+        );
+        assert_eq!(
+            split_host_port("[::1]:8080", 443),
+            Some(("::1".to_string(), 8080))
+        );
+        assert_eq!(
+            split_host_port("[::1]", 443),
+            Some(("::1".to_string(), 443))
+        );
+        assert_eq!(split_host_port("github.com:https", 443), None);
+        assert_eq!(split_host_port(":443", 443), None);
+    }
+}
@@ -29,6 +29,15 @@ pub enum ApiError {
 
     #[error(transparent)]
     DbPool(#[from] diesel_async::pooled_connection::deadpool::PoolError),
+
+    #[error(transparent)]
+    Storage(#[from] object_store::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
 }
 
 pub enum ErrorMessages {
@@ -0,0 +1,157 @@
+// Verification state changes published to NATS for downstream indexers. Events are published on
+// `<EVENTS_SUBJECT_PREFIX>.<event type>` when NATS_URL is set, and dropped otherwise.
+
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use std::env;
+use std::sync::OnceLock;
+
+use crate::models::VerifiedProgram;
+use crate::Result;
+
+const DEFAULT_SUBJECT_PREFIX: &str = "verified_programs";
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    ProgramVerified {
+        on_chain_hash: String,
+        executable_hash: String,
+    },
+    ProgramUnverified {
+        on_chain_hash: String,
+        executable_hash: String,
+    },
+    VerificationFailed {
+        error: String,
+    },
+    HashDrift {
+        previous_hash: String,
+        on_chain_hash: String,
+    },
+    ProgramClosed,
+}
+
+impl EventKind {
+    fn name(&self) -> &'static str {
+        match self {
+            EventKind::ProgramVerified { .. } => "program_verified",
+            EventKind::ProgramUnverified { .. } => "program_unverified",
+            EventKind::VerificationFailed { .. } => "verification_failed",
+            EventKind::HashDrift { .. } => "hash_drift",
+            EventKind::ProgramClosed => "program_closed",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Event {
+    pub program_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_id: Option<String>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+    pub timestamp: NaiveDateTime,
+}
+
+struct Publisher {
+    client: async_nats::Client,
+    subject_prefix: String,
+}
+
+static PUBLISHER: OnceLock<Publisher> = OnceLock::new();
+
+// Connect to NATS_URL. Without it events are not published.
+pub async fn connect() {
+    let Some(url) = env::var("NATS_URL").ok().filter(|url| !url.is_empty()) else {
+        return;
+    };
+    let client = async_nats::connect(&url)
+        .await
+        .expect("Failed to connect to NATS_URL");
+    let subject_prefix = env::var("EVENTS_SUBJECT_PREFIX")
+        .ok()
+        .filter(|prefix| !prefix.is_empty())
+        .unwrap_or_else(|| DEFAULT_SUBJECT_PREFIX.to_string());
+
+    tracing::info!("Publishing verification events to {}", url);
+    let _ = PUBLISHER.set(Publisher {
+        client,
+        subject_prefix,
+    });
+}
+
+// Publish in the background so a slow bus never holds up verification
+pub fn publish(program_id: &str, build_id: Option<&str>, kind: EventKind) {
+    let Some(publisher) = PUBLISHER.get() else {
+        return;
+    };
+
+    let subject = format!("{}.{}", publisher.subject_prefix, kind.name());
+    let event = Event {
+        program_id: program_id.to_string(),
+        build_id: build_id.map(str::to_string),
+        kind,
+        timestamp: Utc::now().naive_utc(),
+    };
+    let payload = match serde_json::to_vec(&event) {
+        Ok(payload) => payload,
+        Err(err) => {
+            tracing::error!("Failed to serialize {} event: {}", subject, err);
+            return;
+        }
+    };
+
+    let client = publisher.client.clone();
+    tokio::spawn(async move {
+        if let Err(err) = client.publish(subject.clone(), payload.into()).await {
+            tracing::error!("Failed to publish {} event: {}", subject, err);
+        }
+    });
+}
+
+// Publish the outcome of a build
+pub fn build_finished(program_id: &str, build_id: &str, result: &Result<VerifiedProgram>) {
+    let kind = match result {
+        Ok(verified) if verified.is_verified => EventKind::ProgramVerified {
+            on_chain_hash: verified.on_chain_hash.clone(),
+            executable_hash: verified.executable_hash.clone(),
+        },
+        Ok(verified) => EventKind::ProgramUnverified {
+            on_chain_hash: verified.on_chain_hash.clone(),
+            executable_hash: verified.executable_hash.clone(),
+        },
+        Err(err) => EventKind::VerificationFailed {
+            error: err.to_string(),
+        },
+    };
+    publish(program_id, Some(build_id), kind);
+}
+
+// Publish an on-chain hash change of a verified program. A program whose new hash doesn't match
+// its build anymore is also reported as unverified.
+pub fn hash_drifted(
+    program_id: &str,
+    previous_hash: &str,
+    on_chain_hash: &str,
+    executable_hash: &str,
+) {
+    publish(
+        program_id,
+        None,
+        EventKind::HashDrift {
+            previous_hash: previous_hash.to_string(),
+            on_chain_hash: on_chain_hash.to_string(),
+        },
+    );
+    if on_chain_hash != executable_hash {
+        publish(
+            program_id,
+            None,
+            EventKind::ProgramUnverified {
+                on_chain_hash: on_chain_hash.to_string(),
+                executable_hash: executable_hash.to_string(),
+            },
+        );
+    }
+}
@@ -0,0 +1,117 @@
+// Maintenance of the images builds run in, on hosts that build locally. Builds pulling their image
+// cold take minutes longer, and the images of past builds fill the disk of the host. Periodically:
+// - pulls PREPULL_IMAGES, comma separated references such as
+//   solanafoundation/solana-verifiable-build:1.18.26, and the base images of the build overrides
+// - with IMAGE_DISK_BUDGET_GB set, removes unused images, oldest first, while the images take more
+//   space than the budget. Dangling layers go first, pre-pulled images are kept.
+// Runs every IMAGE_MAINTENANCE_INTERVAL_SECS, 6 hours by default, starting at startup.
+
+use std::env;
+use std::time::Duration;
+
+use crate::container;
+use crate::db::DbClient;
+use crate::Result;
+
+const DEFAULT_INTERVAL_SECS: u64 = 6 * 60 * 60;
+
+// `db` provides the base images of the build overrides, build workers run without one
+pub fn spawn_maintenance(db: Option<DbClient>) {
+    let interval = env::var("IMAGE_MAINTENANCE_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_INTERVAL_SECS);
+    let budget = env::var("IMAGE_DISK_BUDGET_GB")
+        .ok()
+        .and_then(|gb| gb.parse::<f64>().ok())
+        .filter(|gb| *gb > 0.0)
+        .map(|gb| (gb * 1e9) as u64);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            ticker.tick().await;
+            let images = match prepull_images(db.as_ref()).await {
+                Ok(images) => images,
+                Err(err) => {
+                    tracing::error!("Failed to list the images to pre-pull: {}", err);
+                    continue;
+                }
+            };
+            pull_images(&images).await;
+            if let Some(budget) = budget {
+                match prune_images(&images, budget).await {
+                    Ok(0) => {}
+                    Ok(removed) => tracing::info!("Removed {} build images", removed),
+                    Err(err) => tracing::error!("Build image pruning failed: {}", err),
+                }
+            }
+        }
+    });
+}
+
+async fn prepull_images(db: Option<&DbClient>) -> Result<Vec<String>> {
+    let mut images = env::var("PREPULL_IMAGES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|image| !image.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if let Some(db) = db {
+        images.extend(
+            db.get_build_overrides()
+                .await?
+                .into_iter()
+                .filter_map(|build_override| build_override.base_image),
+        );
+    }
+    images.sort();
+    images.dedup();
+    Ok(images)
+}
+
+async fn pull_images(images: &[String]) {
+    for image in images {
+        match container::get().pull(image).await {
+            Ok(()) => tracing::info!("Pulled build image {}", image),
+            Err(err) => tracing::error!("Failed to pull build image {}: {}", image, err),
+        }
+    }
+}
+
+// Remove images until they fit in `budget` bytes, returns how many were removed
+async fn prune_images(keep: &[String], budget: u64) -> Result<usize> {
+    let runtime = container::get();
+    if runtime.image_disk_usage().await? <= budget {
+        return Ok(0);
+    }
+    runtime.prune_dangling_images().await?;
+
+    let mut removed = 0;
+    let mut usage = runtime.image_disk_usage().await?;
+    for image in runtime.images().await?.iter().rev() {
+        if usage <= budget {
+            break;
+        }
+        if keep.iter().any(|reference| image.matches(reference)) {
+            continue;
+        }
+        // Images used by a container, such as a running build, can't be removed
+        if let Err(err) = runtime.remove_image(image).await {
+            tracing::debug!("Keeping build image {}: {}", image.reference(), err);
+            continue;
+        }
+        removed += 1;
+        usage = runtime.image_disk_usage().await?;
+    }
+    if usage > budget {
+        tracing::warn!(
+            "Build images take {} bytes, over the budget of {} bytes",
+            usage,
+            budget
+        );
+    }
+    Ok(removed)
+}
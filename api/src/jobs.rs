@@ -0,0 +1,252 @@
+use chrono::Utc;
+use std::env;
+use std::time::Duration;
+
+use crate::db::DbClient;
+use crate::events::{self, EventKind};
+use crate::logging::{self, LogRetention};
+use crate::models::{HistoryEvent, VerifiedProgram};
+use crate::program_hash::get_on_chain_hashes;
+use crate::Result;
+
+// Check for hash drift once a day unless configured otherwise
+const DEFAULT_HASH_DRIFT_CHECK_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+// How often instances check whether a claimed job is due
+const JOB_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// Index new build logs every minute unless configured otherwise, in batches of this size
+const DEFAULT_LOG_INDEX_INTERVAL_SECS: u64 = 60;
+const LOG_INDEX_BATCH_SIZE: i64 = 100;
+
+/// Periodically compares the stored on-chain hash of every verified program with the
+/// current on-chain hash and starts a rebuild only for programs that drifted, so results
+/// don't stay stale until someone happens to query `/status`.
+pub fn spawn_hash_drift_check(db: DbClient) {
+    let interval = env::var("HASH_DRIFT_CHECK_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_HASH_DRIFT_CHECK_INTERVAL_SECS);
+
+    tokio::spawn(async move {
+        let mut ticker =
+            tokio::time::interval(JOB_CHECK_INTERVAL.min(Duration::from_secs(interval)));
+        loop {
+            ticker.tick().await;
+            if !claim_run(&db, "hash_drift", interval).await {
+                continue;
+            }
+            match check_hash_drift(&db).await {
+                Ok(drifted) => {
+                    tracing::info!("Hash drift check finished. {} programs drifted", drifted)
+                }
+                Err(err) => tracing::error!("Hash drift check failed: {}", err),
+            }
+        }
+    });
+}
+
+// Claim the next run of `job`, held for `interval` seconds in Redis. Ticking instances only run the
+// job when the previous run is an interval old, so restarts don't delay or repeat it.
+async fn claim_run(db: &DbClient, job: &str, interval: u64) -> bool {
+    db.claim_job(job, interval).await.unwrap_or_else(|err| {
+        tracing::error!("Failed to claim the {} job: {}", job, err);
+        false
+    })
+}
+
+async fn check_hash_drift(db: &DbClient) -> Result<usize> {
+    let verified_programs = db.get_verified_programs().await?;
+    let program_ids = verified_programs
+        .iter()
+        .map(|program| program.program_id.clone())
+        .collect::<Vec<String>>();
+    let on_chain_hashes = get_on_chain_hashes(&program_ids).await;
+    let mut drifted = 0;
+
+    // A failure on one program doesn't stop the check of the others
+    for (program, on_chain_hash) in verified_programs.into_iter().zip(on_chain_hashes) {
+        match check_program_drift(db, &program, on_chain_hash).await {
+            Ok(true) => drifted += 1,
+            Ok(false) => {}
+            Err(err) => {
+                tracing::error!("Hash drift check of {} failed: {}", program.program_id, err)
+            }
+        }
+    }
+
+    Ok(drifted)
+}
+
+// Compare the stored on-chain hash of a program with the current one and rebuild it when it
+// drifted. Returns whether it drifted.
+async fn check_program_drift(
+    db: &DbClient,
+    program: &VerifiedProgram,
+    on_chain_hash: Result<String>,
+) -> Result<bool> {
+    let on_chain_hash = match on_chain_hash {
+        Ok(hash) => hash,
+        Err(err) => {
+            // A program closed since it was verified has no on-chain hash anymore. Marking it
+            // unverified reports the closure once.
+            if db.is_program_closed(&program.program_id).await? {
+                tracing::warn!("Program {} has been closed", program.program_id);
+                db.update_onchain_hash(&program.program_id, &program.on_chain_hash, false)
+                    .await?;
+                events::publish(&program.program_id, None, EventKind::ProgramClosed);
+                return Ok(false);
+            }
+            tracing::error!(
+                "Failed to get on-chain hash for {}: {}",
+                program.program_id,
+                err
+            );
+            return Ok(false);
+        }
+    };
+
+    if on_chain_hash == program.on_chain_hash {
+        return Ok(false);
+    }
+
+    events::hash_drifted(
+        &program.program_id,
+        &program.on_chain_hash,
+        &on_chain_hash,
+        &program.executable_hash,
+    );
+    tracing::warn!(
+        "On chain hash drifted for program: {}. Starting a new build",
+        program.program_id
+    );
+    db.update_onchain_hash(
+        &program.program_id,
+        &on_chain_hash,
+        on_chain_hash == program.executable_hash,
+    )
+    .await?;
+    if let Err(err) = db
+        .record_history_event(
+            &program.program_id,
+            HistoryEvent::Drifted,
+            &on_chain_hash,
+            &program.executable_hash,
+            Some(&program.solana_build_id),
+        )
+        .await
+    {
+        tracing::error!(
+            "Error recording the hash drift of {}: {}",
+            program.program_id,
+            err
+        );
+    }
+
+    let build_params = db.get_job(&program.solana_build_id).await?;
+    db.clone().reverify_program(build_params);
+    Ok(true)
+}
+
+/// Periodically deletes the stored logs of builds past the [`LogRetention`] policy, and marks
+/// their builds so `/logs` reports the log as purged. Nothing runs without a policy.
+pub fn spawn_log_purge(db: DbClient) {
+    let retention = LogRetention::get();
+    if !retention.is_enabled() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let interval = retention.purge_interval_secs;
+        let mut ticker =
+            tokio::time::interval(JOB_CHECK_INTERVAL.min(Duration::from_secs(interval)));
+        loop {
+            ticker.tick().await;
+            if !claim_run(&db, "log_purge", interval).await {
+                continue;
+            }
+            match purge_logs(&db, retention).await {
+                Ok(purged) => tracing::info!("Log purge finished. {} logs purged", purged),
+                Err(err) => tracing::error!("Log purge failed: {}", err),
+            }
+        }
+    });
+}
+
+async fn purge_logs(db: &DbClient, retention: &LogRetention) -> Result<usize> {
+    let days_ago = |days: u32| Utc::now().naive_utc() - Duration::from_secs(days as u64 * 86400);
+    let mut expired = Vec::new();
+    if let Some(days) = retention.retention_days {
+        expired.extend(
+            db.get_builds_with_logs_before(days_ago(days), false)
+                .await?,
+        );
+    }
+    if let Some(days) = retention.failed_retention_days {
+        expired.extend(db.get_builds_with_logs_before(days_ago(days), true).await?);
+    }
+
+    if let Some(max_bytes) = retention.max_bytes_per_program {
+        let mut program = String::new();
+        let mut program_bytes = 0;
+        // Newest first, so the oldest logs of a program are the ones over the limit
+        for (program_id, build_id) in db.get_builds_with_logs().await? {
+            if program_id != program {
+                program = program_id;
+                program_bytes = 0;
+            }
+            program_bytes += logging::get_build_log_size(&build_id).await?.unwrap_or(0);
+            if program_bytes > max_bytes {
+                expired.push(build_id);
+            }
+        }
+    }
+
+    expired.sort();
+    expired.dedup();
+    for build_id in &expired {
+        logging::delete_build_log(build_id).await?;
+    }
+    db.mark_logs_purged(&expired).await?;
+    db.delete_build_log_index(&expired).await?;
+    Ok(expired.len())
+}
+
+/// Periodically adds the logs of finished builds to the search index of `/admin/logs/search`.
+/// Builds are indexed newest first, so logs stored before the index existed are backfilled
+/// without holding up recent ones.
+pub fn spawn_log_indexer(db: DbClient) {
+    let interval = env::var("LOG_INDEX_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_LOG_INDEX_INTERVAL_SECS);
+
+    tokio::spawn(async move {
+        let mut ticker =
+            tokio::time::interval(JOB_CHECK_INTERVAL.min(Duration::from_secs(interval)));
+        loop {
+            ticker.tick().await;
+            if !claim_run(&db, "log_index", interval).await {
+                continue;
+            }
+            match index_logs(&db).await {
+                Ok(0) => {}
+                Ok(indexed) => tracing::info!("Indexed {} build logs", indexed),
+                Err(err) => tracing::error!("Build log indexing failed: {}", err),
+            }
+        }
+    });
+}
+
+async fn index_logs(db: &DbClient) -> Result<usize> {
+    let builds = db.get_unindexed_builds(LOG_INDEX_BATCH_SIZE).await?;
+    for build_id in &builds {
+        // Builds without a stored log are indexed empty so they aren't picked up again
+        let log = logging::get_build_log(build_id).await?.unwrap_or_default();
+        db.index_build_log(build_id, &logging::search_excerpt(&log))
+            .await?;
+    }
+    Ok(builds.len())
+}
@@ -0,0 +1,37 @@
+// The verification engine: build pipeline, on-chain hash retrieval and database layer. The HTTP
+// server in main.rs is one consumer, other services can embed the same pipeline.
+
+extern crate diesel;
+extern crate tracing;
+
+pub mod auth;
+pub mod builder;
+pub mod container;
+pub mod db;
+pub mod diagnostics;
+pub mod disk;
+pub mod egress;
+pub mod errors;
+pub mod events;
+pub mod images;
+pub mod jobs;
+pub mod logging;
+pub mod metrics;
+pub mod models;
+pub mod pow;
+pub mod program_hash;
+pub mod queue;
+pub mod quota;
+pub mod rate_limit;
+pub mod reload;
+pub mod role;
+pub mod routes;
+pub mod schema;
+pub mod self_check;
+pub mod shutdown;
+pub mod snapshots;
+pub mod storage;
+pub mod telemetry;
+pub mod validation;
+
+pub type Result<T> = std::result::Result<T, errors::ApiError>;
@@ -1,35 +1,65 @@
 use dotenv::dotenv;
-use routes::create_router;
 use std::env;
 use std::net::SocketAddr;
-
-extern crate diesel;
-extern crate tracing;
-
-mod builder;
-mod db;
-mod errors;
-mod models;
-mod routes;
-mod schema;
-
-pub type Result<T> = std::result::Result<T, errors::ApiError>;
+use verified_programs_api::role::{self, Role};
+use verified_programs_api::routes::create_router;
+use verified_programs_api::{
+    builder, container, db, disk, egress, events, images, jobs, metrics, queue, reload, self_check,
+    shutdown, snapshots, storage, telemetry,
+};
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    tracing_subscriber::fmt::init();
+    role::init();
+    let role = role::get();
+    telemetry::init();
+    metrics::install();
+    storage::init();
+    if role.runs_builds() {
+        builder::backend::init();
+        container::init();
+        egress::init();
+    }
+    self_check::run().await;
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL not set in .env file");
     let redis_url = env::var("REDIS_URL").expect("REDIS_URL not set in .env file");
 
     let db_client = db::DbClient::new(&database_url, &redis_url);
-    let app = create_router(db_client);
+    events::connect().await;
+    reload::spawn_sighup_listener();
+    if role.runs_builds() {
+        jobs::spawn_hash_drift_check(db_client.clone());
+        jobs::spawn_log_purge(db_client.clone());
+        jobs::spawn_log_indexer(db_client.clone());
+        snapshots::spawn_job(db_client.clone());
+        shutdown::resume_interrupted_builds(db_client.clone());
+        disk::spawn_monitor();
+        if builder::backend::builds_locally() {
+            images::spawn_maintenance(Some(db_client.clone()));
+        }
+    }
+    if role == Role::Worker {
+        queue::spawn_consumer(db_client.clone());
+        tracing::info!("Running as a build worker");
+        shutdown::listen().await;
+        shutdown::drain(&db_client).await;
+        return;
+    }
+    let app = create_router(db_client.clone());
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-    tracing::info!("Listening on {}", addr);
+    tracing::info!("Listening on {} as {:?}", addr, role);
 
-    axum::Server::bind(&addr)
+    // On shutdown the server stops accepting connections while the running builds drain, the
+    // requests of /verify_sync waiting for their build included
+    let server = axum::Server::bind(&addr)
         .serve(app.into_make_service_with_connect_info::<SocketAddr>())
-        .await
-        .unwrap();
+        .with_graceful_shutdown(shutdown::requested());
+    let server = tokio::spawn(server);
+
+    tokio::select! {
+        result = server => result.expect("Server task failed").expect("Server error"),
+        _ = shutdown::listen() => shutdown::drain(&db_client).await,
+    }
 }
@@ -0,0 +1,119 @@
+// Prometheus metrics of the verification pipeline, served at GET /metrics
+
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+const QUEUE_DEPTH: &str = "verification_queue_depth";
+const QUEUE_WAIT: &str = "verification_queue_wait_seconds";
+const ACTIVE_BUILDS: &str = "verification_active_builds";
+const STAGE_DURATION: &str = "verification_stage_duration_seconds";
+const BUILDS: &str = "verification_builds_total";
+const DISK_FREE: &str = "disk_free_bytes";
+
+// Builds take minutes, so the buckets go up to an hour
+const DURATION_BUCKETS: [f64; 12] = [
+    0.1, 0.5, 1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0,
+];
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+// Same as the queue depth gauge, readable by the service itself
+static QUEUED_BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn install() {
+    HANDLE.get_or_init(|| {
+        PrometheusBuilder::new()
+            .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), &DURATION_BUCKETS)
+            .expect("Duration buckets are not empty")
+            .install_recorder()
+            .expect("Failed to install the metrics recorder")
+    });
+}
+
+// Metrics in the Prometheus text format
+pub fn render() -> String {
+    HANDLE
+        .get()
+        .map(PrometheusHandle::render)
+        .unwrap_or_default()
+}
+
+// Number of builds waiting for a build slot
+pub fn queue_depth() -> usize {
+    QUEUED_BUILDS.load(Ordering::Relaxed)
+}
+
+// A build waiting for a build slot. Counted in the queue depth until it starts or is dropped.
+pub struct QueuedBuild {
+    queued_at: Instant,
+}
+
+impl QueuedBuild {
+    pub fn enqueue() -> Self {
+        gauge!(QUEUE_DEPTH).increment(1.0);
+        QUEUED_BUILDS.fetch_add(1, Ordering::Relaxed);
+        Self {
+            queued_at: Instant::now(),
+        }
+    }
+
+    pub fn start(self) -> ActiveBuild {
+        let queue_wait = self.queued_at.elapsed();
+        histogram!(QUEUE_WAIT).record(queue_wait.as_secs_f64());
+        gauge!(ACTIVE_BUILDS).increment(1.0);
+        ActiveBuild {
+            queue_wait,
+            started_at: Instant::now(),
+        }
+    }
+}
+
+impl Drop for QueuedBuild {
+    fn drop(&mut self) {
+        gauge!(QUEUE_DEPTH).decrement(1.0);
+        QUEUED_BUILDS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// A running build, counted in the active builds until dropped
+pub struct ActiveBuild {
+    queue_wait: Duration,
+    started_at: Instant,
+}
+
+// How long a build waited for a slot and how long it ran
+#[derive(Debug, Clone, Copy)]
+pub struct BuildTimings {
+    pub queue_wait: Duration,
+    pub build_duration: Duration,
+}
+
+impl ActiveBuild {
+    pub fn finish(self, success: bool) -> BuildTimings {
+        let result = if success { "success" } else { "failure" };
+        counter!(BUILDS, "result" => result).increment(1);
+        BuildTimings {
+            queue_wait: self.queue_wait,
+            build_duration: self.started_at.elapsed(),
+        }
+    }
+}
+
+impl Drop for ActiveBuild {
+    fn drop(&mut self) {
+        gauge!(ACTIVE_BUILDS).decrement(1.0);
+    }
+}
+
+// Record how long a stage of the pipeline took
+pub fn record_stage(stage: &'static str, started: Instant) {
+    histogram!(STAGE_DURATION, "stage" => stage).record(started.elapsed().as_secs_f64());
+}
+
+// Record the free space of a monitored volume
+pub fn record_disk_free(volume: &str, bytes: u64) {
+    gauge!(DISK_FREE, "volume" => volume.to_string()).set(bytes as f64);
+}
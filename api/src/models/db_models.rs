@@ -1,4 +1,7 @@
-use crate::schema::{solana_program_builds, verified_programs};
+use crate::schema::{
+    crawl_requests, crawler_runs, denylist, mainnet_programs, program_build_overrides,
+    solana_program_builds, verified_programs,
+};
 use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -21,6 +24,10 @@ pub struct SolanaProgramBuild {
     pub bpf_flag: bool,
     pub created_at: NaiveDateTime,
     pub status: String,
+    pub finished_at: Option<NaiveDateTime>,
+    pub queue_wait_seconds: Option<f64>,
+    pub build_duration_seconds: Option<f64>,
+    pub log_purged_at: Option<NaiveDateTime>,
 }
 
 impl<'a> From<&'a SolanaProgramBuildParams> for SolanaProgramBuild {
@@ -38,6 +45,25 @@ impl<'a> From<&'a SolanaProgramBuildParams> for SolanaProgramBuild {
             mount_path: params.mount_path.clone(),
             cargo_args: params.cargo_args.clone(),
             status: JobStatus::InProgress.into(),
+            finished_at: None,
+            queue_wait_seconds: None,
+            build_duration_seconds: None,
+            log_purged_at: None,
+        }
+    }
+}
+
+impl From<SolanaProgramBuild> for SolanaProgramBuildParams {
+    fn from(build: SolanaProgramBuild) -> Self {
+        SolanaProgramBuildParams {
+            program_id: build.program_id,
+            repository: build.repository,
+            commit_hash: build.commit_hash,
+            lib_name: build.lib_name,
+            base_image: build.base_docker_image,
+            mount_path: build.mount_path,
+            bpf_flag: Some(build.bpf_flag),
+            cargo_args: build.cargo_args,
         }
     }
 }
@@ -56,6 +82,189 @@ pub struct VerifiedProgram {
     pub solana_build_id: String,
 }
 
+// Summary of a crawler run, written by the crawler
+#[derive(Debug, Clone, Serialize, Deserialize, Identifiable, Queryable)]
+#[diesel(table_name = crawler_runs, primary_key(id))]
+pub struct CrawlerRun {
+    pub id: i32,
+    pub kind: String,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+    pub programs_scanned: i32,
+    pub security_txt_found: i32,
+    pub verification_json_found: i32,
+    pub verifications_submitted: i32,
+    pub failures: serde_json::Value,
+    pub cluster: String,
+}
+
+// Build settings of a program set by an admin, merged over the params of every verification
+// request of the program
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Insertable, Identifiable, Queryable, AsChangeset,
+)]
+#[diesel(table_name = program_build_overrides, primary_key(program_id))]
+pub struct ProgramBuildOverride {
+    pub program_id: String,
+    // Replaces the base image of the request
+    pub base_image: Option<String>,
+    // Appended to the cargo args of the request
+    pub cargo_args: Option<Vec<String>>,
+    // Version of solana-verify to build with instead of the default one
+    pub solana_verify_version: Option<String>,
+    // Rejects verification requests of the program
+    pub disabled: bool,
+    pub updated_at: NaiveDateTime,
+}
+
+impl ProgramBuildOverride {
+    pub fn apply(&self, params: &mut SolanaProgramBuildParams) {
+        if let Some(base_image) = &self.base_image {
+            params.base_image = Some(base_image.clone());
+        }
+        if let Some(cargo_args) = &self.cargo_args {
+            params
+                .cargo_args
+                .get_or_insert_with(Vec::new)
+                .extend(cargo_args.iter().cloned());
+        }
+    }
+}
+
+// Program, repository or commit of a repository that must not be verified. Unset columns match
+// anything, so an entry with only a repository blocks every commit of it.
+#[derive(Debug, Clone, Serialize, Deserialize, Identifiable, Queryable)]
+#[diesel(table_name = denylist, primary_key(id))]
+pub struct DenylistEntry {
+    pub id: i32,
+    pub program_id: Option<String>,
+    pub repository: Option<String>,
+    pub commit_hash: Option<String>,
+    pub reason: String,
+    pub created_at: NaiveDateTime,
+}
+
+// Program id or repository URL queued for the crawler through POST /admin/crawl
+#[derive(Debug, Clone, Serialize, Deserialize, Identifiable, Queryable)]
+#[diesel(table_name = crawl_requests, primary_key(id))]
+pub struct CrawlRequest {
+    pub id: i32,
+    pub target: String,
+    pub cluster: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+// Change of the upgrade authority of a program observed by the crawler: changed, frozen or
+// closed. The slot is the last deployment of the program when the change was observed.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct AuthorityChange {
+    pub change: String,
+    pub old_authority: Option<String>,
+    pub new_authority: Option<String>,
+    pub slot: Option<i64>,
+    pub observed_at: NaiveDateTime,
+}
+
+// Entry of the verification history: a program that got verified, or whose on chain hash
+// drifted from the one it was verified with
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct VerificationHistoryEntry {
+    pub id: i32,
+    pub program_id: String,
+    pub event: String,
+    pub on_chain_hash: String,
+    pub executable_hash: String,
+    pub solana_build_id: Option<String>,
+    pub recorded_at: NaiveDateTime,
+}
+
+// Snapshot of the verified programs dataset kept in the storage, see snapshots.rs
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct DatasetSnapshot {
+    pub name: String,
+    pub taken_at: NaiveDateTime,
+    pub programs: i32,
+    pub size_bytes: i64,
+}
+
+// Program tracked by the crawler, tagged with the cluster it was crawled on
+#[derive(Debug, Clone, Serialize, Deserialize, Identifiable, Queryable)]
+#[diesel(table_name = mainnet_programs, primary_key(id))]
+pub struct MainnetProgram {
+    pub id: i32,
+    pub project_name: Option<String>,
+    pub program_address: String,
+    pub buffer_address: String,
+    pub github_repo: Option<String>,
+    pub has_security_txt: bool,
+    pub is_closed: bool,
+    pub is_success: bool,
+    pub is_processed: bool,
+    pub updated_at: NaiveDateTime,
+    pub last_deployed_slot: Option<i64>,
+    pub update_authority: Option<String>,
+    pub repo_host: Option<String>,
+    pub last_error: Option<String>,
+    pub last_error_message: Option<String>,
+    pub last_error_at: Option<NaiveDateTime>,
+    pub cluster: String,
+}
+
+// Median duration of recent builds, from submission to completion
+#[derive(Debug, QueryableByName)]
+pub struct BuildDurationEstimate {
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Double>)]
+    pub seconds: Option<f64>,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub samples: i64,
+}
+
+// Number of builds in one interval of a time series
+#[derive(Debug, Serialize, Deserialize, QueryableByName)]
+pub struct TimeSeriesPoint {
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    pub bucket: NaiveDateTime,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub count: i64,
+}
+
+// Build whose log matched a search
+#[derive(Debug, Serialize, Deserialize, QueryableByName)]
+pub struct LogSearchResult {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub build_id: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub program_id: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub repository: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub status: String,
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    pub created_at: NaiveDateTime,
+    // Matching lines of the log with the matches between << and >>
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub headline: String,
+}
+
+// Id of a build, for raw queries selecting builds
+#[derive(Debug, QueryableByName)]
+pub struct BuildId {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub id: String,
+}
+
+// Build a worker claimed from the queue, and how many times it was claimed
+#[derive(Debug, QueryableByName)]
+pub struct BuildClaim {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub build_id: String,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub attempts: i32,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum JobStatus {
     #[serde(rename = "in_progress")]
@@ -86,3 +295,19 @@ impl From<String> for JobStatus {
         }
     }
 }
+
+// Event of the verification history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryEvent {
+    Verified,
+    Drifted,
+}
+
+impl From<HistoryEvent> for String {
+    fn from(event: HistoryEvent) -> Self {
+        match event {
+            HistoryEvent::Verified => "verified".to_string(),
+            HistoryEvent::Drifted => "drifted".to_string(),
+        }
+    }
+}
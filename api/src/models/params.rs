@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SolanaProgramBuildParams {
     pub repository: String,
     pub program_id: String,
@@ -16,3 +16,57 @@ pub struct SolanaProgramBuildParams {
 pub(crate) struct VerificationStatusParams {
     pub address: String,
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct JobIdParams {
+    pub job_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct SnapshotParams {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TimeSeriesParams {
+    // verifications or failures
+    pub metric: String,
+    // day, week or month
+    pub interval: Option<String>,
+    // Number of intervals, ending with the current one
+    pub limit: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CrawlRequestParams {
+    // Program id or repository URL
+    pub target: String,
+    pub cluster: Option<String>,
+}
+
+// Body of PUT /admin/overrides/:address
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProgramBuildOverrideParams {
+    pub base_image: Option<String>,
+    pub cargo_args: Option<Vec<String>>,
+    pub solana_verify_version: Option<String>,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+// Body of POST /admin/denylist. At least one of program_id and repository must be set.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DenylistParams {
+    pub program_id: Option<String>,
+    pub repository: Option<String>,
+    // Only blocks this commit of the repository
+    pub commit_hash: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogSearchParams {
+    // Search terms, in web search syntax: quoted phrases, OR and -excluded terms
+    pub q: String,
+    pub limit: Option<i64>,
+}
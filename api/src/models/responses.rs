@@ -1,7 +1,10 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-use super::JobStatus;
+use super::{
+    AuthorityChange, CrawlerRun, DatasetSnapshot, DenylistEntry, JobStatus, LogSearchResult,
+    MainnetProgram, ProgramBuildOverride, TimeSeriesPoint,
+};
 
 // Types for API responses
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,6 +29,15 @@ pub struct ErrorResponse {
     pub error: String,
 }
 
+// Response for requests rejected by validation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidationErrorResponse {
+    pub status: Status,
+    pub error: String,
+    pub code: String,
+    pub field: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StatusResponse {
     pub is_verified: bool,
@@ -34,6 +46,67 @@ pub struct StatusResponse {
     pub executable_hash: String,
     pub last_verified_at: Option<NaiveDateTime>,
     pub repo_url: String,
+    // Display name of the program, from its security.txt
+    pub name: Option<String>,
+    pub is_closed: bool,
+    // Why the program isn't verified, None when it is
+    pub status_reason: Option<StatusReason>,
+    pub state: VerificationState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusReason {
+    #[serde(rename = "program closed")]
+    ProgramClosed,
+    #[serde(rename = "hash mismatch after upgrade")]
+    HashMismatch,
+    #[serde(rename = "never verified")]
+    NeverVerified,
+}
+
+impl StatusReason {
+    pub fn new(is_closed: bool, is_verified: bool, has_build: bool) -> Option<Self> {
+        if is_closed {
+            Some(Self::ProgramClosed)
+        } else if is_verified {
+            None
+        } else if has_build {
+            Some(Self::HashMismatch)
+        } else {
+            Some(Self::NeverVerified)
+        }
+    }
+}
+
+// Verification state of a program, telling apart the reasons a program isn't verified so clients
+// know whether submitting a verification would help
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationState {
+    // No build was ever submitted for the program
+    NeverVerified,
+    // The latest build of the program failed
+    VerificationFailed,
+    // The program was built, but the on-chain program doesn't match the build
+    UnverifiedHashMismatch,
+    Verified,
+    ProgramClosed,
+}
+
+impl VerificationState {
+    pub fn new(is_closed: bool, is_verified: bool, has_build: bool, build_failed: bool) -> Self {
+        if is_closed {
+            Self::ProgramClosed
+        } else if is_verified {
+            Self::Verified
+        } else if has_build {
+            Self::UnverifiedHashMismatch
+        } else if build_failed {
+            Self::VerificationFailed
+        } else {
+            Self::NeverVerified
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -60,9 +133,59 @@ impl From<StatusResponse> for SuccessResponse {
 #[serde(untagged)]
 pub enum ApiResponse {
     Success(SuccessResponse),
+    QuotaExceeded(QuotaExceededResponse),
     Error(ErrorResponse),
 }
 
+// Response of GET /verify/challenge
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    pub challenge: String,
+    // Leading zero bits required in sha256("<challenge>:<nonce>"), 0 when no proof is required
+    pub difficulty: u32,
+    pub expires_at: NaiveDateTime,
+}
+
+// Response of GET /repro/:address, how to reproduce the verification of a program locally
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReproResponse {
+    pub program_id: String,
+    pub build_id: String,
+    // Shell command running the verification, with the build override of the program applied
+    pub command: String,
+    // Version of solana-verify pinned for the program, None for the latest
+    pub solana_verify_version: Option<String>,
+    // Docker image the program is built in, None for the default image of solana-verify
+    pub base_image: Option<String>,
+    // Hashes the command is expected to print
+    pub on_chain_hash: String,
+    pub executable_hash: String,
+    pub verified_at: NaiveDateTime,
+}
+
+// Response of a verification request over a build quota
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuotaExceededResponse {
+    pub status: Status,
+    pub error: String,
+    // When a new build is allowed, UTC
+    pub reset_at: NaiveDateTime,
+}
+
+impl From<QuotaExceededResponse> for ApiResponse {
+    fn from(value: QuotaExceededResponse) -> Self {
+        Self::QuotaExceeded(value)
+    }
+}
+
+// Response of a request over a rate limit, sent with a Retry-After header
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RateLimitedResponse {
+    pub status: Status,
+    pub error: String,
+    pub code: String,
+}
+
 impl From<StatusResponse> for ApiResponse {
     fn from(value: StatusResponse) -> Self {
         Self::Success(SuccessResponse::Status(value))
@@ -89,10 +212,124 @@ pub struct JobVerificationResponse {
     pub on_chain_hash: String,
     pub executable_hash: String,
     pub repo_url: String,
+    // Estimated completion time of a job in progress, from the duration of similar builds
+    pub estimated_completion_at: Option<NaiveDateTime>,
+    // How long the job waited for a build slot and how long its last build ran
+    pub queue_wait_seconds: Option<f64>,
+    pub build_duration_seconds: Option<f64>,
+}
+
+// Responses for the /admin/crawler/runs endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrawlerRunListResponse {
+    pub runs: Vec<CrawlerRun>,
+}
+
+// Responses for the /admin/crawler/programs endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrawlerProgramListResponse {
+    pub programs: Vec<MainnetProgram>,
+}
+
+// Responses for the /admin/overrides endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProgramBuildOverrideListResponse {
+    pub overrides: Vec<ProgramBuildOverride>,
+}
+
+// Responses for the /admin/denylist endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DenylistResponse {
+    pub entries: Vec<DenylistEntry>,
+}
+
+// Responses for the /admin/logs/search endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogSearchResponse {
+    pub results: Vec<LogSearchResult>,
+}
+
+// Responses for the /admin/config/reload endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigReloadResponse {
+    // Names of the variables whose value changed
+    pub changed: Vec<String>,
+}
+
+// Responses for the /authority/:address/history endpoint, newest change first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorityHistoryResponse {
+    pub program_id: String,
+    pub changes: Vec<AuthorityChange>,
+}
+
+// Responses for the /snapshots endpoint, newest snapshot first. Each one is served at
+// /snapshots/:name.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotListResponse {
+    pub snapshots: Vec<DatasetSnapshot>,
+}
+
+// Content of a snapshot: every program of verified_programs with the build it was verified with
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub taken_at: NaiveDateTime,
+    pub programs: Vec<SnapshotProgram>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotProgram {
+    pub program_id: String,
+    pub name: Option<String>,
+    pub is_verified: bool,
+    pub on_chain_hash: String,
+    pub executable_hash: String,
+    pub verified_at: NaiveDateTime,
+    pub build_id: String,
+    pub repository: String,
+    pub commit_hash: Option<String>,
+    pub lib_name: Option<String>,
+    pub base_docker_image: Option<String>,
+    pub mount_path: Option<String>,
+    pub cargo_args: Option<Vec<String>>,
+    pub bpf_flag: bool,
+}
+
+// Responses for the /health/ready endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub draining: bool,
+    pub disk: Vec<VolumeSpace>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeSpace {
+    pub volume: String,
+    pub path: String,
+    pub free_bytes: u64,
+    // Whether the free space is under MIN_FREE_DISK_GB
+    pub low: bool,
+}
+
+// Responses for the /stats/timeseries endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeSeriesResponse {
+    pub metric: String,
+    pub interval: String,
+    pub points: Vec<TimeSeriesPoint>,
 }
 
 // Responses for the /verified_programs endpoint
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VerifiedProgramListResponse {
     pub verified_programs: Vec<String>,
+    // The same programs with their display names
+    pub programs: Vec<VerifiedProgramSummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifiedProgramSummary {
+    pub program_id: String,
+    pub name: Option<String>,
 }
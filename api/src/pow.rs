@@ -0,0 +1,223 @@
+// Optional proof of work on verification requests, a knob between a fully open /verify and
+// requiring credentials while the build queue is under attack.
+//
+// With VERIFY_POW_DIFFICULTY set, callers first fetch a challenge from GET /verify/challenge and
+// send it back with the request:
+// - X-Pow-Challenge: the challenge, usable once within CHALLENGE_TTL_SECS
+// - X-Pow-Nonce: any string such that sha256("<challenge>:<nonce>") starts with `difficulty`
+//   zero bits
+//
+// The difficulty grows by one bit for every VERIFY_POW_QUEUE_STEP builds waiting in the queue.
+// Clients in the VERIFY_POW_EXEMPT_CIDRS networks, e.g. the crawler, don't need a proof.
+
+use axum::extract::{ConnectInfo, State};
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::Utc;
+use ipnet::IpNet;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::auth::{client_ip, parse_networks};
+use crate::db::DbClient;
+use crate::metrics;
+use crate::models::{ChallengeResponse, ErrorResponse, Status};
+
+const CHALLENGE_HEADER: &str = "x-pow-challenge";
+const NONCE_HEADER: &str = "x-pow-nonce";
+
+const CHALLENGE_TTL_SECS: u64 = 300;
+const DEFAULT_QUEUE_STEP: usize = 10;
+// Around a minute of hashing on a single core
+const MAX_DIFFICULTY: u32 = 28;
+const MAX_NONCE_LEN: usize = 64;
+
+struct Config {
+    difficulty: u32,
+    queue_step: usize,
+    exempt: Vec<IpNet>,
+}
+
+fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| Config {
+        difficulty: env::var("VERIFY_POW_DIFFICULTY")
+            .ok()
+            .and_then(|difficulty| difficulty.parse().ok())
+            .unwrap_or(0),
+        queue_step: env::var("VERIFY_POW_QUEUE_STEP")
+            .ok()
+            .and_then(|step| step.parse().ok())
+            .filter(|step| *step > 0)
+            .unwrap_or(DEFAULT_QUEUE_STEP),
+        exempt: parse_networks("VERIFY_POW_EXEMPT_CIDRS"),
+    })
+}
+
+// Difficulty of a new challenge, 0 when proofs of work are disabled
+fn current_difficulty() -> u32 {
+    let config = config();
+    if config.difficulty == 0 {
+        return 0;
+    }
+    let pressure = (metrics::queue_depth() / config.queue_step) as u32;
+    config
+        .difficulty
+        .saturating_add(pressure)
+        .min(MAX_DIFFICULTY)
+}
+
+// Route handler for GET /verify/challenge which issues a proof of work challenge
+pub(crate) async fn get_challenge(State(db): State<DbClient>) -> Response {
+    let difficulty = current_difficulty();
+    let challenge = uuid::Uuid::new_v4().simple().to_string();
+    let expires_at = Utc::now().naive_utc() + Duration::from_secs(CHALLENGE_TTL_SECS);
+
+    if difficulty > 0 {
+        if let Err(err) = db
+            .store_challenge(&challenge, difficulty, CHALLENGE_TTL_SECS)
+            .await
+        {
+            tracing::error!("Failed to store proof of work challenge: {}", err);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while issuing a challenge".to_string(),
+                }),
+            )
+                .into_response();
+        }
+    }
+
+    Json(ChallengeResponse {
+        challenge,
+        difficulty,
+        expires_at,
+    })
+    .into_response()
+}
+
+// Middleware rejecting verification requests without a valid proof of work
+pub async fn require_proof_of_work<B>(
+    State(db): State<DbClient>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let config = config();
+    if config.difficulty == 0 {
+        return next.run(request).await;
+    }
+    let client = client_ip(peer.ip(), request.headers());
+    if config.exempt.iter().any(|net| net.contains(&client)) {
+        return next.run(request).await;
+    }
+
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let (Some(challenge), Some(nonce)) = (header(CHALLENGE_HEADER), header(NONCE_HEADER)) else {
+        return rejected("A proof of work is required, get a challenge from /verify/challenge");
+    };
+    if nonce.len() > MAX_NONCE_LEN {
+        return rejected("Invalid proof of work nonce");
+    }
+
+    // Taken before checking the proof so each challenge gets a single attempt
+    let difficulty = match db.take_challenge(challenge).await {
+        Ok(Some(difficulty)) => difficulty,
+        Ok(None) => return rejected("Unknown or expired proof of work challenge"),
+        Err(err) => {
+            tracing::error!("Failed to get proof of work challenge: {}", err);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while checking the proof of work".to_string(),
+                }),
+            )
+                .into_response();
+        }
+    };
+    if !is_valid_proof(challenge, nonce, difficulty) {
+        return rejected("Invalid proof of work");
+    }
+
+    next.run(request).await
+}
+
+// Whether sha256("<challenge>:<nonce>") starts with `difficulty` zero bits
+fn is_valid_proof(challenge: &str, nonce: &str, difficulty: u32) -> bool {
+    let hash = Sha256::digest(format!("{}:{}", challenge, nonce));
+    leading_zero_bits(&hash) >= difficulty
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+fn rejected(error: &str) -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(ErrorResponse {
+            status: Status::Error,
+            error: error.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0x80, 0x00]), 0);
+        assert_eq!(leading_zero_bits(&[0x01, 0xff]), 7);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00, 0x10]), 19);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+        assert_eq!(leading_zero_bits(&[]), 0);
+    }
+
+    #[test]
+    fn test_is_valid_proof() {
+        let challenge = "0123456789abcdef0123456789abcdef";
+        // Solve the challenge the way clients do
+        let nonce = (0u64..)
+            .map(|nonce| nonce.to_string())
+            .find(|nonce| is_valid_proof(challenge, nonce, 12))
+            .unwrap();
+        let hash = Sha256::digest(format!("{}:{}", challenge, nonce));
+        assert!(leading_zero_bits(&hash) >= 12);
+
+        assert!(is_valid_proof(challenge, &nonce, 0));
+        assert!(!is_valid_proof(
+            challenge,
+            &nonce,
+            leading_zero_bits(&hash) + 1
+        ));
+        // The proof is bound to its challenge
+        assert!(!is_valid_proof(
+            "fedcba9876543210fedcba9876543210",
+            &nonce,
+            12
+        ));
+    }
+}
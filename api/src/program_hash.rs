@@ -0,0 +1,404 @@
+// Hash of the program deployed on chain, computed the same way as `solana-verify
+// get-program-hash` from the program accounts fetched over RPC.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::builder::rpc_url;
+use crate::errors::ApiError;
+use crate::metrics;
+use crate::Result;
+
+const BPF_LOADER_UPGRADEABLE: &str = "BPFLoaderUpgradeab1e11111111111111111111111";
+const BPF_LOADERS: [&str; 2] = [
+    "BPFLoader2111111111111111111111111111111111",
+    "BPFLoader1111111111111111111111111111111111",
+];
+// UpgradeableLoaderState::Program tag followed by the program data address
+const PROGRAM_STATE_TAG: u32 = 2;
+const PROGRAM_STATE_LEN: usize = 4 + 32;
+// UpgradeableLoaderState::ProgramData tag, slot and optional upgrade authority before the
+// executable
+const PROGRAM_DATA_METADATA_LEN: usize = 4 + 8 + 1 + 32;
+
+// Most accounts getMultipleAccounts returns in one request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+const RPC_ATTEMPTS: u32 = 3;
+const RPC_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountInfoResult {
+    value: Option<Account>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MultipleAccountsResult {
+    value: Vec<Option<Account>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Account {
+    // Data and its encoding
+    data: (String, String),
+    owner: String,
+}
+
+// Client sending the provider auth headers of RPC_HEADERS, shared with the crawler, with every
+// request. They are kept out of RPC_URL so they never end up in logs.
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        let headers = env::var("RPC_HEADERS")
+            .ok()
+            .map(|headers| {
+                parse_headers(&headers)
+                    .expect("RPC_HEADERS must be a comma separated list of `Name: value` pairs")
+            })
+            .unwrap_or_default();
+        reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(RPC_TIMEOUT)
+            .build()
+            .expect("Failed to create RPC client")
+    })
+}
+
+// Parse `Name: value` pairs separated by commas. Values are marked sensitive so they are
+// redacted from any debug output.
+fn parse_headers(input: &str) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for pair in input.split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (name, value) = pair
+            .split_once(':')
+            .ok_or_else(|| ApiError::Custom("Header is missing a value".to_string()))?;
+        let name = HeaderName::from_str(name.trim())
+            .map_err(|err| ApiError::Custom(format!("Invalid header name: {}", err)))?;
+        let mut value = HeaderValue::from_str(value.trim())
+            .map_err(|err| ApiError::Custom(format!("Invalid header value: {}", err)))?;
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
+
+// Commitment of every account read, from COMMITMENT like the crawler so program and program
+// data are seen at the same state. Defaults to confirmed, so fresh deployments are seen quickly.
+fn commitment() -> String {
+    match env::var("COMMITMENT").as_deref() {
+        Ok(level @ ("processed" | "confirmed" | "finalized")) => level.to_string(),
+        Ok("") | Err(_) => "confirmed".to_string(),
+        Ok(level) => {
+            tracing::warn!("Unknown COMMITMENT {}, using confirmed", level);
+            "confirmed".to_string()
+        }
+    }
+}
+
+/// Compute the hash of the executable of a program deployed on chain.
+///
+/// Returns: The hex encoded sha256 of the executable without its trailing zero bytes, an error
+/// when the program doesn't exist or isn't a deployed BPF program.
+#[tracing::instrument]
+pub async fn get_on_chain_hash(program_id: &str) -> Result<String> {
+    let started = Instant::now();
+    let program = get_account(program_id)
+        .await?
+        .ok_or_else(|| ApiError::Custom(format!("Program {} not found", program_id)))?;
+
+    let program_data = match program_data_address(program_id, &program)? {
+        Some(address) => Some(get_account(&address).await?.ok_or_else(|| {
+            ApiError::Custom(format!("Program data of {} not found", program_id))
+        })?),
+        None => None,
+    };
+    let executable = executable(program_id, &program, program_data.as_ref())?;
+    metrics::record_stage("on_chain_hash", started);
+
+    Ok(executable_hash(&executable))
+}
+
+/// Compute the on-chain hashes of many programs, fetching their accounts with
+/// `getMultipleAccounts` in chunks of 100 accounts.
+///
+/// Returns: The result of [`get_on_chain_hash`] for every program, in the order of
+/// `program_ids`. A failed RPC request fails every program of its chunk.
+pub async fn get_on_chain_hashes(program_ids: &[String]) -> Vec<Result<String>> {
+    let mut hashes = Vec::with_capacity(program_ids.len());
+    for chunk in program_ids.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        match get_chunk_hashes(chunk).await {
+            Ok(chunk_hashes) => hashes.extend(chunk_hashes),
+            Err(err) => hashes.extend(chunk.iter().map(|_| {
+                Err(ApiError::Custom(format!(
+                    "Failed to fetch program accounts: {}",
+                    err
+                )))
+            })),
+        }
+    }
+    hashes
+}
+
+async fn get_chunk_hashes(program_ids: &[String]) -> Result<Vec<Result<String>>> {
+    let programs = program_ids
+        .iter()
+        .zip(get_multiple_accounts(program_ids).await?)
+        .map(|(program_id, program)| {
+            let program = program
+                .ok_or_else(|| ApiError::Custom(format!("Program {} not found", program_id)))?;
+            let program_data_address = program_data_address(program_id, &program)?;
+            Ok((program, program_data_address))
+        })
+        .collect::<Vec<Result<(Account, Option<String>)>>>();
+
+    // Program data of the upgradeable programs, fetched in a second request
+    let addresses = programs
+        .iter()
+        .filter_map(|program| program.as_ref().ok()?.1.clone())
+        .collect::<Vec<String>>();
+    let mut program_data = get_multiple_accounts(&addresses).await?.into_iter();
+
+    Ok(program_ids
+        .iter()
+        .zip(programs)
+        .map(|(program_id, program)| {
+            let (program, program_data_address) = program?;
+            let program_data = match program_data_address {
+                Some(_) => Some(program_data.next().flatten().ok_or_else(|| {
+                    ApiError::Custom(format!("Program data of {} not found", program_id))
+                })?),
+                None => None,
+            };
+            let executable = executable(program_id, &program, program_data.as_ref())?;
+            Ok(executable_hash(&executable))
+        })
+        .collect())
+}
+
+// Address of the program data account of an upgradeable program, None for programs of the
+// older loaders that hold their executable in the program account
+fn program_data_address(program_id: &str, program: &Account) -> Result<Option<String>> {
+    if program.owner == BPF_LOADER_UPGRADEABLE {
+        let state = decode(program)?;
+        if state.len() < PROGRAM_STATE_LEN
+            || u32::from_le_bytes([state[0], state[1], state[2], state[3]]) != PROGRAM_STATE_TAG
+        {
+            return Err(ApiError::Custom(format!(
+                "{} is not an upgradeable program",
+                program_id
+            )));
+        }
+        Ok(Some(
+            bs58::encode(&state[4..PROGRAM_STATE_LEN]).into_string(),
+        ))
+    } else if BPF_LOADERS.contains(&program.owner.as_str()) {
+        Ok(None)
+    } else {
+        Err(ApiError::Custom(format!(
+            "{} is not a program, it is owned by {}",
+            program_id, program.owner
+        )))
+    }
+}
+
+// Executable of a program, read from its program data account when it is upgradeable
+fn executable(
+    program_id: &str,
+    program: &Account,
+    program_data: Option<&Account>,
+) -> Result<Vec<u8>> {
+    let Some(program_data) = program_data else {
+        return decode(program);
+    };
+    let mut data = decode(program_data)?;
+    if data.len() < PROGRAM_DATA_METADATA_LEN {
+        return Err(ApiError::Custom(format!(
+            "Program {} has been closed",
+            program_id
+        )));
+    }
+    Ok(data.split_off(PROGRAM_DATA_METADATA_LEN))
+}
+
+// Executables are stored in accounts padded with zeros, which aren't part of the hash
+fn executable_hash(executable: &[u8]) -> String {
+    let end = executable
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |last| last + 1);
+    Sha256::digest(&executable[..end])
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn decode(account: &Account) -> Result<Vec<u8>> {
+    BASE64
+        .decode(&account.data.0)
+        .map_err(|err| ApiError::Custom(format!("Invalid account data: {}", err)))
+}
+
+// Fetch an account
+async fn get_account(address: &str) -> Result<Option<Account>> {
+    rpc_request::<AccountInfoResult>(
+        "getAccountInfo",
+        json!([address, { "encoding": "base64", "commitment": commitment() }]),
+    )
+    .await
+    .map(|result| result.value)
+}
+
+// Fetch many accounts in one request, in the order of `addresses`
+async fn get_multiple_accounts(addresses: &[String]) -> Result<Vec<Option<Account>>> {
+    if addresses.is_empty() {
+        return Ok(Vec::new());
+    }
+    rpc_request::<MultipleAccountsResult>(
+        "getMultipleAccounts",
+        json!([addresses, { "encoding": "base64", "commitment": commitment() }]),
+    )
+    .await
+    .map(|result| result.value)
+}
+
+// Send an RPC request, retrying errors of the RPC node that are likely to go away
+async fn rpc_request<T: DeserializeOwned>(method: &str, params: serde_json::Value) -> Result<T> {
+    let url = rpc_url();
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    });
+
+    let mut attempt = 1;
+    loop {
+        let response = client().post(&url).json(&request).send().await;
+        let retryable = match &response {
+            Ok(response) => {
+                response.status().is_server_error()
+                    || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(err) => err.is_timeout() || err.is_connect(),
+        };
+        if retryable && attempt < RPC_ATTEMPTS {
+            tracing::warn!("RPC request {} failed, retrying", method);
+            tokio::time::sleep(RPC_RETRY_DELAY * attempt).await;
+            attempt += 1;
+            continue;
+        }
+
+        let response = response?
+            .error_for_status()?
+            .json::<RpcResponse<T>>()
+            .await?;
+        if let Some(error) = response.error {
+            return Err(ApiError::Custom(format!(
+                "RPC error in {}: {}",
+                method, error.message
+            )));
+        }
+        return response
+            .result
+            .ok_or_else(|| ApiError::Custom(format!("RPC response to {} has no result", method)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sha256 of "hello"
+    const HELLO_HASH: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    fn account(owner: &str, data: &[u8]) -> Account {
+        Account {
+            data: (BASE64.encode(data), "base64".to_string()),
+            owner: owner.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_executable_hash() {
+        assert_eq!(executable_hash(b"hello"), HELLO_HASH);
+        // Trailing zeros are padding, zeros inside the executable are not
+        assert_eq!(executable_hash(b"hello\0\0\0"), HELLO_HASH);
+        assert_ne!(executable_hash(b"he\0llo"), HELLO_HASH);
+        assert_eq!(
+            executable_hash(&[0; 8]),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_upgradeable_program() {
+        let program_data_key = [7u8; 32];
+        let mut state = PROGRAM_STATE_TAG.to_le_bytes().to_vec();
+        state.extend_from_slice(&program_data_key);
+        let program = account(BPF_LOADER_UPGRADEABLE, &state);
+        assert_eq!(
+            program_data_address("program", &program).unwrap(),
+            Some(bs58::encode(program_data_key).into_string())
+        );
+
+        // Tag, slot, upgrade authority option and key, then the executable padded with zeros
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&42u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&[9u8; 32]);
+        assert_eq!(data.len(), PROGRAM_DATA_METADATA_LEN);
+        data.extend_from_slice(b"hello");
+        data.extend_from_slice(&[0; 1024]);
+        let program_data = account(BPF_LOADER_UPGRADEABLE, &data);
+        let bytes = executable("program", &program, Some(&program_data)).unwrap();
+        assert_eq!(executable_hash(&bytes), HELLO_HASH);
+
+        // Closed programs keep a program data account without the executable
+        let closed = account(BPF_LOADER_UPGRADEABLE, &data[..4]);
+        assert!(executable("program", &program, Some(&closed)).is_err());
+    }
+
+    #[test]
+    fn test_other_programs() {
+        let program = account(BPF_LOADERS[0], b"hello\0\0");
+        assert_eq!(program_data_address("program", &program).unwrap(), None);
+        let bytes = executable("program", &program, None).unwrap();
+        assert_eq!(executable_hash(&bytes), HELLO_HASH);
+
+        let wallet = account("11111111111111111111111111111111", &[]);
+        assert!(program_data_address("wallet", &wallet).is_err());
+        let buffer = account(BPF_LOADER_UPGRADEABLE, &1u32.to_le_bytes());
+        assert!(program_data_address("buffer", &buffer).is_err());
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let headers = parse_headers("x-api-key: secret, Authorization: Bearer token").unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["x-api-key"], "secret");
+        assert!(headers["authorization"].is_sensitive());
+        assert!(parse_headers("").unwrap().is_empty());
+        assert!(parse_headers("x-api-key").is_err());
+    }
+}
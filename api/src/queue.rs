@@ -0,0 +1,130 @@
+// Queue of the builds accepted by instances with the api role, see role.rs, kept in the
+// build_queue table so any number of workers can share it. Workers take a build only when they
+// have a free build slot, so the queued builds go to the workers with capacity.
+//
+// A worker claims a build for BUILD_LEASE_SECS, 60 seconds by default, and renews the lease while
+// the build runs. The build of a worker that crashed is claimed again by another worker once its
+// lease expires, up to BUILD_MAX_ATTEMPTS times, 3 by default, before it's marked failed. A worker
+// shutting down releases the builds it couldn't finish, and builds still queued stay for the
+// other workers.
+
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::Instrument;
+
+use crate::builder;
+use crate::db::DbClient;
+use crate::disk;
+use crate::models::JobStatus;
+use crate::shutdown;
+use crate::telemetry;
+
+// How often an idle worker checks the queue
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const DEFAULT_LEASE_SECS: u64 = 60;
+const DEFAULT_MAX_ATTEMPTS: i32 = 3;
+
+fn lease() -> u64 {
+    env::var("BUILD_LEASE_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_LEASE_SECS)
+}
+
+fn max_attempts() -> i32 {
+    env::var("BUILD_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|attempts| attempts.parse::<i32>().ok())
+        .filter(|attempts| *attempts > 0)
+        .unwrap_or(DEFAULT_MAX_ATTEMPTS)
+}
+
+// Id the claims of this instance are recorded under
+pub fn worker_id() -> &'static str {
+    static WORKER_ID: OnceLock<String> = OnceLock::new();
+    WORKER_ID.get_or_init(|| {
+        let host = env::var("HOSTNAME").unwrap_or_else(|_| "worker".to_string());
+        format!("{}-{}", host, uuid::Uuid::new_v4())
+    })
+}
+
+pub fn spawn_consumer(db: DbClient) {
+    tokio::spawn(async move {
+        loop {
+            let slot = builder::acquire_worker_slot().await;
+            if shutdown::is_draining() {
+                break;
+            }
+            if disk::is_low() {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                continue;
+            }
+            let lease = lease();
+            let claim = match db.claim_queued_build(worker_id(), lease).await {
+                Ok(Some(claim)) => claim,
+                Ok(None) => {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+                Err(err) => {
+                    tracing::error!("Error taking a build from the queue: {}", err);
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            let build_id = claim.build_id;
+            let span = telemetry::background_span(&build_id);
+            let db = db.clone();
+            tokio::spawn(
+                async move {
+                    let _slot = slot;
+                    if claim.attempts > max_attempts() {
+                        tracing::error!(
+                            "Build {} was interrupted {} times, giving up",
+                            build_id,
+                            claim.attempts - 1
+                        );
+                        let _ = db
+                            .update_build_status(&build_id, JobStatus::Failed.into())
+                            .await;
+                        let _ = db.remove_queued_build(&build_id).await;
+                        return;
+                    }
+
+                    let heartbeat = tokio::spawn(renew_lease(db.clone(), build_id.clone(), lease));
+                    db.run_stored_build(&build_id).await;
+                    heartbeat.abort();
+                    if let Err(err) = db.remove_queued_build(&build_id).await {
+                        tracing::error!(
+                            "Error removing build {} from the queue: {}",
+                            build_id,
+                            err
+                        );
+                    }
+                }
+                .instrument(span),
+            );
+        }
+    });
+}
+
+// Renew the lease of a running build every third of the lease until aborted
+async fn renew_lease(db: DbClient, build_id: String, lease: u64) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(lease).div_f64(3.0));
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        match db.renew_build_lease(&build_id, worker_id(), lease).await {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::warn!("Lost the claim on build {}", build_id);
+                return;
+            }
+            Err(err) => tracing::error!("Error renewing the lease of build {}: {}", build_id, err),
+        }
+    }
+}
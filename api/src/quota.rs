@@ -0,0 +1,86 @@
+// Build submission quotas over a rolling window, so a single repository or client can't take over
+// the build fleet. BUILD_QUOTA_PER_REPOSITORY and BUILD_QUOTA_PER_IP are the number of builds
+// allowed in the last BUILD_QUOTA_WINDOW_SECS seconds, one hour by default. A quota left unset or
+// set to 0 is unlimited.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::env;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+use crate::builder::normalize_repository_url;
+use crate::db::DbClient;
+use crate::Result;
+
+const DEFAULT_WINDOW_SECS: i64 = 3600;
+
+struct Quotas {
+    per_repository: u64,
+    per_ip: u64,
+    window_ms: i64,
+}
+
+fn quotas() -> &'static Quotas {
+    static QUOTAS: OnceLock<Quotas> = OnceLock::new();
+    QUOTAS.get_or_init(|| {
+        let var = |name: &str| env::var(name).ok().and_then(|value| value.parse().ok());
+        Quotas {
+            per_repository: var("BUILD_QUOTA_PER_REPOSITORY").unwrap_or(0),
+            per_ip: var("BUILD_QUOTA_PER_IP").unwrap_or(0),
+            window_ms: var("BUILD_QUOTA_WINDOW_SECS")
+                .map(|secs: u64| secs as i64)
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_WINDOW_SECS)
+                * 1000,
+        }
+    })
+}
+
+// A quota a submission ran into
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    // repository or ip
+    pub scope: &'static str,
+    // When the oldest build of the window leaves it and a new build is allowed
+    pub reset_at: NaiveDateTime,
+}
+
+// Count the build `build_id` against the quotas of its repository and of the client address.
+// Returns the exceeded quota instead when either is used up, without counting the build.
+pub async fn claim_build(
+    db: &DbClient,
+    build_id: &str,
+    repository: &str,
+    client: IpAddr,
+) -> Result<Option<QuotaExceeded>> {
+    let quotas = quotas();
+    let windows = [
+        (
+            "repository",
+            quotas.per_repository,
+            format!("quota:repository:{}", normalize_repository_url(repository)),
+        ),
+        ("ip", quotas.per_ip, format!("quota:ip:{}", client)),
+    ]
+    .into_iter()
+    .filter(|(_, limit, _)| *limit > 0)
+    .collect::<Vec<_>>();
+    if windows.is_empty() {
+        return Ok(None);
+    }
+    let keys = windows
+        .iter()
+        .map(|(_, limit, key)| (key.clone(), *limit))
+        .collect::<Vec<(String, u64)>>();
+    let now_ms = Utc::now().timestamp_millis();
+
+    let exceeded = db
+        .claim_window_entry(&keys, build_id, quotas.window_ms, now_ms)
+        .await?;
+    Ok(exceeded.map(|(index, oldest)| QuotaExceeded {
+        scope: windows[index].0,
+        reset_at: DateTime::from_timestamp_millis(oldest + quotas.window_ms)
+            .unwrap_or_default()
+            .naive_utc(),
+    }))
+}
@@ -0,0 +1,145 @@
+// X-RateLimit-Limit, X-RateLimit-Remaining and X-RateLimit-Reset headers on the responses of the
+// rate limited routes, so clients can slow down before they're throttled. Each route group has a
+// global limiter and a per IP limiter, the headers describe whichever of them has the fewest
+// requests left. Reset is the number of seconds until that limiter allows its full limit again.
+
+use axum::body::BoxBody;
+use axum::extract::State;
+use axum::http::{header::RETRY_AFTER, HeaderMap, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::{BoxError, Json};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tower_governor::GovernorError;
+
+use crate::models::{RateLimitedResponse, Status};
+
+const LIMIT: &str = "x-ratelimit-limit";
+const REMAINING: &str = "x-ratelimit-remaining";
+const RESET: &str = "x-ratelimit-reset";
+// Set by tower_governor on the requests it rejects
+const AFTER: &str = "x-ratelimit-after";
+
+// Requests seen by a global limiter in its current window. The limiter queues requests over the
+// limit rather than rejecting them, so this only reports how close a route group is to it.
+#[derive(Clone)]
+pub struct GlobalWindow {
+    limit: u64,
+    period: Duration,
+    window: Arc<Mutex<(Instant, u64)>>,
+}
+
+impl GlobalWindow {
+    pub fn new(limit: u64, period: Duration) -> Self {
+        Self {
+            limit,
+            period,
+            window: Arc::new(Mutex::new((Instant::now(), 0))),
+        }
+    }
+
+    // Count a request, returns the requests left in the window and the seconds until it ends
+    fn record(&self) -> (u64, u64) {
+        let now = Instant::now();
+        let mut window = self.window.lock().unwrap();
+        if now.duration_since(window.0) >= self.period {
+            *window = (now, 0);
+        }
+        window.1 += 1;
+        let reset = (window.0 + self.period).saturating_duration_since(now);
+        (
+            self.limit.saturating_sub(window.1),
+            reset.as_secs_f64().ceil() as u64,
+        )
+    }
+}
+
+// Middleware in front of a global limiter
+pub async fn global_rate_limit_headers<B>(
+    State(window): State<GlobalWindow>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let (remaining, reset) = window.record();
+    let mut response = next.run(request).await;
+    merge_headers(response.headers_mut(), window.limit, remaining, reset);
+    response
+}
+
+// Middleware in front of a per IP limiter replenishing one request every `period`, which adds the
+// reset the limiter doesn't report to the limit and remaining requests it does
+pub async fn per_ip_rate_limit_headers<B>(
+    State(period): State<Duration>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    let reset = match (
+        number(headers, AFTER),
+        number(headers, LIMIT),
+        number(headers, REMAINING),
+    ) {
+        (Some(after), _, _) => after,
+        (None, Some(limit), Some(remaining)) => {
+            (period * limit.saturating_sub(remaining) as u32).as_secs()
+        }
+        _ => return response,
+    };
+    headers.insert(RESET, HeaderValue::from(reset));
+    response
+}
+
+// Error handler of the per IP limiters, which report the requests they reject as errors
+pub fn rate_limit_error(err: BoxError) -> Response<BoxBody> {
+    match err.downcast_ref::<GovernorError>() {
+        Some(GovernorError::TooManyRequests { wait_time, headers }) => {
+            // The wait is rounded down, so a retry right away would be rejected again
+            let retry_after = (*wait_time).max(1);
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, HeaderValue::from(retry_after))],
+                Json(RateLimitedResponse {
+                    status: Status::Error,
+                    error: format!("Too many requests, retry in {}s", retry_after),
+                    code: "RATE_LIMITED".to_string(),
+                }),
+            )
+                .into_response();
+            if let Some(headers) = headers {
+                response.headers_mut().extend(headers.clone());
+            }
+            response
+        }
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unhandled error: {}", err),
+        )
+            .into_response(),
+    }
+}
+
+// Replace the headers of the response with those of another limiter when it has fewer requests
+// left, or as many but a later reset
+fn merge_headers(headers: &mut HeaderMap, limit: u64, remaining: u64, reset: u64) {
+    let replace = match (number(headers, REMAINING), number(headers, RESET)) {
+        (Some(current_remaining), current_reset) => {
+            remaining < current_remaining
+                || (remaining == current_remaining && reset > current_reset.unwrap_or(0))
+        }
+        (None, _) => true,
+    };
+    if replace {
+        headers.insert(LIMIT, HeaderValue::from(limit));
+        headers.insert(REMAINING, HeaderValue::from(remaining));
+        headers.insert(RESET, HeaderValue::from(reset));
+    }
+}
+
+fn number(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
@@ -0,0 +1,48 @@
+// Reload of the runtime configuration without a restart, on SIGHUP or POST /admin/config/reload.
+// The .env file is read again over the environment, which the following settings pick up:
+// - RPC_URL and COMMITMENT, read for every RPC request
+// - STATUS_CACHE_TTL_SECS, for statuses cached from then on
+// - MAX_CONCURRENT_BUILDS, running builds are unaffected
+// The denylist and build overrides live in the database and apply as soon as they change. Other
+// settings still need a restart.
+
+use std::env;
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::builder;
+use crate::errors::ApiError;
+use crate::Result;
+
+// Reload the configuration, returns the names of the variables that changed
+pub fn reload() -> Result<Vec<String>> {
+    // The iterator is the only way to read the values without skipping the variables already set
+    #[allow(deprecated)]
+    let vars = dotenv::dotenv_iter()
+        .map_err(|err| ApiError::Custom(format!("Failed to read the .env file: {}", err)))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|err| ApiError::Custom(format!("Invalid .env file: {}", err)))?;
+
+    let mut changed = Vec::new();
+    for (name, value) in vars {
+        if env::var(&name).ok().as_ref() != Some(&value) {
+            env::set_var(&name, value);
+            changed.push(name);
+        }
+    }
+    builder::reload_build_limit();
+
+    // Only names are logged, values may be secrets
+    tracing::info!("Configuration reloaded, changed: {:?}", changed);
+    Ok(changed)
+}
+
+pub fn spawn_sighup_listener() {
+    tokio::spawn(async {
+        let mut hangup = signal(SignalKind::hangup()).expect("Failed to listen for SIGHUP");
+        while hangup.recv().await.is_some() {
+            if let Err(err) = reload() {
+                tracing::error!("Error reloading the configuration: {}", err);
+            }
+        }
+    });
+}
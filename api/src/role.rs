@@ -0,0 +1,72 @@
+// Role of an instance, selected with `--role` or ROLE, so HTTP frontends and build capacity scale
+// separately:
+// - api: serves the HTTP API and queues the builds it accepts for the workers
+// - worker: runs the queued builds and the background jobs, without serving HTTP. Each run of a
+//   periodic job is claimed in Redis, so a single worker takes it however many are running
+// - all (default): both, builds run on the instance that accepted them
+
+use std::env;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::errors::ApiError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Api,
+    Worker,
+    All,
+}
+
+impl Role {
+    pub fn serves_http(self) -> bool {
+        self != Role::Worker
+    }
+
+    pub fn runs_builds(self) -> bool {
+        self != Role::Api
+    }
+}
+
+impl FromStr for Role {
+    type Err = ApiError;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "api" => Ok(Role::Api),
+            "worker" => Ok(Role::Worker),
+            "" | "all" => Ok(Role::All),
+            role => Err(ApiError::Custom(format!(
+                "The role must be one of api, worker or all, got {}",
+                role
+            ))),
+        }
+    }
+}
+
+static ROLE: OnceLock<Role> = OnceLock::new();
+
+// Read the role from the command line, then the environment. Panics on an invalid role so it
+// surfaces at startup.
+pub fn init() {
+    let mut args = env::args().skip(1);
+    let mut role = None;
+    while let Some(arg) = args.next() {
+        if arg == "--role" {
+            role = args.next();
+        } else if let Some(value) = arg.strip_prefix("--role=") {
+            role = Some(value.to_string());
+        }
+    }
+    let role = role
+        .or_else(|| env::var("ROLE").ok())
+        .unwrap_or_default()
+        .parse()
+        .expect("Invalid role");
+    ROLE.get_or_init(|| role);
+}
+
+// Role of this instance. Services embedding the pipeline without calling `init` run everything.
+pub fn get() -> Role {
+    ROLE.get().copied().unwrap_or(Role::All)
+}
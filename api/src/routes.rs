@@ -1,33 +1,86 @@
+mod admin;
+mod authority;
+mod feed;
+mod health;
 mod job;
+mod logs;
+mod preflight;
+mod repro;
+mod snapshots;
+mod stats;
 mod status;
 mod verified_programs;
 mod verify_async;
 mod verify_sync;
+mod worker;
+use crate::auth::{
+    require_allowed_ip, require_metrics_token, require_signature, require_worker_token,
+    IpAllowlist, Signers,
+};
 use crate::db::DbClient;
+use crate::models::{ErrorResponse, Status};
+use crate::pow::{get_challenge, require_proof_of_work};
+use crate::rate_limit::{
+    global_rate_limit_headers, per_ip_rate_limit_headers, rate_limit_error, GlobalWindow,
+};
 use crate::routes::{
-    job::get_job_status, status::verify_status, verified_programs::get_verified_programs_list,
-    verify_async::verify_async, verify_sync::verify_sync,
+    admin::{
+        add_denylist_entry, crawl_target, delete_build_override, delete_denylist_entry,
+        get_build_override, get_build_overrides, get_crawl_request, get_crawler_programs,
+        get_crawler_runs, get_denylist, get_log_retention, put_build_override, reload_config,
+        search_logs,
+    },
+    authority::get_authority_history,
+    feed::get_feed,
+    health::get_readiness,
+    job::get_job_status,
+    logs::get_job_logs,
+    repro::get_repro,
+    snapshots::{get_snapshot, get_snapshots},
+    stats::get_time_series,
+    status::verify_status,
+    verified_programs::get_verified_programs_list,
+    verify_async::verify_async,
+    verify_sync::verify_sync,
+    worker::run_worker_build,
 };
+use crate::telemetry;
 use axum::{
     error_handling::HandleErrorLayer,
-    http::{Method, StatusCode},
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    http::{header::VARY, HeaderValue, Method, StatusCode},
+    middleware,
+    response::IntoResponse,
+    routing::{delete, get, post},
     BoxError, Json, Router,
 };
 use serde_json::{json, Value};
 use std::sync::OnceLock;
 use std::time::Duration;
-use tower::{buffer::BufferLayer, limit::RateLimitLayer, ServiceBuilder};
+use tower::{
+    buffer::BufferLayer,
+    limit::RateLimitLayer,
+    timeout::{error::Elapsed, TimeoutLayer},
+    ServiceBuilder,
+};
 use tower_governor::{
     governor::GovernorConfigBuilder, key_extractor::SmartIpKeyExtractor, GovernorLayer,
 };
 use tower_http::{
     compression::CompressionLayer,
     cors::{Any, CorsLayer},
-    trace::{DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, TraceLayer},
+    set_header::SetResponseHeaderLayer,
+    trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer},
 };
 use tracing::Level;
 
+// Largest body accepted by the verify routes, far above any legitimate build request
+const MAX_VERIFY_BODY_SIZE: usize = 64 * 1024;
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(60);
+// /verify_sync runs the whole build, so it gets as long as a build may take
+const VERIFY_SYNC_TIMEOUT: Duration = Duration::from_secs(2 * 60 * 60);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub fn create_router(db: DbClient) -> Router {
     let error_handler = || {
         ServiceBuilder::new().layer(HandleErrorLayer::new(|err: BoxError| async move {
@@ -40,6 +93,10 @@ pub fn create_router(db: DbClient) -> Router {
 
     let global_rate_limit = |req_per_sec: u64| {
         ServiceBuilder::new()
+            .layer(middleware::from_fn_with_state(
+                GlobalWindow::new(req_per_sec, Duration::from_secs(1)),
+                global_rate_limit_headers,
+            ))
             .layer(error_handler())
             .layer(BufferLayer::new(1024))
             .layer(RateLimitLayer::new(req_per_sec, Duration::from_secs(1)))
@@ -57,56 +114,213 @@ pub fn create_router(db: DbClient) -> Router {
         );
 
         ServiceBuilder::new()
-            .layer(error_handler())
+            .layer(middleware::from_fn_with_state(
+                Duration::from_secs(timeout),
+                per_ip_rate_limit_headers,
+            ))
+            .layer(HandleErrorLayer::new(|err: BoxError| async move {
+                rate_limit_error(err)
+            }))
             .layer(GovernorLayer {
                 config: Box::leak(config),
             })
     };
 
+    // Encoding picked from the Accept-Encoding of the request. Responses vary on it so caches
+    // don't serve an encoding the client didn't ask for. Routes get this layer more than once, so
+    // the header is only set when it isn't already.
+    let compression = || {
+        ServiceBuilder::new()
+            .layer(SetResponseHeaderLayer::if_not_present(
+                VARY,
+                HeaderValue::from_static("accept-encoding"),
+            ))
+            .layer(CompressionLayer::new().gzip(true).br(true).zstd(true))
+    };
+
+    // Handlers running past their deadline are dropped and answered with a 408
+    let timeout = |duration: Duration| {
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|err: BoxError| async move {
+                match err.is::<Elapsed>() {
+                    true => (
+                        StatusCode::REQUEST_TIMEOUT,
+                        Json(ErrorResponse {
+                            status: Status::Error,
+                            error: "The request timed out".to_string(),
+                        }),
+                    )
+                        .into_response(),
+                    false => (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("Unhandled error: {}", err),
+                    )
+                        .into_response(),
+                }
+            }))
+            .layer(TimeoutLayer::new(duration))
+    };
+
     let cors = |method: Method| {
         ServiceBuilder::new().layer(CorsLayer::new().allow_methods(method).allow_origin(Any))
     };
 
     let trace_layer = TraceLayer::new_for_http()
-        .make_span_with(DefaultMakeSpan::new().include_headers(true))
+        .make_span_with(telemetry::request_span)
         .on_request(DefaultOnRequest::new().level(Level::INFO))
         .on_response(DefaultOnResponse::new().level(Level::INFO));
 
+    // Admin routes live in their own router so the signature check doesn't apply to public routes.
+    // Each capability has its own signers so a key only grants what it needs.
+    let admin_router = Router::new()
+        .route("/admin/crawler/runs", get(get_crawler_runs))
+        .route("/admin/crawler/programs", get(get_crawler_programs))
+        .route("/admin/overrides", get(get_build_overrides))
+        .route(
+            "/admin/overrides/:address",
+            get(get_build_override)
+                .put(put_build_override)
+                .delete(delete_build_override),
+        )
+        .route(
+            "/admin/denylist",
+            get(get_denylist).post(add_denylist_entry),
+        )
+        .route("/admin/denylist/:id", delete(delete_denylist_entry))
+        .route("/admin/logs/retention", get(get_log_retention))
+        .route("/admin/logs/search", get(search_logs))
+        .route("/admin/config/reload", post(reload_config))
+        .route_layer(middleware::from_fn_with_state(
+            Signers::from_env(db.clone(), "ADMIN_PUBKEYS"),
+            require_signature,
+        ))
+        .merge(
+            Router::new()
+                .route("/admin/crawl", post(crawl_target))
+                .route("/admin/crawl/:id", get(get_crawl_request))
+                .route_layer(middleware::from_fn_with_state(
+                    Signers::from_env(db.clone(), "CRAWL_PUBKEYS"),
+                    require_signature,
+                )),
+        )
+        // Added last so it runs before the signature checks
+        .route_layer(middleware::from_fn_with_state(
+            IpAllowlist::from_env("ADMIN_ALLOWED_CIDRS"),
+            require_allowed_ip,
+        ))
+        .merge(
+            Router::new()
+                .route("/metrics", get(|| async { crate::metrics::render() }))
+                .route_layer(middleware::from_fn(require_metrics_token)),
+        )
+        .layer(
+            global_rate_limit(10000)
+                .layer(rate_limit_per_ip(1, 100))
+                .layer(compression()),
+        );
+
+    let verify_router = Router::new()
+        .route("/verify", post(verify_async).layer(timeout(VERIFY_TIMEOUT)))
+        .route(
+            "/verify_sync",
+            post(verify_sync).layer(timeout(VERIFY_SYNC_TIMEOUT)),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            db.clone(),
+            require_proof_of_work,
+        ))
+        .layer(DefaultBodyLimit::max(MAX_VERIFY_BODY_SIZE));
+
     Router::new()
         .route("/", get(|| async { index() }))
-        .route("/verify", post(verify_async))
-        .route("/verify_sync", post(verify_sync))
+        .merge(verify_router)
         .layer(
             global_rate_limit(1)
                 .layer(rate_limit_per_ip(30, 1))
                 .layer(cors(Method::POST))
-                .layer(CompressionLayer::new().zstd(true)),
+                .layer(compression()),
+        )
+        .route(
+            "/status/:address",
+            get(verify_status).layer(timeout(READ_TIMEOUT)),
+        )
+        .route(
+            "/verify/challenge",
+            get(get_challenge).layer(timeout(READ_TIMEOUT)),
         )
-        .route("/status/:address", get(verify_status))
         .layer(
             global_rate_limit(10000)
                 .layer(rate_limit_per_ip(1, 100))
                 .layer(cors(Method::GET))
-                .layer(CompressionLayer::new().zstd(true)),
+                .layer(compression()),
+        )
+        .route(
+            "/job/:job_id",
+            get(get_job_status).layer(timeout(READ_TIMEOUT)),
+        )
+        .route(
+            "/logs/:job_id",
+            get(get_job_logs).layer(timeout(READ_TIMEOUT)),
+        )
+        .route(
+            "/repro/:address",
+            get(get_repro).layer(timeout(READ_TIMEOUT)),
+        )
+        .route(
+            "/authority/:address/history",
+            get(get_authority_history).layer(timeout(READ_TIMEOUT)),
         )
-        .route("/job/:job_id", get(get_job_status))
         .layer(
             global_rate_limit(10000)
                 .layer(rate_limit_per_ip(1, 100))
                 .layer(cors(Method::GET))
-                .layer(CompressionLayer::new().zstd(true)),
+                .layer(compression()),
+        )
+        .route(
+            "/verified-programs",
+            get(get_verified_programs_list).layer(timeout(READ_TIMEOUT)),
+        )
+        .route(
+            "/stats/timeseries",
+            get(get_time_series).layer(timeout(READ_TIMEOUT)),
+        )
+        .route("/feed.atom", get(get_feed).layer(timeout(READ_TIMEOUT)))
+        .route(
+            "/snapshots",
+            get(get_snapshots).layer(timeout(READ_TIMEOUT)),
+        )
+        .route(
+            "/snapshots/:name",
+            get(get_snapshot).layer(timeout(READ_TIMEOUT)),
         )
-        .route("/verified-programs", get(get_verified_programs_list))
         .layer(
             global_rate_limit(10000)
                 .layer(rate_limit_per_ip(1, 100))
                 .layer(cors(Method::GET))
-                .layer(CompressionLayer::new().zstd(true)),
+                .layer(compression()),
         )
+        // Probes aren't rate limited
+        .route("/health/ready", get(get_readiness))
+        .merge(admin_router)
         .layer(trace_layer)
+        .layer(middleware::from_fn(telemetry::propagate_request_id))
         .with_state(db)
 }
 
+// Router of the build_worker binary, which runs the builds the remote build backend dispatches
+pub fn create_worker_router() -> Router {
+    let trace_layer = TraceLayer::new_for_http()
+        .make_span_with(telemetry::request_span)
+        .on_request(DefaultOnRequest::new().level(Level::INFO))
+        .on_response(DefaultOnResponse::new().level(Level::INFO));
+
+    Router::new()
+        .route("/builds", post(run_worker_build))
+        .route_layer(middleware::from_fn(require_worker_token))
+        .layer(trace_layer)
+        .layer(middleware::from_fn(telemetry::propagate_request_id))
+}
+
 static INDEX_JSON: OnceLock<Value> = OnceLock::new();
 
 fn index() -> Json<Value> {
@@ -120,14 +334,19 @@ fn index() -> Json<Value> {
                     "params" : {
                         "repo": "Git repository URL",
                         "program_id": "Program ID of the program in mainnet",
-                        "commit": "(Optional) Commit hash of the repository. If not specified, the latest commit will be used.",
+                        "commit": "(Optional) Commit hash, branch or tag of the repository. If not specified, the latest commit of the default branch will be used. Branches and tags are resolved to a commit hash when the request is received.",
                         "lib_name": "(Optional) If the repository contains multiple programs, specify the name of the library name of the program to build and verify.",
                         "bpf_flag": "(Optional)  If the program requires cargo build-bpf (instead of cargo build-sbf), as for an Anchor program, set this flag.",
-                        "base_image": "(Optional) Base docker image to use for building the program.",
+                        "base_image": "(Optional) Base docker image to use for building the program, pinned to a digest (image@sha256:<digest>). Only allowed images are accepted.",
                         "mount_path": "(Optional) Mount path for the repository.",
                         "cargo_args": "(Optional) Cargo args to pass to the build command. It should be Vector of strings."
                     },
                 },
+                {
+                    "path": "/verify/challenge",
+                    "method": "GET",
+                    "description": "Get a proof of work challenge. When its difficulty is above 0, /verify requires the challenge in the X-Pow-Challenge header and an X-Pow-Nonce such that sha256(\"<challenge>:<nonce>\") starts with difficulty zero bits."
+                },
                 {
                     "path": "/status/:address",
                     "method": "GET",
@@ -136,10 +355,62 @@ fn index() -> Json<Value> {
                         "address": "Address of the mainnet program to check the verification status"
                     }
                 },
+                {
+                    "path": "/logs/:job_id",
+                    "method": "GET",
+                    "description": "Get the build output of a verification job",
+                    "params": {
+                        "job_id": "Job ID returned by /verify"
+                    }
+                },
+                {
+                    "path": "/repro/:address",
+                    "method": "GET",
+                    "description": "Get the solana-verify command reproducing the verification of a program locally, with the hashes it is expected to print",
+                    "params": {
+                        "address": "Address of the mainnet program"
+                    }
+                },
+                {
+                    "path": "/authority/:address/history",
+                    "method": "GET",
+                    "description": "Get the upgrade authority changes of a program observed by the crawler, newest first: changed, frozen or closed",
+                    "params": {
+                        "address": "Address of the mainnet program"
+                    }
+                },
                 {
                     "path": "/verified-programs",
                     "method": "GET",
                     "description": "Get the list of verified programs"
+                },
+                {
+                    "path": "/feed.atom",
+                    "method": "GET",
+                    "description": "Atom feed of the programs recently verified or whose on chain hash drifted, linking to their status"
+                },
+                {
+                    "path": "/snapshots",
+                    "method": "GET",
+                    "description": "List the snapshots of the verified programs dataset, newest first, with when each was taken, its number of programs and its size"
+                },
+                {
+                    "path": "/snapshots/:name",
+                    "method": "GET",
+                    "description": "Get a snapshot of the verified programs dataset as JSON: every program with its hashes, verification status and the build it was verified with",
+                    "params": {
+                        "name": "Name of a snapshot listed by /snapshots"
+                    }
+                },
+                {
+                    "path": "/stats/timeseries",
+                    "method": "GET",
+                    "description": "Number of completed or failed verification builds per interval",
+                    "params": {
+                        "metric": "verifications or failures",
+                        "interval": "(Optional) day, week or month. Defaults to day.",
+                        "limit": "(Optional) Number of intervals up to the current one. Defaults to 90."
+                    }
                 }
             ]
         })
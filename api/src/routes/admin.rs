@@ -0,0 +1,367 @@
+use crate::db::DbClient;
+use crate::logging::LogRetention;
+use crate::models::{
+    ConfigReloadResponse, CrawlRequestParams, CrawlerProgramListResponse, CrawlerRunListResponse,
+    DenylistParams, DenylistResponse, ErrorResponse, LogSearchParams, LogSearchResponse,
+    ProgramBuildOverride, ProgramBuildOverrideListResponse, ProgramBuildOverrideParams, Status,
+    VerificationStatusParams,
+};
+use crate::reload;
+use crate::validation::{ValidJson, ValidPath, ValidQuery};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CrawlerRunsQuery {
+    cluster: Option<String>,
+    limit: Option<i64>,
+}
+
+// Route handler for GET /admin/crawler/runs which lists the latest crawler run summaries
+pub(crate) async fn get_crawler_runs(
+    State(db): State<DbClient>,
+    Query(query): Query<CrawlerRunsQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match db.get_crawler_runs(query.cluster.as_deref(), limit).await {
+        Ok(runs) => (StatusCode::OK, Json(CrawlerRunListResponse { runs })).into_response(),
+        Err(err) => {
+            tracing::error!("Error getting crawler runs from database: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while getting Data from DB".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CrawlerProgramsQuery {
+    cluster: Option<String>,
+    error: Option<String>,
+    limit: Option<i64>,
+}
+
+// Route handler for GET /admin/crawler/programs which lists programs skipped or failed by the
+// crawler, optionally filtered by cluster and error category
+pub(crate) async fn get_crawler_programs(
+    State(db): State<DbClient>,
+    Query(query): Query<CrawlerProgramsQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match db
+        .get_programs_with_errors(query.cluster.as_deref(), query.error.as_deref(), limit)
+        .await
+    {
+        Ok(programs) => (
+            StatusCode::OK,
+            Json(CrawlerProgramListResponse { programs }),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("Error getting crawled programs from database: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while getting Data from DB".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for POST /admin/crawl which queues a program id or repository URL for the crawler
+pub(crate) async fn crawl_target(
+    State(db): State<DbClient>,
+    ValidJson(payload): ValidJson<CrawlRequestParams>,
+) -> Response {
+    let target = payload.target.trim();
+    let cluster = payload.cluster.as_deref().unwrap_or("mainnet");
+
+    match db.insert_crawl_request(target, cluster).await {
+        Ok(request) => (StatusCode::ACCEPTED, Json(request)).into_response(),
+        Err(err) => {
+            tracing::error!("Error queueing crawl request: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while queueing the crawl request".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for GET /admin/crawl/:id which returns the status of a crawl request
+pub(crate) async fn get_crawl_request(
+    State(db): State<DbClient>,
+    Path(request_id): Path<i32>,
+) -> Response {
+    match db.get_crawl_request(request_id).await {
+        Ok(Some(request)) => (StatusCode::OK, Json(request)).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                status: Status::Error,
+                error: "Crawl request not found".to_string(),
+            }),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("Error getting crawl request from database: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while getting Data from DB".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for GET /admin/overrides which lists the build overrides of all programs
+pub(crate) async fn get_build_overrides(State(db): State<DbClient>) -> Response {
+    match db.get_build_overrides().await {
+        Ok(overrides) => (
+            StatusCode::OK,
+            Json(ProgramBuildOverrideListResponse { overrides }),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("Error getting build overrides from database: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while getting Data from DB".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for GET /admin/overrides/:address which returns the build override of a program
+pub(crate) async fn get_build_override(
+    State(db): State<DbClient>,
+    ValidPath(VerificationStatusParams { address }): ValidPath<VerificationStatusParams>,
+) -> Response {
+    match db.get_build_override(&address).await {
+        Ok(Some(build_override)) => (StatusCode::OK, Json(build_override)).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                status: Status::Error,
+                error: "Build override not found".to_string(),
+            }),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("Error getting build override from database: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while getting Data from DB".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for PUT /admin/overrides/:address which sets the build override of a program
+pub(crate) async fn put_build_override(
+    State(db): State<DbClient>,
+    ValidPath(VerificationStatusParams { address }): ValidPath<VerificationStatusParams>,
+    ValidJson(payload): ValidJson<ProgramBuildOverrideParams>,
+) -> Response {
+    let build_override = ProgramBuildOverride {
+        program_id: address,
+        base_image: payload.base_image,
+        cargo_args: payload.cargo_args,
+        solana_verify_version: payload.solana_verify_version,
+        disabled: payload.disabled,
+        updated_at: Utc::now().naive_utc(),
+    };
+
+    match db.upsert_build_override(&build_override).await {
+        Ok(build_override) => (StatusCode::OK, Json(build_override)).into_response(),
+        Err(err) => {
+            tracing::error!("Error storing build override: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while storing the build override".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for DELETE /admin/overrides/:address which removes the build override of a program
+pub(crate) async fn delete_build_override(
+    State(db): State<DbClient>,
+    ValidPath(VerificationStatusParams { address }): ValidPath<VerificationStatusParams>,
+) -> Response {
+    match db.delete_build_override(&address).await {
+        Ok(0) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                status: Status::Error,
+                error: "Build override not found".to_string(),
+            }),
+        )
+            .into_response(),
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            tracing::error!("Error deleting build override: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while deleting the build override".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for GET /admin/denylist which lists the denied programs, repositories and commits
+pub(crate) async fn get_denylist(State(db): State<DbClient>) -> Response {
+    match db.get_denylist().await {
+        Ok(entries) => (StatusCode::OK, Json(DenylistResponse { entries })).into_response(),
+        Err(err) => {
+            tracing::error!("Error getting the denylist from database: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while getting Data from DB".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for POST /admin/denylist which denies the verification of a program, repository
+// or commit
+pub(crate) async fn add_denylist_entry(
+    State(db): State<DbClient>,
+    ValidJson(payload): ValidJson<DenylistParams>,
+) -> Response {
+    match db.insert_denylist_entry(&payload).await {
+        Ok(entry) => (StatusCode::CREATED, Json(entry)).into_response(),
+        Err(err) => {
+            tracing::error!("Error adding denylist entry: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while adding the denylist entry".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for DELETE /admin/denylist/:id which removes a denylist entry
+pub(crate) async fn delete_denylist_entry(
+    State(db): State<DbClient>,
+    Path(entry_id): Path<i32>,
+) -> Response {
+    match db.delete_denylist_entry(entry_id).await {
+        Ok(0) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                status: Status::Error,
+                error: "Denylist entry not found".to_string(),
+            }),
+        )
+            .into_response(),
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            tracing::error!("Error deleting denylist entry: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while deleting the denylist entry".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for GET /admin/logs/retention which returns the build log retention policy
+pub(crate) async fn get_log_retention() -> Json<LogRetention> {
+    Json(LogRetention::get().clone())
+}
+
+// Route handler for POST /admin/config/reload which reloads the runtime configuration
+pub(crate) async fn reload_config() -> Response {
+    match reload::reload() {
+        Ok(changed) => (StatusCode::OK, Json(ConfigReloadResponse { changed })).into_response(),
+        Err(err) => {
+            tracing::error!("Error reloading the configuration: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: err.to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for GET /admin/logs/search which finds the builds whose log matches a query
+pub(crate) async fn search_logs(
+    State(db): State<DbClient>,
+    ValidQuery(query): ValidQuery<LogSearchParams>,
+) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+
+    match db.search_build_logs(query.q.trim(), limit).await {
+        Ok(results) => (StatusCode::OK, Json(LogSearchResponse { results })).into_response(),
+        Err(err) => {
+            tracing::error!("Error searching build logs: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while searching the build logs".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
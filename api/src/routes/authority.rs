@@ -0,0 +1,40 @@
+use crate::db::DbClient;
+use crate::errors::ErrorMessages;
+use crate::models::{AuthorityHistoryResponse, ErrorResponse, Status, VerificationStatusParams};
+use crate::validation::ValidPath;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+// Route handler for GET /authority/:address/history which lists the upgrade authority changes of
+// a program observed by the crawler
+pub(crate) async fn get_authority_history(
+    State(db): State<DbClient>,
+    ValidPath(VerificationStatusParams { address }): ValidPath<VerificationStatusParams>,
+) -> Response {
+    match db.get_authority_history(&address).await {
+        Ok(changes) => Json(AuthorityHistoryResponse {
+            program_id: address,
+            changes,
+        })
+        .into_response(),
+        Err(err) => {
+            tracing::error!(
+                "Error getting the authority history of {}: {}",
+                address,
+                err
+            );
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: ErrorMessages::DB.to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::db::DbClient;
+use crate::errors::ErrorMessages;
+use crate::models::{ErrorResponse, HistoryEvent, Status, VerificationHistoryEntry};
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{NaiveDateTime, Utc};
+use std::collections::HashMap;
+use std::env;
+
+// Redis key of the rendered feed, cached for the status cache TTL
+const FEED_CACHE_KEY: &str = "feed.atom";
+const FEED_SIZE: i64 = 50;
+
+// Links of the feed point to PUBLIC_URL, the hosted API by default
+const DEFAULT_PUBLIC_URL: &str = "https://verify.osec.io";
+
+fn public_url() -> String {
+    env::var("PUBLIC_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| DEFAULT_PUBLIC_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+// Route handler for GET /feed.atom, an Atom feed of the programs recently verified or whose on
+// chain hash recently drifted, linking to their status
+pub(crate) async fn get_feed(State(db): State<DbClient>) -> Response {
+    if let Ok(feed) = db.get_cache(FEED_CACHE_KEY).await {
+        return atom(feed);
+    }
+
+    let entries = match db.get_recent_history(FEED_SIZE).await {
+        Ok(entries) => entries,
+        Err(err) => {
+            tracing::error!("Error getting the verification history: {}", err);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: ErrorMessages::DB.to_string(),
+                }),
+            )
+                .into_response();
+        }
+    };
+
+    // Names are optional, entries without one are titled with the address
+    let addresses = entries
+        .iter()
+        .map(|entry| entry.program_id.clone())
+        .collect::<Vec<String>>();
+    let names = db
+        .get_program_names(&addresses)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::error!("Error getting program names from database: {}", err);
+            Default::default()
+        });
+
+    let feed = render(&public_url(), &entries, &names);
+    let _ = db.set_cache(FEED_CACHE_KEY, &feed).await;
+    atom(feed)
+}
+
+fn atom(feed: String) -> Response {
+    (
+        [(CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        feed,
+    )
+        .into_response()
+}
+
+fn render(
+    base_url: &str,
+    entries: &[VerificationHistoryEntry],
+    names: &HashMap<String, String>,
+) -> String {
+    let updated = entries
+        .first()
+        .map(|entry| entry.recorded_at)
+        .unwrap_or_else(|| Utc::now().naive_utc());
+
+    let mut feed = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Solana Verified Programs</title>
+  <subtitle>Programs recently verified, or whose on chain hash drifted</subtitle>
+  <id>{base}/feed.atom</id>
+  <link rel="self" href="{base}/feed.atom"/>
+  <updated>{updated}</updated>
+"#,
+        base = escape(base_url),
+        updated = timestamp(updated),
+    );
+    for entry in entries {
+        let program = names.get(&entry.program_id).unwrap_or(&entry.program_id);
+        let (title, summary) = if entry.event == String::from(HistoryEvent::Drifted) {
+            (
+                format!("{} on chain hash drifted", program),
+                format!(
+                    "The on chain hash of {} changed to {}. The verified build has hash {}.",
+                    entry.program_id, entry.on_chain_hash, entry.executable_hash
+                ),
+            )
+        } else {
+            (
+                format!("{} verified", program),
+                format!(
+                    "{} matches its verified build, with hash {}.",
+                    entry.program_id, entry.on_chain_hash
+                ),
+            )
+        };
+        let link = format!("{}/status/{}", base_url, entry.program_id);
+        feed.push_str(&format!(
+            r#"  <entry>
+    <title>{title}</title>
+    <id>{link}#{id}</id>
+    <link href="{link}"/>
+    <updated>{updated}</updated>
+    <category term="{event}"/>
+    <summary>{summary}</summary>
+  </entry>
+"#,
+            title = escape(&title),
+            link = escape(&link),
+            id = entry.id,
+            updated = timestamp(entry.recorded_at),
+            event = escape(&entry.event),
+            summary = escape(&summary),
+        ));
+    }
+    feed.push_str("</feed>\n");
+    feed
+}
+
+fn timestamp(time: NaiveDateTime) -> String {
+    time.and_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,29 @@
+use crate::disk;
+use crate::models::ReadinessResponse;
+use crate::shutdown;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+// Route handler for GET /health/ready, 503 while the instance doesn't take new builds
+pub(crate) async fn get_readiness() -> Response {
+    let disk = disk::status();
+    let draining = shutdown::is_draining();
+    let ready = !draining && !disk.iter().any(|volume| volume.low);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            ready,
+            draining,
+            disk,
+        }),
+    )
+        .into_response()
+}
@@ -1,12 +1,17 @@
+use crate::builder::build_repository_url;
 use crate::db::DbClient;
-use crate::models::{JobStatus, JobVerificationResponse};
-use axum::extract::{Path, State};
+use crate::logging::get_build_errors;
+use crate::models::{JobIdParams, JobStatus, JobVerificationResponse};
+use crate::validation::ValidPath;
+use axum::extract::State;
 use axum::Json;
+use chrono::Utc;
+use std::time::Duration;
 
 // Route handler for GET /jobs/:job_id which checks the status of a job
 pub(crate) async fn get_job_status(
     State(db): State<DbClient>,
-    Path(job_id): Path<String>,
+    ValidPath(JobIdParams { job_id }): ValidPath<JobIdParams>,
 ) -> Json<JobVerificationResponse> {
     let status = db.get_job(&job_id).await;
     match status {
@@ -19,9 +24,10 @@ pub(crate) async fn get_job_status(
                         message: "Job completed".to_string(),
                         on_chain_hash: verified_build.on_chain_hash,
                         executable_hash: verified_build.executable_hash,
-                        repo_url: res.commit_hash.map_or(res.repository.clone(), |hash| {
-                            format!("{}/commit/{}", res.repository, hash)
-                        }),
+                        repo_url: build_repository_url(&res.repository, res.commit_hash.as_deref()),
+                        estimated_completion_at: None,
+                        queue_wait_seconds: res.queue_wait_seconds,
+                        build_duration_seconds: res.build_duration_seconds,
                     }),
                     Err(err) => {
                         tracing::error!("Error getting data from database: {}", err);
@@ -31,24 +37,48 @@ pub(crate) async fn get_job_status(
                             on_chain_hash: "".to_string(),
                             executable_hash: "".to_string(),
                             repo_url: "".to_string(),
+                            estimated_completion_at: None,
+                            queue_wait_seconds: res.queue_wait_seconds,
+                            build_duration_seconds: res.build_duration_seconds,
                         })
                     }
                 }
             }
             JobStatus::Failed => Json(JobVerificationResponse {
                 status: JobStatus::Failed.into(),
-                message: "Verification failed".to_string(),
-                on_chain_hash: "".to_string(),
-                executable_hash: "".to_string(),
-                repo_url: "".to_string(),
-            }),
-            JobStatus::InProgress => Json(JobVerificationResponse {
-                status: JobStatus::InProgress.into(),
-                message: "Please wait the verification was in progress".to_string(),
+                message: failure_message(&res.id).await,
                 on_chain_hash: "".to_string(),
                 executable_hash: "".to_string(),
                 repo_url: "".to_string(),
+                estimated_completion_at: None,
+                queue_wait_seconds: res.queue_wait_seconds,
+                build_duration_seconds: res.build_duration_seconds,
             }),
+            JobStatus::InProgress => {
+                let estimate = db
+                    .estimate_build_duration(&res.repository, res.bpf_flag)
+                    .await
+                    .unwrap_or_else(|err| {
+                        tracing::error!("Error estimating the build duration: {}", err);
+                        None
+                    });
+                // A job running longer than usual is expected to finish any moment
+                let estimated_completion_at = estimate.map(|seconds| {
+                    (res.created_at + Duration::from_secs_f64(seconds.max(0.0)))
+                        .max(Utc::now().naive_utc())
+                });
+
+                Json(JobVerificationResponse {
+                    status: JobStatus::InProgress.into(),
+                    message: "Please wait the verification was in progress".to_string(),
+                    on_chain_hash: "".to_string(),
+                    executable_hash: "".to_string(),
+                    repo_url: "".to_string(),
+                    estimated_completion_at,
+                    queue_wait_seconds: res.queue_wait_seconds,
+                    build_duration_seconds: res.build_duration_seconds,
+                })
+            }
         },
         Err(err) => {
             tracing::error!("Error getting data from database: {}", err);
@@ -58,7 +88,30 @@ pub(crate) async fn get_job_status(
                 on_chain_hash: "".to_string(),
                 executable_hash: "".to_string(),
                 repo_url: "".to_string(),
+                estimated_completion_at: None,
+                queue_wait_seconds: None,
+                build_duration_seconds: None,
             })
         }
     }
 }
+
+// Errors shown in the message of a failed job, the full list is stored next to the log
+const MAX_MESSAGE_ERRORS: usize = 3;
+
+async fn failure_message(build_id: &str) -> String {
+    let errors = get_build_errors(build_id).await.unwrap_or_else(|err| {
+        tracing::error!("Error reading the build errors of {}: {}", build_id, err);
+        Vec::new()
+    });
+    if errors.is_empty() {
+        return "Verification failed".to_string();
+    }
+
+    let errors = errors
+        .iter()
+        .take(MAX_MESSAGE_ERRORS)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    format!("Verification failed: {}", errors.join("; "))
+}
@@ -0,0 +1,90 @@
+use crate::db::DbClient;
+use crate::logging::{get_build_log, get_compressed_build_log};
+use crate::models::{ErrorResponse, JobIdParams, Status};
+use crate::validation::ValidPath;
+use axum::{
+    extract::State,
+    http::{
+        header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+
+// Route handler for GET /logs/:job_id which returns the output of a verification build
+pub(crate) async fn get_job_logs(
+    State(db): State<DbClient>,
+    headers: HeaderMap,
+    ValidPath(JobIdParams { job_id }): ValidPath<JobIdParams>,
+) -> Response {
+    if let Ok(build) = db.get_job(&job_id).await {
+        if build.log_purged_at.is_some() {
+            return (
+                StatusCode::GONE,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "The log of this job was purged by the retention policy".to_string(),
+                }),
+            )
+                .into_response();
+        }
+    }
+
+    // Logs are stored compressed, clients accepting zstd get them without a decompression
+    if accepts_zstd(&headers) {
+        match get_compressed_build_log(&job_id).await {
+            Ok(Some(log)) => {
+                return (
+                    StatusCode::OK,
+                    [
+                        (CONTENT_TYPE, "text/plain; charset=utf-8"),
+                        (CONTENT_ENCODING, "zstd"),
+                    ],
+                    log,
+                )
+                    .into_response()
+            }
+            Ok(None) => {}
+            Err(err) => tracing::error!("Error reading the build log of {}: {}", job_id, err),
+        }
+    }
+
+    match get_build_log(&job_id).await {
+        Ok(Some(log)) => (StatusCode::OK, log).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                status: Status::Error,
+                error: "No logs found for this job".to_string(),
+            }),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("Error reading the build log of {}: {}", job_id, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while reading the build log".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+fn accepts_zstd(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|encoding| {
+            let mut params = encoding.split(';').map(str::trim);
+            params
+                .next()
+                .is_some_and(|name| name.eq_ignore_ascii_case("zstd"))
+                && params.all(|param| !matches!(param, "q=0" | "q=0.0" | "q=0.00" | "q=0.000"))
+        })
+}
@@ -0,0 +1,135 @@
+use crate::auth;
+use crate::builder::resolve_commit;
+use crate::db::DbClient;
+use crate::disk;
+use crate::errors::ErrorMessages;
+use crate::models::{
+    ApiResponse, ErrorResponse, ProgramBuildOverride, QuotaExceededResponse, SolanaProgramBuild,
+    SolanaProgramBuildParams, Status,
+};
+use crate::quota;
+use crate::shutdown;
+use axum::{
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use std::net::SocketAddr;
+
+// Checks of POST /verify and /verify_sync before a build is looked up or submitted. Pins the
+// commit of `payload` and returns the build override of the program, or the response rejecting
+// the request.
+pub(crate) async fn preflight(
+    db: &DbClient,
+    payload: &mut SolanaProgramBuildParams,
+) -> Result<Option<ProgramBuildOverride>, (StatusCode, Json<ApiResponse>)> {
+    if shutdown::is_draining() {
+        return Err(shutdown::unavailable());
+    }
+    if disk::is_low() {
+        return Err(disk::unavailable());
+    }
+
+    let build_override = match db.get_build_override(&payload.program_id).await {
+        Ok(Some(build_override)) if build_override.disabled => {
+            return Err(error(
+                StatusCode::FORBIDDEN,
+                "Verification of this program is disabled".to_string(),
+            ));
+        }
+        Ok(build_override) => build_override,
+        Err(err) => {
+            tracing::error!("Error getting the build override: {}", err);
+            return Err(error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorMessages::DB.to_string(),
+            ));
+        }
+    };
+
+    // Pin the build to a commit so the stored params and the repo_url are reproducible
+    match resolve_commit(&payload.repository, payload.commit_hash.as_deref()).await {
+        Ok(commit_hash) => payload.commit_hash = Some(commit_hash),
+        Err(err) => {
+            tracing::warn!("Failed to resolve the commit to verify: {}", err);
+            return Err(error(
+                StatusCode::BAD_REQUEST,
+                format!("Failed to resolve the commit to verify: {}", err),
+            ));
+        }
+    }
+
+    // Checked after resolving the commit so a denied commit can't be requested through a branch
+    match db.find_denylist_entry(payload).await {
+        Ok(None) => {}
+        Ok(Some(entry)) => {
+            tracing::info!(
+                "Denied verification of {}: {}",
+                payload.program_id,
+                entry.reason
+            );
+            return Err(error(
+                StatusCode::FORBIDDEN,
+                format!("Verification of this program is denied: {}", entry.reason),
+            ));
+        }
+        Err(err) => {
+            tracing::error!("Error checking the denylist: {}", err);
+            return Err(error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorMessages::DB.to_string(),
+            ));
+        }
+    }
+
+    Ok(build_override)
+}
+
+// Count a new build against the quotas, once it is known not to be a duplicate so requests for a
+// build that is already done or in progress don't use up the quotas
+pub(crate) async fn claim_quota(
+    db: &DbClient,
+    build: &SolanaProgramBuild,
+    peer: SocketAddr,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, Json<ApiResponse>)> {
+    let client = auth::client_ip(peer.ip(), headers);
+    match quota::claim_build(db, &build.id, &build.repository, client).await {
+        Ok(None) => {}
+        Ok(Some(exceeded)) => {
+            tracing::warn!(%client, "Build quota per {} exceeded", exceeded.scope);
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(
+                    QuotaExceededResponse {
+                        status: Status::Error,
+                        error: format!("Build quota per {} exceeded", exceeded.scope),
+                        reset_at: exceeded.reset_at,
+                    }
+                    .into(),
+                ),
+            ));
+        }
+        Err(err) => {
+            tracing::error!("Error checking the build quotas: {}", err);
+            return Err(error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorMessages::Unexpected.to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn error(status: StatusCode, error: String) -> (StatusCode, Json<ApiResponse>) {
+    (
+        status,
+        Json(
+            ErrorResponse {
+                status: Status::Error,
+                error,
+            }
+            .into(),
+        ),
+    )
+}
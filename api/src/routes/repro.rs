@@ -0,0 +1,92 @@
+use crate::builder::backend::verify_from_repo_args;
+use crate::db::DbClient;
+use crate::errors::{ApiError, ErrorMessages};
+use crate::models::{
+    ErrorResponse, ReproResponse, SolanaProgramBuildParams, Status, VerificationStatusParams,
+};
+use crate::validation::ValidPath;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+// Route handler for GET /repro/:address which returns the command reproducing the stored
+// verification of a program
+pub(crate) async fn get_repro(
+    State(db): State<DbClient>,
+    ValidPath(VerificationStatusParams { address }): ValidPath<VerificationStatusParams>,
+) -> Response {
+    let verified = match db.get_verified_build(&address).await {
+        Ok(verified) => verified,
+        Err(err) => return not_found_or_error(err, &address),
+    };
+    let build = match db.get_job(&verified.solana_build_id).await {
+        Ok(build) => build,
+        Err(err) => return not_found_or_error(err, &address),
+    };
+
+    // Rebuilds apply the current build override, so the reproduction does too
+    let mut params = SolanaProgramBuildParams::from(build);
+    let mut solana_verify_version = None;
+    match db.get_build_override(&address).await {
+        Ok(Some(build_override)) => {
+            build_override.apply(&mut params);
+            solana_verify_version = build_override.solana_verify_version;
+        }
+        Ok(None) => {}
+        Err(err) => return not_found_or_error(err, &address),
+    }
+
+    let command = std::iter::once("solana-verify".to_string())
+        .chain(verify_from_repo_args(&params))
+        .map(|arg| shell_quote(&arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Json(ReproResponse {
+        program_id: verified.program_id,
+        build_id: verified.solana_build_id,
+        command,
+        solana_verify_version,
+        base_image: params.base_image,
+        on_chain_hash: verified.on_chain_hash,
+        executable_hash: verified.executable_hash,
+        verified_at: verified.verified_at,
+    })
+    .into_response()
+}
+
+fn not_found_or_error(err: ApiError, address: &str) -> Response {
+    let (status, error) = match err {
+        ApiError::Diesel(diesel::result::Error::NotFound) => (
+            StatusCode::NOT_FOUND,
+            format!("No verification of {} to reproduce", address),
+        ),
+        err => {
+            tracing::error!("Error getting the build of {}: {}", address, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorMessages::DB.to_string(),
+            )
+        }
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            status: Status::Error,
+            error,
+        }),
+    )
+        .into_response()
+}
+
+// Quote `arg` for a POSIX shell when it has characters the shell would interpret
+fn shell_quote(arg: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=@,+".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_plain) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
@@ -0,0 +1,79 @@
+use crate::db::DbClient;
+use crate::errors::ErrorMessages;
+use crate::models::{ErrorResponse, SnapshotListResponse, SnapshotParams, Status};
+use crate::snapshots::storage_key;
+use crate::storage;
+use crate::validation::ValidPath;
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+// Route handler for GET /snapshots which lists the snapshots of the verified programs dataset
+pub(crate) async fn get_snapshots(State(db): State<DbClient>) -> Response {
+    match db.get_snapshots().await {
+        Ok(snapshots) => Json(SnapshotListResponse { snapshots }).into_response(),
+        Err(err) => {
+            tracing::error!("Error getting the snapshots: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: ErrorMessages::DB.to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+// Route handler for GET /snapshots/:name which returns a snapshot listed by /snapshots
+pub(crate) async fn get_snapshot(
+    State(db): State<DbClient>,
+    ValidPath(SnapshotParams { name }): ValidPath<SnapshotParams>,
+) -> Response {
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                status: Status::Error,
+                error: "No snapshot found with this name".to_string(),
+            }),
+        )
+            .into_response()
+    };
+
+    match db.get_snapshot(&name).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return not_found(),
+        Err(err) => {
+            tracing::error!("Error getting the snapshot {}: {}", name, err);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: ErrorMessages::DB.to_string(),
+                }),
+            )
+                .into_response();
+        }
+    }
+
+    match storage::get().get(&storage_key(&name)).await {
+        Ok(Some(data)) => ([(CONTENT_TYPE, "application/json")], data).into_response(),
+        Ok(None) => not_found(),
+        Err(err) => {
+            tracing::error!("Error reading the snapshot {}: {}", name, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while reading the snapshot".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::db::DbClient;
+use crate::models::{ErrorResponse, JobStatus, Status, TimeSeriesParams, TimeSeriesResponse};
+use crate::validation::ValidQuery;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+const DEFAULT_INTERVAL: &str = "day";
+const DEFAULT_LIMIT: i32 = 90;
+
+// Route handler for GET /stats/timeseries which counts verification builds per day, week or
+// month. `verifications` counts the builds that verified their program and `failures` counts
+// failed builds.
+pub(crate) async fn get_time_series(
+    State(db): State<DbClient>,
+    ValidQuery(params): ValidQuery<TimeSeriesParams>,
+) -> Response {
+    let interval = params
+        .interval
+        .unwrap_or_else(|| DEFAULT_INTERVAL.to_string());
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    let points = match params.metric.as_str() {
+        "failures" => {
+            db.get_build_time_series(&String::from(JobStatus::Failed), &interval, limit)
+                .await
+        }
+        _ => db.get_verification_time_series(&interval, limit).await,
+    };
+
+    match points {
+        Ok(points) => (
+            StatusCode::OK,
+            Json(TimeSeriesResponse {
+                metric: params.metric,
+                interval,
+                points,
+            }),
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("Error getting the build time series from database: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: "Unexpected error while getting Data from DB".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
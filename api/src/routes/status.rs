@@ -1,29 +1,69 @@
 use crate::db::DbClient;
-use crate::models::{ApiResponse, ErrorResponse, Status, StatusResponse, VerificationStatusParams};
-use axum::extract::{Path, State};
+use crate::models::{
+    ApiResponse, ErrorResponse, JobStatus, Status, StatusReason, StatusResponse, VerificationState,
+    VerificationStatusParams,
+};
+use crate::validation::ValidPath;
+use axum::extract::State;
 use axum::Json;
 
 //  Route handler for GET /status/:address which checks if the program is verified or not
 pub(crate) async fn verify_status(
     State(db): State<DbClient>,
-    Path(VerificationStatusParams { address }): Path<VerificationStatusParams>,
+    ValidPath(VerificationStatusParams { address }): ValidPath<VerificationStatusParams>,
 ) -> Json<ApiResponse> {
-    match db.check_is_verified(address).await {
-        Ok(result) => Json(
-            StatusResponse {
-                is_verified: result.is_verified,
-                message: if result.is_verified {
-                    "On chain program verified".to_string()
-                } else {
-                    "On chain program not verified".to_string()
-                },
-                on_chain_hash: result.on_chain_hash,
-                last_verified_at: result.last_verified_at,
-                executable_hash: result.executable_hash,
-                repo_url: result.repo_url,
-            }
-            .into(),
-        ),
+    let name = match db.get_program_names(std::slice::from_ref(&address)).await {
+        Ok(mut names) => names.remove(&address),
+        Err(err) => {
+            tracing::error!("Error getting the name of {}: {}", address, err);
+            None
+        }
+    };
+    let is_closed = match db.is_program_closed(&address).await {
+        Ok(is_closed) => is_closed,
+        Err(err) => {
+            tracing::error!("Error checking whether {} is closed: {}", address, err);
+            false
+        }
+    };
+
+    match db.clone().check_is_verified(address.clone()).await {
+        Ok(result) => {
+            let has_build = !result.executable_hash.is_empty();
+            // Only programs without a verified build can be in the failed state
+            let build_failed = !has_build
+                && match db.get_latest_build_status(&address).await {
+                    Ok(status) => status == Some(JobStatus::Failed.into()),
+                    Err(err) => {
+                        tracing::error!("Error getting the latest build of {}: {}", address, err);
+                        false
+                    }
+                };
+            Json(
+                StatusResponse {
+                    status_reason: StatusReason::new(is_closed, result.is_verified, has_build),
+                    state: VerificationState::new(
+                        is_closed,
+                        result.is_verified,
+                        has_build,
+                        build_failed,
+                    ),
+                    name,
+                    is_closed,
+                    is_verified: result.is_verified,
+                    message: if result.is_verified {
+                        "On chain program verified".to_string()
+                    } else {
+                        "On chain program not verified".to_string()
+                    },
+                    on_chain_hash: result.on_chain_hash,
+                    last_verified_at: result.last_verified_at,
+                    executable_hash: result.executable_hash,
+                    repo_url: result.repo_url,
+                }
+                .into(),
+            )
+        }
         Err(err) => {
             tracing::error!("Error getting data from database: {}", err);
             Json(
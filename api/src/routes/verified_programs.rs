@@ -1,5 +1,5 @@
 use crate::db::DbClient;
-use crate::models::VerifiedProgramListResponse;
+use crate::models::{VerifiedProgramListResponse, VerifiedProgramSummary};
 use axum::{extract::State, http::StatusCode, Json};
 
 pub(crate) async fn get_verified_programs_list(
@@ -13,8 +13,25 @@ pub(crate) async fn get_verified_programs_list(
         .map(|program| program.program_id.clone())
         .collect::<Vec<String>>();
 
+    // Names are optional, the list is still served without them
+    let mut names = db
+        .get_program_names(&programs_list)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::error!("Error getting program names from database: {}", err);
+            Default::default()
+        });
+    let programs = programs_list
+        .iter()
+        .map(|program_id| VerifiedProgramSummary {
+            program_id: program_id.clone(),
+            name: names.remove(program_id),
+        })
+        .collect();
+
     let response_data = VerifiedProgramListResponse {
         verified_programs: programs_list,
+        programs,
     };
 
     (StatusCode::OK, Json(response_data))
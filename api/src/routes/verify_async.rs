@@ -1,17 +1,32 @@
-use crate::builder::verify_build;
 use crate::db::DbClient;
 use crate::errors::ErrorMessages;
 use crate::models::{
     ApiResponse, ErrorResponse, JobStatus, SolanaProgramBuild, SolanaProgramBuildParams, Status,
     VerifyResponse,
 };
-use axum::{extract::State, http::StatusCode, Json};
+use crate::routes::preflight::{claim_quota, preflight};
+use crate::telemetry;
+use crate::validation::ValidJson;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use std::net::SocketAddr;
+use tracing::Instrument;
 
 // Route handler for POST /verify which creates a new process to verify the program
 pub(crate) async fn verify_async(
     State(db): State<DbClient>,
-    Json(payload): Json<SolanaProgramBuildParams>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    ValidJson(mut payload): ValidJson<SolanaProgramBuildParams>,
 ) -> (StatusCode, Json<ApiResponse>) {
+    let build_override = match preflight(&db, &mut payload).await {
+        Ok(build_override) => build_override,
+        Err(response) => return response,
+    };
+
     let verify_build_data = SolanaProgramBuild::from(&payload);
     let uuid = verify_build_data.id.clone();
 
@@ -56,6 +71,10 @@ pub(crate) async fn verify_async(
         }
     }
 
+    if let Err(response) = claim_quota(&db, &verify_build_data, peer, &headers).await {
+        return response;
+    }
+
     // insert into database
     if let Err(e) = db.insert_build_params(&verify_build_data).await {
         tracing::error!("Error inserting into database: {:?}", e);
@@ -74,23 +93,15 @@ pub(crate) async fn verify_async(
     tracing::info!("Inserted into database");
 
     //run task in background
-    tokio::spawn(async move {
-        match verify_build(payload, &verify_build_data.id).await {
-            Ok(res) => {
-                let _ = db.insert_or_update_verified_build(&res).await;
-                let _ = db
-                    .update_build_status(&verify_build_data.id, JobStatus::Completed.into())
-                    .await;
-            }
-            Err(err) => {
-                let _ = db
-                    .update_build_status(&verify_build_data.id, JobStatus::Failed.into())
-                    .await;
-                tracing::error!("Error verifying build: {:?}", err);
-                tracing::error!("{:?}", ErrorMessages::Unexpected.to_string());
-            }
+    let span = telemetry::background_span(&verify_build_data.id);
+    tokio::spawn(
+        async move {
+            let _ = db
+                .submit_build(payload, build_override.as_ref(), &verify_build_data.id)
+                .await;
         }
-    });
+        .instrument(span),
+    );
 
     (
         StatusCode::OK,
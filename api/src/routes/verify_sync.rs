@@ -1,16 +1,32 @@
-use crate::builder::verify_build;
+use crate::builder::build_repository_url;
 use crate::db::DbClient;
-use crate::errors::ErrorMessages;
+use crate::errors::{ApiError, ErrorMessages};
 use crate::models::{
     ApiResponse, ErrorResponse, JobStatus, SolanaProgramBuild, SolanaProgramBuildParams, Status,
-    StatusResponse,
+    StatusReason, StatusResponse, VerificationState,
 };
-use axum::{extract::State, http::StatusCode, Json};
+use crate::routes::preflight::{claim_quota, preflight};
+use crate::telemetry;
+use crate::validation::ValidJson;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use std::net::SocketAddr;
+use tracing::Instrument;
 
 pub(crate) async fn verify_sync(
     State(db): State<DbClient>,
-    Json(payload): Json<SolanaProgramBuildParams>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    ValidJson(mut payload): ValidJson<SolanaProgramBuildParams>,
 ) -> (StatusCode, Json<ApiResponse>) {
+    let build_override = match preflight(&db, &mut payload).await {
+        Ok(build_override) => build_override,
+        Err(response) => return response,
+    };
+
     let verify_build_data = SolanaProgramBuild::from(&payload);
 
     // First check if the program is already verified
@@ -24,6 +40,19 @@ pub(crate) async fn verify_sync(
                     StatusCode::CONFLICT,
                     Json(
                         StatusResponse {
+                            name: None,
+                            is_closed: false,
+                            status_reason: StatusReason::new(
+                                false,
+                                verified_build.is_verified,
+                                true,
+                            ),
+                            state: VerificationState::new(
+                                false,
+                                verified_build.is_verified,
+                                true,
+                                false,
+                            ),
                             is_verified: verified_build.is_verified,
                             message: if verified_build.is_verified {
                                 "On chain program verified".to_string()
@@ -32,11 +61,10 @@ pub(crate) async fn verify_sync(
                             },
                             on_chain_hash: verified_build.on_chain_hash,
                             executable_hash: verified_build.executable_hash,
-                            repo_url: verify_build_data
-                                .commit_hash
-                                .map_or(verify_build_data.repository.clone(), |hash| {
-                                    format!("{}/commit/{}", verify_build_data.repository, hash)
-                                }),
+                            repo_url: build_repository_url(
+                                &verify_build_data.repository,
+                                verify_build_data.commit_hash.as_deref(),
+                            ),
                             last_verified_at: Some(verified_build.verified_at),
                         }
                         .into(),
@@ -48,15 +76,18 @@ pub(crate) async fn verify_sync(
                     StatusCode::CONFLICT,
                     Json(
                         StatusResponse {
+                            name: None,
+                            is_closed: false,
+                            status_reason: StatusReason::new(false, false, false),
+                            state: VerificationState::new(false, false, false, false),
                             is_verified: false,
                             message: "Build verification already in progress".to_string(),
                             on_chain_hash: "".to_string(),
                             executable_hash: "".to_string(),
-                            repo_url: verify_build_data
-                                .commit_hash
-                                .map_or(verify_build_data.repository.clone(), |hash| {
-                                    format!("{}/commit/{}", verify_build_data.repository, hash)
-                                }),
+                            repo_url: build_repository_url(
+                                &verify_build_data.repository,
+                                verify_build_data.commit_hash.as_deref(),
+                            ),
                             last_verified_at: None,
                         }
                         .into(),
@@ -70,6 +101,10 @@ pub(crate) async fn verify_sync(
         }
     }
 
+    if let Err(response) = claim_quota(&db, &verify_build_data, peer, &headers).await {
+        return response;
+    }
+
     // insert into database
     if let Err(e) = db.insert_build_params(&verify_build_data).await {
         tracing::error!("Error inserting into database: {:?}", e);
@@ -87,51 +122,59 @@ pub(crate) async fn verify_sync(
 
     tracing::info!("Inserted into database");
 
-    // run task and wait for it to finish
-    match verify_build(payload, &verify_build_data.id).await {
-        Ok(res) => {
-            let _ = db.insert_or_update_verified_build(&res).await;
-            let _ = db
-                .update_build_status(&verify_build_data.id, JobStatus::Completed.into())
-                .await;
-            (
-                StatusCode::OK,
-                Json(
-                    StatusResponse {
-                        is_verified: res.is_verified,
-                        message: if res.is_verified {
-                            "On chain program verified".to_string()
-                        } else {
-                            "On chain program not verified".to_string()
-                        },
-                        on_chain_hash: res.on_chain_hash,
-                        executable_hash: res.executable_hash,
-                        last_verified_at: Some(res.verified_at),
-                        repo_url: verify_build_data
-                            .commit_hash
-                            .map_or(verify_build_data.repository.clone(), |hash| {
-                                format!("{}/commit/{}", verify_build_data.repository, hash)
-                            }),
-                    }
-                    .into(),
-                ),
-            )
-        }
-        Err(err) => {
-            let _ = db
-                .update_build_status(&verify_build_data.id, JobStatus::Failed.into())
-                .await;
-            tracing::error!("Error verifying build: {:?}", err);
-            (
-                StatusCode::OK,
-                Json(
-                    ErrorResponse {
-                        status: Status::Error,
-                        error: ErrorMessages::Unexpected.to_string(),
-                    }
-                    .into(),
-                ),
-            )
+    // Run the build in its own task and only wait for it here, so a timeout of the request
+    // stops the wait but not the build, which still records its outcome
+    let span = telemetry::background_span(&verify_build_data.id);
+    let build = tokio::spawn({
+        let db = db.clone();
+        let build_id = verify_build_data.id.clone();
+        async move {
+            db.submit_build(payload, build_override.as_ref(), &build_id)
+                .await
         }
+        .instrument(span)
+    });
+    let result = match build.await {
+        Ok(Ok(Some(res))) => Ok(res),
+        Ok(Ok(None)) => db.wait_for_build(&verify_build_data.id).await,
+        Ok(Err(err)) => Err(err),
+        Err(err) => Err(ApiError::Custom(format!("Build task failed: {}", err))),
+    };
+    match result {
+        Ok(res) => (
+            StatusCode::OK,
+            Json(
+                StatusResponse {
+                    name: None,
+                    is_closed: false,
+                    status_reason: StatusReason::new(false, res.is_verified, true),
+                    state: VerificationState::new(false, res.is_verified, true, false),
+                    is_verified: res.is_verified,
+                    message: if res.is_verified {
+                        "On chain program verified".to_string()
+                    } else {
+                        "On chain program not verified".to_string()
+                    },
+                    on_chain_hash: res.on_chain_hash,
+                    executable_hash: res.executable_hash,
+                    last_verified_at: Some(res.verified_at),
+                    repo_url: build_repository_url(
+                        &verify_build_data.repository,
+                        verify_build_data.commit_hash.as_deref(),
+                    ),
+                }
+                .into(),
+            ),
+        ),
+        Err(_) => (
+            StatusCode::OK,
+            Json(
+                ErrorResponse {
+                    status: Status::Error,
+                    error: ErrorMessages::Unexpected.to_string(),
+                }
+                .into(),
+            ),
+        ),
     }
 }
@@ -0,0 +1,63 @@
+use crate::builder::backend::{BuildRequest, Builder, CliBuilder};
+use crate::builder::remote::{WorkerBuildRequest, WorkerBuildResponse};
+use crate::models::{ErrorResponse, Status};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::env;
+use std::sync::OnceLock;
+use tokio::sync::Semaphore;
+
+// Builds a worker runs at the same time, read from BUILD_WORKER_SLOTS. One by default.
+fn build_slots() -> &'static Semaphore {
+    static SLOTS: OnceLock<Semaphore> = OnceLock::new();
+    SLOTS.get_or_init(|| {
+        let slots = env::var("BUILD_WORKER_SLOTS")
+            .ok()
+            .and_then(|slots| slots.parse::<usize>().ok())
+            .filter(|slots| *slots > 0)
+            .unwrap_or(1);
+        Semaphore::new(slots)
+    })
+}
+
+// Route handler for POST /builds on build workers which runs a build dispatched by the API
+pub(crate) async fn run_worker_build(Json(request): Json<WorkerBuildRequest>) -> Response {
+    // Busy workers turn builds away right away so the API can offer them to another worker
+    let Ok(_slot) = build_slots().try_acquire() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                status: Status::Error,
+                error: "All build slots are in use".to_string(),
+            }),
+        )
+            .into_response();
+    };
+
+    tracing::info!("Running build {}", request.build_id);
+    let outcome = CliBuilder
+        .execute(BuildRequest {
+            build_id: &request.build_id,
+            params: &request.params,
+            solana_verify_version: request.solana_verify_version.as_deref(),
+        })
+        .await;
+
+    match outcome {
+        Ok(outcome) => Json(WorkerBuildResponse::from(outcome)).into_response(),
+        Err(err) => {
+            tracing::error!("Build {} failed to run: {}", request.build_id, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    status: Status::Error,
+                    error: err.to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
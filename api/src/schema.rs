@@ -1,3 +1,97 @@
+diesel::table! {
+    authority_history (id) {
+        id -> Int4,
+        program_address -> Varchar,
+        cluster -> Varchar,
+        change -> Varchar,
+        old_authority -> Nullable<Varchar>,
+        new_authority -> Nullable<Varchar>,
+        slot -> Nullable<Int8>,
+        observed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    crawl_requests (id) {
+        id -> Int4,
+        target -> Varchar,
+        cluster -> Varchar,
+        status -> Varchar,
+        error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    crawler_runs (id) {
+        id -> Int4,
+        kind -> Varchar,
+        started_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+        programs_scanned -> Int4,
+        security_txt_found -> Int4,
+        verification_json_found -> Int4,
+        verifications_submitted -> Int4,
+        failures -> Jsonb,
+        cluster -> Varchar,
+    }
+}
+
+diesel::table! {
+    dataset_snapshots (id) {
+        id -> Int4,
+        name -> Varchar,
+        taken_at -> Timestamp,
+        programs -> Int4,
+        size_bytes -> Int8,
+    }
+}
+
+diesel::table! {
+    denylist (id) {
+        id -> Int4,
+        program_id -> Nullable<Varchar>,
+        repository -> Nullable<Varchar>,
+        commit_hash -> Nullable<Varchar>,
+        reason -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    mainnet_programs (id) {
+        id -> Int4,
+        project_name -> Nullable<Varchar>,
+        program_address -> Varchar,
+        buffer_address -> Varchar,
+        github_repo -> Nullable<Varchar>,
+        has_security_txt -> Bool,
+        is_closed -> Bool,
+        is_success -> Bool,
+        is_processed -> Bool,
+        updated_at -> Timestamp,
+        last_deployed_slot -> Nullable<Int8>,
+        update_authority -> Nullable<Varchar>,
+        repo_host -> Nullable<Varchar>,
+        last_error -> Nullable<Varchar>,
+        last_error_message -> Nullable<Text>,
+        last_error_at -> Nullable<Timestamp>,
+        cluster -> Varchar,
+    }
+}
+
+diesel::table! {
+    program_build_overrides (program_id) {
+        program_id -> Varchar,
+        base_image -> Nullable<Varchar>,
+        cargo_args -> Nullable<Array<Text>>,
+        solana_verify_version -> Nullable<Varchar>,
+        disabled -> Bool,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     solana_program_builds (id) {
         id -> Varchar,
@@ -11,6 +105,22 @@ diesel::table! {
         bpf_flag -> Bool,
         created_at -> Timestamp,
         status -> Varchar,
+        finished_at -> Nullable<Timestamp>,
+        queue_wait_seconds -> Nullable<Float8>,
+        build_duration_seconds -> Nullable<Float8>,
+        log_purged_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    verification_history (id) {
+        id -> Int4,
+        program_id -> Varchar,
+        event -> Varchar,
+        on_chain_hash -> Varchar,
+        executable_hash -> Varchar,
+        solana_build_id -> Nullable<Varchar>,
+        recorded_at -> Timestamp,
     }
 }
 
@@ -28,4 +138,15 @@ diesel::table! {
 
 diesel::joinable!(verified_programs -> solana_program_builds (solana_build_id));
 
-diesel::allow_tables_to_appear_in_same_query!(solana_program_builds, verified_programs,);
+diesel::allow_tables_to_appear_in_same_query!(
+    authority_history,
+    crawl_requests,
+    crawler_runs,
+    dataset_snapshots,
+    denylist,
+    mainnet_programs,
+    program_build_overrides,
+    solana_program_builds,
+    verification_history,
+    verified_programs,
+);
@@ -0,0 +1,133 @@
+// Checks run at startup so a misconfigured deployment fails fast with a clear report instead of
+// failing every verification later. SKIP_STARTUP_CHECKS=true skips them, e.g. for local
+// development without a container runtime.
+
+use serde_json::json;
+use std::env;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::builder::{backend, rpc_url};
+use crate::container;
+use crate::egress;
+use crate::role;
+use crate::storage;
+
+// Oldest solana-verify release with the `verify-from-repo` command used by the builder
+const MIN_SOLANA_VERIFY_VERSION: (u64, u64, u64) = (0, 2, 0);
+
+const RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+const PROBE_KEY: &str = "self-check";
+
+// Run every check and panic with the failures if any of them failed
+pub async fn run() {
+    if env::var("SKIP_STARTUP_CHECKS").is_ok_and(|skip| skip == "true") {
+        tracing::warn!("Skipping startup checks");
+        return;
+    }
+
+    let mut results = vec![
+        ("storage", check_storage().await),
+        ("rpc", check_rpc().await),
+    ];
+    // Instances with the api role leave the builds to the workers
+    if role::get().runs_builds() {
+        results.push(("solana-verify", check_solana_verify().await));
+        if backend::builds_locally() {
+            results.push(("container runtime", container::get().version().await));
+            if let Some(result) = egress::check().await {
+                results.push(("build egress", result));
+            }
+        }
+    }
+
+    let mut failures = Vec::new();
+    for (name, result) in results {
+        match result {
+            Ok(detail) => tracing::info!("Startup check {} passed: {}", name, detail),
+            Err(err) => {
+                tracing::error!("Startup check {} failed: {}", name, err);
+                failures.push(format!("{}: {}", name, err));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!("Startup checks failed:\n{}", failures.join("\n"));
+    }
+}
+
+async fn check_solana_verify() -> Result<String, String> {
+    let output = Command::new("solana-verify")
+        .arg("--version")
+        .output()
+        .await
+        .map_err(|err| format!("failed to run solana-verify: {}", err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "solana-verify --version exited with {}",
+            output.status
+        ));
+    }
+
+    // Prints e.g. "solana-verify 0.2.11"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout
+        .split_whitespace()
+        .last()
+        .and_then(parse_version)
+        .ok_or_else(|| format!("unrecognized version output {:?}", stdout.trim()))?;
+    if version < MIN_SOLANA_VERIFY_VERSION {
+        let (major, minor, patch) = MIN_SOLANA_VERIFY_VERSION;
+        return Err(format!(
+            "found {}, at least {}.{}.{} is required",
+            stdout.trim(),
+            major,
+            minor,
+            patch
+        ));
+    }
+    Ok(stdout.trim().to_string())
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.').map(|part| part.parse().ok());
+    Some((parts.next()??, parts.next()??, parts.next()??))
+}
+
+// Write, read back and delete a probe file
+async fn check_storage() -> Result<String, String> {
+    let storage = storage::get();
+    storage
+        .put(PROBE_KEY, b"ok".to_vec())
+        .await
+        .map_err(|err| format!("storage is not writable: {}", err))?;
+    let read = storage
+        .get(PROBE_KEY)
+        .await
+        .map_err(|err| format!("storage is not readable: {}", err))?;
+    if read.as_deref() != Some(b"ok".as_slice()) {
+        return Err("storage did not return the probe file".to_string());
+    }
+    storage
+        .delete(PROBE_KEY)
+        .await
+        .map_err(|err| format!("failed to delete the probe file: {}", err))?;
+    Ok("writable".to_string())
+}
+
+async fn check_rpc() -> Result<String, String> {
+    let url = rpc_url();
+    let response = reqwest::Client::new()
+        .post(&url)
+        .timeout(RPC_TIMEOUT)
+        .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": "getHealth" }))
+        .send()
+        .await
+        .map_err(|err| format!("{} is not reachable: {}", url, err))?;
+    if !response.status().is_success() {
+        return Err(format!("{} responded with {}", url, response.status()));
+    }
+    Ok(format!("{} responded", url))
+}
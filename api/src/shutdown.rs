@@ -0,0 +1,146 @@
+// Graceful shutdown. On SIGTERM or Ctrl-C the API stops taking verification requests and gives the
+// running builds SHUTDOWN_GRACE_SECS, 10 minutes by default, to finish. Builds still running after
+// that are recorded so the next instance to start resumes them instead of leaving them in
+// progress forever, or go back to the queue when a worker took them from it.
+
+use axum::{http::StatusCode, Json};
+use std::collections::HashSet;
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::Instrument;
+
+use crate::db::DbClient;
+use crate::models::{ApiResponse, ErrorResponse, Status};
+use crate::queue;
+use crate::telemetry;
+
+const DEFAULT_GRACE_SECS: u64 = 10 * 60;
+
+fn draining() -> &'static watch::Sender<bool> {
+    static DRAINING: OnceLock<watch::Sender<bool>> = OnceLock::new();
+    DRAINING.get_or_init(|| watch::channel(false).0)
+}
+
+// Ids of the builds running on this instance
+fn running() -> &'static watch::Sender<HashSet<String>> {
+    static RUNNING: OnceLock<watch::Sender<HashSet<String>>> = OnceLock::new();
+    RUNNING.get_or_init(|| watch::channel(HashSet::new()).0)
+}
+
+// Whether the instance is shutting down and turns new builds away
+pub fn is_draining() -> bool {
+    *draining().borrow()
+}
+
+// Response of the verification routes while draining, other instances take the request
+pub(crate) fn unavailable() -> (StatusCode, Json<ApiResponse>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(
+            ErrorResponse {
+                status: Status::Error,
+                error: "The server is shutting down, please retry".to_string(),
+            }
+            .into(),
+        ),
+    )
+}
+
+// A build running on this instance, tracked until dropped
+pub struct RunningBuild(String);
+
+pub fn track_build(build_id: &str) -> RunningBuild {
+    running().send_modify(|running| {
+        running.insert(build_id.to_string());
+    });
+    RunningBuild(build_id.to_string())
+}
+
+impl Drop for RunningBuild {
+    fn drop(&mut self) {
+        running().send_modify(|running| {
+            running.remove(&self.0);
+        });
+    }
+}
+
+// Wait for SIGTERM or Ctrl-C, then start draining
+pub async fn listen() {
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    tracing::info!("Shutting down, no longer accepting builds");
+    draining().send_replace(true);
+}
+
+// Resolves once the instance started draining
+pub async fn requested() {
+    let mut draining = draining().subscribe();
+    let _ = draining.wait_for(|draining| *draining).await;
+}
+
+// Wait for the running builds to finish within the grace period, and record those that don't
+pub async fn drain(db: &DbClient) {
+    let grace = env::var("SHUTDOWN_GRACE_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_GRACE_SECS);
+    let mut running_builds = running().subscribe();
+    let count = running_builds.borrow().len();
+    if count > 0 {
+        tracing::info!("Waiting up to {}s for {} running builds", grace, count);
+    }
+    let drained = tokio::time::timeout(
+        Duration::from_secs(grace),
+        running_builds.wait_for(|running| running.is_empty()),
+    )
+    .await
+    .is_ok();
+    if drained {
+        return;
+    }
+
+    let interrupted = running().borrow().iter().cloned().collect::<Vec<_>>();
+    tracing::warn!("Interrupting {} builds", interrupted.len());
+    // Builds from the queue go back to it for the other workers, the others are resumed by the
+    // next instance to start
+    let released = db
+        .release_queued_builds(queue::worker_id(), &interrupted)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::error!("Error releasing the queued builds: {}", err);
+            Vec::new()
+        });
+    let interrupted = interrupted
+        .into_iter()
+        .filter(|build_id| !released.contains(build_id))
+        .collect::<Vec<_>>();
+    if let Err(err) = db.record_interrupted_builds(&interrupted).await {
+        tracing::error!("Error recording the interrupted builds: {}", err);
+    }
+}
+
+// Resume the builds interrupted by the shutdown of an instance. Each is resumed by a single
+// instance since the first one to start takes it.
+pub fn resume_interrupted_builds(db: DbClient) {
+    tokio::spawn(async move {
+        loop {
+            let build_id = match db.take_interrupted_build().await {
+                Ok(Some(build_id)) => build_id,
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::error!("Error getting the interrupted builds: {}", err);
+                    break;
+                }
+            };
+            let span = telemetry::background_span(&build_id);
+            let db = db.clone();
+            tokio::spawn(async move { db.run_stored_build(&build_id).await }.instrument(span));
+        }
+    });
+}
@@ -0,0 +1,132 @@
+// Snapshots of the verified programs dataset, so researchers get point in time data instead of
+// reconstructing it from the live API. Every SNAPSHOT_INTERVAL_SECS, a day by default, one of the
+// instances running the background jobs writes the complete dataset as JSON to the storage, see
+// storage.rs, under snapshots/, and records it in the dataset_snapshots table listed by
+// /snapshots.
+
+use chrono::{DateTime, Utc};
+use std::env;
+use std::time::Duration;
+
+use crate::db::DbClient;
+use crate::models::{DatasetSnapshot, Snapshot, SnapshotProgram};
+use crate::storage;
+use crate::Result;
+
+const DEFAULT_SNAPSHOT_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+// How often instances check whether the snapshot of the current interval was taken
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+fn interval() -> u64 {
+    env::var("SNAPSHOT_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_SECS)
+}
+
+// Storage key of a snapshot
+pub fn storage_key(name: &str) -> String {
+    format!("snapshots/{}", name)
+}
+
+// Name of the snapshot of the interval `now` is in, named after the start of the interval
+fn snapshot_name(now: DateTime<Utc>, interval: u64) -> String {
+    let start = now.timestamp() - now.timestamp().rem_euclid(interval as i64);
+    let start = DateTime::from_timestamp(start, 0).unwrap_or(now);
+    format!("verified-programs-{}.json", start.format("%Y%m%dT%H%M%SZ"))
+}
+
+pub fn spawn_job(db: DbClient) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            match take_due_snapshot(&db).await {
+                Ok(Some(snapshot)) => tracing::info!(
+                    "Snapshot {} taken with {} programs",
+                    snapshot.name,
+                    snapshot.programs
+                ),
+                Ok(None) => {}
+                Err(err) => tracing::error!("Snapshot failed: {}", err),
+            }
+        }
+    });
+}
+
+// Take the snapshot of the current interval unless it was taken or claimed by another instance
+async fn take_due_snapshot(db: &DbClient) -> Result<Option<DatasetSnapshot>> {
+    let interval = interval();
+    let name = snapshot_name(Utc::now(), interval);
+    if db.get_snapshot(&name).await?.is_some() || !db.claim_snapshot(&name, interval).await? {
+        return Ok(None);
+    }
+    match take_snapshot(db, name.clone()).await {
+        Ok(snapshot) => Ok(Some(snapshot)),
+        Err(err) => {
+            // Without the claim, the next check of this or another instance retries the snapshot
+            if let Err(release_err) = db.release_snapshot(&name).await {
+                tracing::error!(
+                    "Failed to release the claim of snapshot {}: {}",
+                    name,
+                    release_err
+                );
+            }
+            Err(err)
+        }
+    }
+}
+
+async fn take_snapshot(db: &DbClient, name: String) -> Result<DatasetSnapshot> {
+    let taken_at = Utc::now().naive_utc();
+    let dataset = db.get_snapshot_dataset().await?;
+
+    // Names are optional, the snapshot is still taken without them
+    let addresses = dataset
+        .iter()
+        .map(|(program, _)| program.program_id.clone())
+        .collect::<Vec<String>>();
+    let mut names = db
+        .get_program_names(&addresses)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::error!("Error getting program names from database: {}", err);
+            Default::default()
+        });
+
+    let programs = dataset
+        .into_iter()
+        .map(|(program, build)| SnapshotProgram {
+            name: names.remove(&program.program_id),
+            program_id: program.program_id,
+            is_verified: program.is_verified,
+            on_chain_hash: program.on_chain_hash,
+            executable_hash: program.executable_hash,
+            verified_at: program.verified_at,
+            build_id: build.id,
+            repository: build.repository,
+            commit_hash: build.commit_hash,
+            lib_name: build.lib_name,
+            base_docker_image: build.base_docker_image,
+            mount_path: build.mount_path,
+            cargo_args: build.cargo_args,
+            bpf_flag: build.bpf_flag,
+        })
+        .collect::<Vec<SnapshotProgram>>();
+    let count = programs.len() as i32;
+    let data = serde_json::to_vec(&Snapshot { taken_at, programs })?;
+
+    let snapshot = DatasetSnapshot {
+        name,
+        taken_at,
+        programs: count,
+        size_bytes: data.len() as i64,
+    };
+    storage::get()
+        .put(&storage_key(&snapshot.name), data)
+        .await?;
+    db.insert_snapshot(&snapshot).await?;
+    Ok(snapshot)
+}
@@ -0,0 +1,179 @@
+// Storage for build logs and other files that must outlive a single host.
+//
+// The backend is selected with STORAGE_BACKEND:
+// - local (default): files under STORAGE_PATH, ./storage by default
+// - s3: STORAGE_BUCKET, credentials and region from the usual AWS_* variables
+// - gcs: STORAGE_BUCKET, credentials from the usual GOOGLE_* variables
+
+use async_trait::async_trait;
+use object_store::aws::AmazonS3Builder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use std::env;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+use crate::errors::ApiError;
+use crate::Result;
+
+const DEFAULT_STORAGE_PATH: &str = "./storage";
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()>;
+
+    // None when nothing is stored under the key
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    // Size in bytes, None when nothing is stored under the key
+    async fn size(&self, key: &str) -> Result<Option<u64>>;
+}
+
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, data).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.root.join(key)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.root.join(key)).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn size(&self, key: &str) -> Result<Option<u64>> {
+        match tokio::fs::metadata(self.root.join(key)).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+// S3 or GCS bucket
+pub struct BucketStorage {
+    store: Box<dyn ObjectStore>,
+}
+
+#[async_trait]
+impl Storage for BucketStorage {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        self.store.put(&ObjectPath::from(key), data.into()).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match self.store.get(&ObjectPath::from(key)).await {
+            Ok(result) => Ok(Some(result.bytes().await?.to_vec())),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match self.store.delete(&ObjectPath::from(key)).await {
+            Err(object_store::Error::NotFound { .. }) => Ok(()),
+            result => result.map_err(Into::into),
+        }
+    }
+
+    async fn size(&self, key: &str) -> Result<Option<u64>> {
+        match self.store.head(&ObjectPath::from(key)).await {
+            Ok(meta) => Ok(Some(meta.size as u64)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+static STORAGE: OnceLock<Arc<dyn Storage>> = OnceLock::new();
+
+// Build the configured backend. Panics on an invalid configuration so it surfaces at startup.
+pub fn init() {
+    STORAGE.get_or_init(|| from_env().expect("Invalid storage configuration"));
+}
+
+pub fn get() -> Arc<dyn Storage> {
+    STORAGE
+        .get_or_init(|| from_env().expect("Invalid storage configuration"))
+        .clone()
+}
+
+// Directory of the local backend
+fn local_path() -> String {
+    env::var("STORAGE_PATH")
+        .ok()
+        .filter(|path| !path.is_empty())
+        .unwrap_or_else(|| DEFAULT_STORAGE_PATH.to_string())
+}
+
+// Directory the logs are stored in, None when they're stored in a bucket
+pub fn local_dir() -> Option<PathBuf> {
+    match env::var("STORAGE_BACKEND").unwrap_or_default().as_str() {
+        "" | "local" => Some(PathBuf::from(local_path())),
+        _ => None,
+    }
+}
+
+fn from_env() -> Result<Arc<dyn Storage>> {
+    let backend = env::var("STORAGE_BACKEND").unwrap_or_default();
+    let bucket = || {
+        env::var("STORAGE_BUCKET")
+            .ok()
+            .filter(|bucket| !bucket.is_empty())
+            .ok_or_else(|| ApiError::Custom("STORAGE_BUCKET must be set".to_string()))
+    };
+
+    let storage: Arc<dyn Storage> = match backend.as_str() {
+        "" | "local" => Arc::new(LocalStorage::new(local_path())),
+        "s3" => Arc::new(BucketStorage {
+            store: Box::new(
+                AmazonS3Builder::from_env()
+                    .with_bucket_name(bucket()?)
+                    .build()?,
+            ),
+        }),
+        "gcs" => Arc::new(BucketStorage {
+            store: Box::new(
+                GoogleCloudStorageBuilder::from_env()
+                    .with_bucket_name(bucket()?)
+                    .build()?,
+            ),
+        }),
+        backend => {
+            return Err(ApiError::Custom(format!(
+                "STORAGE_BACKEND must be one of local, s3 or gcs, got {}",
+                backend
+            )))
+        }
+    };
+    Ok(storage)
+}
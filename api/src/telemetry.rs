@@ -0,0 +1,146 @@
+// Tracing setup. Logs always go to stdout as JSON, spans are also exported to an OpenTelemetry
+// collector over OTLP when OTEL_EXPORTER_OTLP_ENDPOINT is set.
+
+use axum::http::{HeaderMap, HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use std::env;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+const DEFAULT_SERVICE_NAME: &str = "verified-programs-api";
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    // Request id of the request handled by the current task
+    static REQUEST_ID: String;
+}
+
+pub fn init() {
+    let otel_layer = env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty())
+        .map(|endpoint| {
+            global::set_text_map_propagator(TraceContextPropagator::new());
+
+            let service_name = env::var("OTEL_SERVICE_NAME")
+                .ok()
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+            let tracer =
+                opentelemetry_otlp::new_pipeline()
+                    .tracing()
+                    .with_exporter(
+                        opentelemetry_otlp::new_exporter()
+                            .tonic()
+                            .with_endpoint(endpoint),
+                    )
+                    .with_trace_config(trace::config().with_resource(Resource::new([
+                        KeyValue::new("service.name", service_name),
+                    ])))
+                    .install_batch(runtime::Tokio)
+                    .expect("Failed to install the OTLP exporter");
+
+            tracing_opentelemetry::layer().with_tracer(tracer)
+        });
+
+    // LOG_FORMAT=text keeps the human readable output for local development
+    let text_logs = env::var("LOG_FORMAT").is_ok_and(|format| format == "text");
+    let (json_layer, text_layer) = if text_logs {
+        (None, Some(tracing_subscriber::fmt::layer()))
+    } else {
+        (Some(tracing_subscriber::fmt::layer().json()), None)
+    };
+
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(json_layer)
+        .with(text_layer)
+        .with(otel_layer)
+        .init();
+}
+
+// Span of an incoming request. Continues the trace of the caller when it sends a traceparent
+// header. Headers aren't recorded so the admin Authorization header never reaches the collector.
+pub fn request_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id,
+    );
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+    span
+}
+
+// Span of a verification running in the background. It starts its own trace, linked to the span
+// that queued it, so a long build doesn't stretch the request trace.
+pub fn background_span(build_id: &str) -> Span {
+    let span = tracing::info_span!(
+        parent: None,
+        "verification",
+        build_id,
+        request_id = tracing::field::Empty,
+    );
+    if let Ok(request_id) = REQUEST_ID.try_with(Clone::clone) {
+        span.record("request_id", request_id);
+    }
+    span.follows_from(Span::current());
+    span
+}
+
+// Middleware giving every request an id, taken from the X-Request-Id header when the caller sends
+// a valid one. The id is echoed in the response and attached to the logs of the request and of
+// the verifications it starts.
+pub async fn propagate_request_id<B>(mut request: Request<B>, next: Next<B>) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|request_id| is_valid_request_id(request_id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let header =
+        HeaderValue::from_str(&request_id).expect("Request ids are visible ASCII characters");
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header.clone());
+
+    let mut response = REQUEST_ID.scope(request_id, next.run(request)).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, header);
+    response
+}
+
+fn is_valid_request_id(request_id: &str) -> bool {
+    (1..=128).contains(&request_id.len()) && request_id.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
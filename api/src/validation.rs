@@ -0,0 +1,672 @@
+// Validation of request input before it reaches the handlers. Invalid requests are rejected with
+// a 400, or a 413 for bodies over the limit of the route, and a machine readable error code instead
+// of failing deep in an RPC call or a build.
+
+use async_trait::async_trait;
+use axum::extract::{FromRequest, FromRequestParts, Path, Query};
+use axum::http::request::Parts;
+use axum::http::{Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::de::DeserializeOwned;
+use std::env;
+use std::sync::OnceLock;
+
+use crate::builder::is_full_commit_hash;
+use crate::models::{
+    CrawlRequestParams, DenylistParams, JobIdParams, LogSearchParams, ProgramBuildOverrideParams,
+    SnapshotParams, SolanaProgramBuildParams, Status, TimeSeriesParams, ValidationErrorResponse,
+    VerificationStatusParams,
+};
+
+pub const CLUSTERS: [&str; 3] = ["mainnet", "devnet", "testnet"];
+
+const MAX_URL_LEN: usize = 256;
+const MAX_CARGO_ARGS: usize = 32;
+const MAX_CARGO_ARG_LEN: usize = 128;
+const MAX_GIT_REF_LEN: usize = 128;
+const MAX_IMAGE_LEN: usize = 256;
+const MAX_VERSION_LEN: usize = 32;
+const MAX_REASON_LEN: usize = 512;
+const MAX_SEARCH_QUERY_LEN: usize = 256;
+const MAX_SEARCH_LIMIT: i64 = 500;
+
+const TIME_SERIES_METRICS: [&str; 2] = ["verifications", "failures"];
+const TIME_SERIES_INTERVALS: [&str; 3] = ["day", "week", "month"];
+const MAX_TIME_SERIES_LIMIT: i32 = 366;
+
+#[derive(Debug)]
+pub struct ValidationError {
+    status: StatusCode,
+    code: &'static str,
+    field: &'static str,
+    message: String,
+}
+
+impl ValidationError {
+    pub fn new(code: &'static str, field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            code,
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for ValidationError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            Json(ValidationErrorResponse {
+                status: Status::Error,
+                error: self.message,
+                code: self.code.to_string(),
+                field: self.field.to_string(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationError>;
+}
+
+// JSON body that passed validation
+pub struct ValidJson<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    Json<T>: FromRequest<S, B, Rejection = axum::extract::rejection::JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = ValidationError;
+
+    async fn from_request(request: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(|err| match err.status() {
+                StatusCode::PAYLOAD_TOO_LARGE => ValidationError {
+                    status: StatusCode::PAYLOAD_TOO_LARGE,
+                    ..ValidationError::new("body_too_large", "body", "Request body is too large")
+                },
+                _ => ValidationError::new("invalid_body", "body", err.body_text()),
+            })?;
+        value.validate()?;
+        Ok(Self(value))
+    }
+}
+
+// Path parameters that passed validation
+pub struct ValidPath<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidPath<T>
+where
+    T: DeserializeOwned + Validate + Send,
+    S: Send + Sync,
+{
+    type Rejection = ValidationError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| ValidationError::new("invalid_path", "path", err.body_text()))?;
+        value.validate()?;
+        Ok(Self(value))
+    }
+}
+
+// Query parameters that passed validation
+pub struct ValidQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidQuery<T>
+where
+    T: DeserializeOwned + Validate + Send,
+    S: Send + Sync,
+{
+    type Rejection = ValidationError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| ValidationError::new("invalid_query", "query", err.body_text()))?;
+        value.validate()?;
+        Ok(Self(value))
+    }
+}
+
+impl Validate for SolanaProgramBuildParams {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if !is_pubkey(&self.program_id) {
+            return Err(ValidationError::new(
+                "invalid_program_id",
+                "program_id",
+                "program_id must be a base58 encoded public key",
+            ));
+        }
+        if !is_repository_url(&self.repository) {
+            return Err(ValidationError::new(
+                "invalid_repository",
+                "repository",
+                "repository must be an https URL of a git repository",
+            ));
+        }
+        if let Some(commit_hash) = &self.commit_hash {
+            if !is_git_ref(commit_hash) {
+                return Err(ValidationError::new(
+                    "invalid_commit_hash",
+                    "commit_hash",
+                    "commit_hash must be a commit hash, a branch or a tag",
+                ));
+            }
+        }
+        if let Some(base_image) = &self.base_image {
+            validate_base_image(base_image)?;
+        }
+        validate_cargo_args(self.cargo_args.as_deref())
+    }
+}
+
+impl Validate for ProgramBuildOverrideParams {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if let Some(base_image) = &self.base_image {
+            if !is_docker_image(base_image) {
+                return Err(ValidationError::new(
+                    "invalid_base_image",
+                    "base_image",
+                    "base_image must be a docker image reference",
+                ));
+            }
+        }
+        if let Some(version) = &self.solana_verify_version {
+            if !is_version(version) {
+                return Err(ValidationError::new(
+                    "invalid_solana_verify_version",
+                    "solana_verify_version",
+                    "solana_verify_version must be a version number, e.g. 0.2.11",
+                ));
+            }
+        }
+        validate_cargo_args(self.cargo_args.as_deref())
+    }
+}
+
+impl Validate for DenylistParams {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.program_id.is_none() && self.repository.is_none() {
+            return Err(ValidationError::new(
+                "missing_target",
+                "program_id",
+                "program_id or repository must be set",
+            ));
+        }
+        if let Some(program_id) = &self.program_id {
+            if !is_pubkey(program_id) {
+                return Err(ValidationError::new(
+                    "invalid_program_id",
+                    "program_id",
+                    "program_id must be a base58 encoded public key",
+                ));
+            }
+        }
+        if let Some(repository) = &self.repository {
+            if !is_repository_url(repository) {
+                return Err(ValidationError::new(
+                    "invalid_repository",
+                    "repository",
+                    "repository must be an https URL of a git repository",
+                ));
+            }
+        }
+        if let Some(commit_hash) = &self.commit_hash {
+            if self.repository.is_none() || !is_full_commit_hash(commit_hash) {
+                return Err(ValidationError::new(
+                    "invalid_commit_hash",
+                    "commit_hash",
+                    "commit_hash must be a full commit hash of the repository",
+                ));
+            }
+        }
+        let reason = self.reason.trim();
+        if reason.is_empty() || reason.len() > MAX_REASON_LEN {
+            return Err(ValidationError::new(
+                "invalid_reason",
+                "reason",
+                format!("reason must be 1 to {} characters", MAX_REASON_LEN),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Base images of verification requests run arbitrary code in the build environment, so they must
+// be pinned to a digest of an image allowed by BASE_IMAGE_ALLOWLIST
+fn validate_base_image(base_image: &str) -> Result<(), ValidationError> {
+    let Some((name, digest)) = is_docker_image(base_image)
+        .then(|| base_image.split_once('@'))
+        .flatten()
+    else {
+        return Err(ValidationError::new(
+            "invalid_base_image",
+            "base_image",
+            "base_image must be a docker image pinned to a digest, e.g. image@sha256:<digest>",
+        ));
+    };
+    if !is_sha256_digest(digest) {
+        return Err(ValidationError::new(
+            "invalid_base_image",
+            "base_image",
+            "base_image digest must be sha256:<64 hex characters>",
+        ));
+    }
+    if !is_allowed_image(strip_image_tag(name)) {
+        return Err(ValidationError::new(
+            "base_image_not_allowed",
+            "base_image",
+            "base_image is not an allowed image",
+        ));
+    }
+    Ok(())
+}
+
+// Images allowed as base images, read from the comma separated BASE_IMAGE_ALLOWLIST. Entries ending
+// with a / allow every image under that registry or namespace, other entries a single image.
+// Without entries no base image is accepted.
+fn allowed_base_images() -> &'static [String] {
+    static ALLOWED: OnceLock<Vec<String>> = OnceLock::new();
+    ALLOWED.get_or_init(|| {
+        env::var("BASE_IMAGE_ALLOWLIST")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+fn is_allowed_image(name: &str) -> bool {
+    allowed_base_images().iter().any(|entry| {
+        if entry.ends_with('/') {
+            name.starts_with(entry.as_str())
+        } else {
+            name == entry
+        }
+    })
+}
+
+// Image name without its tag. A : before the last / is the port of the registry.
+fn strip_image_tag(image: &str) -> &str {
+    match image.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => name,
+        _ => image,
+    }
+}
+
+fn is_sha256_digest(digest: &str) -> bool {
+    digest
+        .strip_prefix("sha256:")
+        .is_some_and(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+impl Validate for LogSearchParams {
+    fn validate(&self) -> Result<(), ValidationError> {
+        let q = self.q.trim();
+        if q.is_empty() || q.len() > MAX_SEARCH_QUERY_LEN {
+            return Err(ValidationError::new(
+                "invalid_query",
+                "q",
+                format!("q must be 1 to {} characters", MAX_SEARCH_QUERY_LEN),
+            ));
+        }
+        if let Some(limit) = self.limit {
+            if !(1..=MAX_SEARCH_LIMIT).contains(&limit) {
+                return Err(ValidationError::new(
+                    "invalid_limit",
+                    "limit",
+                    format!("limit must be between 1 and {}", MAX_SEARCH_LIMIT),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_cargo_args(cargo_args: Option<&[String]>) -> Result<(), ValidationError> {
+    if let Some(cargo_args) = cargo_args {
+        if cargo_args.len() > MAX_CARGO_ARGS
+            || cargo_args.iter().any(|arg| arg.len() > MAX_CARGO_ARG_LEN)
+        {
+            return Err(ValidationError::new(
+                "invalid_cargo_args",
+                "cargo_args",
+                format!(
+                    "cargo_args takes at most {} arguments of at most {} characters",
+                    MAX_CARGO_ARGS, MAX_CARGO_ARG_LEN
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl Validate for VerificationStatusParams {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if !is_pubkey(&self.address) {
+            return Err(ValidationError::new(
+                "invalid_address",
+                "address",
+                "address must be a base58 encoded public key",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for JobIdParams {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if uuid::Uuid::parse_str(&self.job_id).is_err() {
+            return Err(ValidationError::new(
+                "invalid_job_id",
+                "job_id",
+                "job_id must be a UUID",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for SnapshotParams {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if !is_snapshot_name(&self.name) {
+            return Err(ValidationError::new(
+                "invalid_snapshot",
+                "name",
+                "name must be the name of a snapshot listed by /snapshots",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for TimeSeriesParams {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if !TIME_SERIES_METRICS.contains(&self.metric.as_str()) {
+            return Err(ValidationError::new(
+                "invalid_metric",
+                "metric",
+                "metric must be verifications or failures",
+            ));
+        }
+        if let Some(interval) = &self.interval {
+            if !TIME_SERIES_INTERVALS.contains(&interval.as_str()) {
+                return Err(ValidationError::new(
+                    "invalid_interval",
+                    "interval",
+                    "interval must be one of day, week or month",
+                ));
+            }
+        }
+        if let Some(limit) = self.limit {
+            if !(1..=MAX_TIME_SERIES_LIMIT).contains(&limit) {
+                return Err(ValidationError::new(
+                    "invalid_limit",
+                    "limit",
+                    format!("limit must be between 1 and {}", MAX_TIME_SERIES_LIMIT),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Validate for CrawlRequestParams {
+    fn validate(&self) -> Result<(), ValidationError> {
+        let target = self.target.trim();
+        if !is_pubkey(target) && !is_repository_url(target) {
+            return Err(ValidationError::new(
+                "invalid_target",
+                "target",
+                "target must be a program id or an https repository URL",
+            ));
+        }
+        if let Some(cluster) = &self.cluster {
+            if !CLUSTERS.contains(&cluster.as_str()) {
+                return Err(ValidationError::new(
+                    "invalid_cluster",
+                    "cluster",
+                    "cluster must be one of mainnet, devnet or testnet",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Base58 encoded 32 byte address
+pub fn is_pubkey(value: &str) -> bool {
+    bs58::decode(value)
+        .into_vec()
+        .is_ok_and(|bytes| bytes.len() == 32)
+}
+
+// https://<host>/<owner>/<repository>[/...]
+fn is_repository_url(value: &str) -> bool {
+    let Some(rest) = value.strip_prefix("https://") else {
+        return false;
+    };
+    let mut segments = rest.trim_end_matches('/').split('/');
+    let host = segments.next().unwrap_or_default();
+
+    value.len() <= MAX_URL_LEN
+        && value
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !matches!(c, '"' | '\'' | '`' | '\\'))
+        && host.contains('.')
+        && segments.filter(|segment| !segment.is_empty()).count() >= 2
+}
+
+// Commit hash, branch or tag. Stricter than git so the value is always safe to pass to it.
+fn is_git_ref(value: &str) -> bool {
+    (1..=MAX_GIT_REF_LEN).contains(&value.len())
+        && !value.starts_with(['-', '/', '.'])
+        && !value.ends_with(['/', '.'])
+        && !value.contains("..")
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+}
+
+// [registry/]name[:tag][@digest]
+fn is_docker_image(value: &str) -> bool {
+    (1..=MAX_IMAGE_LEN).contains(&value.len())
+        && !value.starts_with(['-', '/', '.', ':', '@'])
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | ':' | '@'))
+}
+
+// Release version, also used in the name of the solana-verify binary so it can't contain a path
+fn is_version(value: &str) -> bool {
+    (1..=MAX_VERSION_LEN).contains(&value.len())
+        && value.starts_with(|c: char| c.is_ascii_digit())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+}
+
+// verified-programs-<timestamp>.json, see snapshots.rs
+fn is_snapshot_name(value: &str) -> bool {
+    value
+        .strip_prefix("verified-programs-")
+        .and_then(|name| name.strip_suffix(".json"))
+        .is_some_and(|timestamp| {
+            // 20240101T000000Z
+            timestamp.len() == 16 && timestamp.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn build_params() -> SolanaProgramBuildParams {
+        SolanaProgramBuildParams {
+            repository: "https://github.com/Ellipsis-Labs/phoenix-v1".to_string(),
+            program_id: "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY".to_string(),
+            commit_hash: None,
+            lib_name: None,
+            bpf_flag: None,
+            base_image: None,
+            mount_path: None,
+            cargo_args: None,
+        }
+    }
+
+    #[test]
+    fn test_is_pubkey() {
+        assert!(is_pubkey("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY"));
+        assert!(is_pubkey("11111111111111111111111111111111"));
+        assert!(!is_pubkey(""));
+        assert!(!is_pubkey("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqd"));
+        // 0, O, I and l aren't base58
+        assert!(!is_pubkey("0hoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY"));
+    }
+
+    #[test]
+    fn test_is_repository_url() {
+        assert!(is_repository_url(
+            "https://github.com/Ellipsis-Labs/phoenix-v1"
+        ));
+        assert!(is_repository_url(
+            "https://gitlab.com/group/subgroup/project/"
+        ));
+        assert!(!is_repository_url(
+            "http://github.com/Ellipsis-Labs/phoenix-v1"
+        ));
+        assert!(!is_repository_url(
+            "git@github.com:Ellipsis-Labs/phoenix-v1.git"
+        ));
+        assert!(!is_repository_url("https://github.com/Ellipsis-Labs"));
+        assert!(!is_repository_url("https://localhost/owner/repo"));
+        assert!(!is_repository_url("https://github.com/owner/repo name"));
+        assert!(!is_repository_url("https://github.com/owner/repo`id`"));
+        assert!(!is_repository_url(&format!(
+            "https://github.com/owner/{}",
+            "a".repeat(MAX_URL_LEN)
+        )));
+    }
+
+    #[test]
+    fn test_is_git_ref() {
+        assert!(is_git_ref("7a3e2f1c9b8d4e5f6a7b8c9d0e1f2a3b4c5d6e7f"));
+        assert!(is_git_ref("main"));
+        assert!(is_git_ref("release/v1.2.3"));
+        assert!(!is_git_ref(""));
+        assert!(!is_git_ref("--upload-pack=touch"));
+        assert!(!is_git_ref("/main"));
+        assert!(!is_git_ref(".hidden"));
+        assert!(!is_git_ref("main/"));
+        assert!(!is_git_ref("main."));
+        assert!(!is_git_ref("a/../b"));
+        assert!(!is_git_ref("main;rm"));
+        assert!(!is_git_ref(&"a".repeat(MAX_GIT_REF_LEN + 1)));
+    }
+
+    #[test]
+    fn test_is_docker_image() {
+        assert!(is_docker_image("ellipsislabs/solana:1.18.26"));
+        assert!(is_docker_image(&format!(
+            "ghcr.io:443/org/image@{}",
+            DIGEST
+        )));
+        assert!(!is_docker_image(""));
+        assert!(!is_docker_image("-image"));
+        assert!(!is_docker_image("@sha256:abc"));
+        assert!(!is_docker_image("image name"));
+        assert!(!is_docker_image("image;ls"));
+        assert!(!is_docker_image(&"a".repeat(MAX_IMAGE_LEN + 1)));
+    }
+
+    #[test]
+    fn test_is_version() {
+        assert!(is_version("0.2.11"));
+        assert!(is_version("1.0.0-rc.1+build"));
+        assert!(!is_version(""));
+        assert!(!is_version("v0.2.11"));
+        assert!(!is_version("0.2/../../bin"));
+    }
+
+    #[test]
+    fn test_is_snapshot_name() {
+        assert!(is_snapshot_name("verified-programs-20240101T000000Z.json"));
+        assert!(!is_snapshot_name("verified-programs-20240101T000000Z"));
+        assert!(!is_snapshot_name("verified-programs-../../secret.json"));
+        assert!(!is_snapshot_name("other-20240101T000000Z.json"));
+    }
+
+    #[test]
+    fn test_validate_base_image() {
+        // The allowlist is read once, this is the only test reading it
+        env::set_var(
+            "BASE_IMAGE_ALLOWLIST",
+            "ellipsislabs/solana, ghcr.io:443/trusted/",
+        );
+        let code = |image: &str| validate_base_image(image).err().map(|err| err.code);
+
+        assert_eq!(code(&format!("ellipsislabs/solana@{}", DIGEST)), None);
+        assert_eq!(code(&format!("ellipsislabs/solana:1.18@{}", DIGEST)), None);
+        assert_eq!(code(&format!("ghcr.io:443/trusted/image@{}", DIGEST)), None);
+        assert_eq!(code("ellipsislabs/solana:1.18"), Some("invalid_base_image"));
+        assert_eq!(
+            code("ellipsislabs/solana@sha256:abc"),
+            Some("invalid_base_image")
+        );
+        assert_eq!(
+            code(&format!("attacker/solana@{}", DIGEST)),
+            Some("base_image_not_allowed")
+        );
+        assert_eq!(
+            code(&format!("ghcr.io:443/trusted-not/image@{}", DIGEST)),
+            Some("base_image_not_allowed")
+        );
+    }
+
+    #[test]
+    fn test_strip_image_tag() {
+        assert_eq!(strip_image_tag("image:1.0"), "image");
+        assert_eq!(
+            strip_image_tag("registry:5000/image"),
+            "registry:5000/image"
+        );
+        assert_eq!(
+            strip_image_tag("registry:5000/image:1.0"),
+            "registry:5000/image"
+        );
+    }
+
+    #[test]
+    fn test_validate_build_params() {
+        assert!(build_params().validate().is_ok());
+
+        let mut params = build_params();
+        params.program_id = "not a key".to_string();
+        assert_eq!(params.validate().unwrap_err().field, "program_id");
+
+        let mut params = build_params();
+        params.commit_hash = Some("--help".to_string());
+        assert_eq!(params.validate().unwrap_err().code, "invalid_commit_hash");
+
+        let mut params = build_params();
+        params.cargo_args = Some(vec!["--features".to_string(); MAX_CARGO_ARGS + 1]);
+        assert_eq!(params.validate().unwrap_err().code, "invalid_cargo_args");
+    }
+}
@@ -0,0 +1,285 @@
+//! Client for the Solana verified programs API.
+//!
+//! ```no_run
+//! # async fn run() -> solana_verified_programs_client::Result<()> {
+//! use solana_verified_programs_client::{Client, PollConfig};
+//!
+//! let client = Client::new("https://verify.osec.io");
+//! let status = client.status("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY").await?;
+//! println!("verified: {}", status.is_verified);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod models;
+
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+use models::{
+    ApiResponse, AuthorityChange, AuthorityHistoryResponse, ErrorResponse, JobStatus,
+    JobVerificationResponse, ReproResponse, SolanaProgramBuildParams, StatusResponse,
+    VerifiedProgramListResponse, VerifiedProgramSummary, VerifyResponse,
+};
+
+pub const DEFAULT_URL: &str = "https://verify.osec.io";
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    #[error("API error ({status}): {}", .response.error)]
+    Api {
+        status: StatusCode,
+        response: ErrorResponse,
+    },
+
+    #[error("Unexpected response ({status}): {body}")]
+    UnexpectedResponse { status: StatusCode, body: String },
+
+    #[error("Job {0} did not finish in time")]
+    Timeout(String),
+}
+
+// How often a job is polled. The delay starts at `initial_delay` and grows by `multiplier` up to
+// `max_delay` until `timeout` is reached.
+#[derive(Debug, Clone)]
+pub struct PollConfig {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub timeout: Duration,
+}
+
+impl Default for PollConfig {
+    fn default() -> Self {
+        // Builds take minutes, up to hours for large workspaces
+        Self {
+            initial_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(60),
+            multiplier: 1.5,
+            timeout: Duration::from_secs(5 * 60 * 60),
+        }
+    }
+}
+
+impl PollConfig {
+    fn next_delay(&self, delay: Duration) -> Duration {
+        delay.mul_f64(self.multiplier).min(self.max_delay)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new(DEFAULT_URL)
+    }
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    // Use a preconfigured HTTP client, e.g. with custom timeouts
+    pub fn with_http_client(base_url: &str, http: reqwest::Client) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http,
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // POST /verify, queue a verification and return its job id
+    pub async fn verify(&self, params: &SolanaProgramBuildParams) -> Result<VerifyResponse> {
+        let response = self
+            .http
+            .post(self.url("/verify"))
+            .json(params)
+            .send()
+            .await?;
+        parse(response).await
+    }
+
+    // GET /status/:address, the verification status of a program
+    pub async fn status(&self, address: &str) -> Result<StatusResponse> {
+        let response = self
+            .http
+            .get(self.url(&format!("/status/{}", address)))
+            .send()
+            .await?;
+        parse(response).await
+    }
+
+    // GET /repro/:address, the command reproducing the verification of a program locally
+    pub async fn repro(&self, address: &str) -> Result<ReproResponse> {
+        let response = self
+            .http
+            .get(self.url(&format!("/repro/{}", address)))
+            .send()
+            .await?;
+        parse(response).await
+    }
+
+    // GET /authority/:address/history, the upgrade authority changes of a program, newest first
+    pub async fn authority_history(&self, address: &str) -> Result<Vec<AuthorityChange>> {
+        let response = self
+            .http
+            .get(self.url(&format!("/authority/{}/history", address)))
+            .send()
+            .await?;
+        let response: AuthorityHistoryResponse = parse(response).await?;
+        Ok(response.changes)
+    }
+
+    // GET /verified-programs, the ids of every verified program
+    pub async fn verified_programs(&self) -> Result<Vec<String>> {
+        let response = self.http.get(self.url("/verified-programs")).send().await?;
+        let response: VerifiedProgramListResponse = parse(response).await?;
+        Ok(response.verified_programs)
+    }
+
+    // GET /verified-programs, every verified program with its display name
+    pub async fn verified_program_summaries(&self) -> Result<Vec<VerifiedProgramSummary>> {
+        let response = self.http.get(self.url("/verified-programs")).send().await?;
+        let response: VerifiedProgramListResponse = parse(response).await?;
+        Ok(response.programs)
+    }
+
+    // GET /job/:job_id, the status of a verification job
+    pub async fn job(&self, job_id: &str) -> Result<JobVerificationResponse> {
+        let response = self
+            .http
+            .get(self.url(&format!("/job/{}", job_id)))
+            .send()
+            .await?;
+        parse(response).await
+    }
+
+    // GET /logs/:job_id, the build output of a verification job
+    pub async fn logs(&self, job_id: &str) -> Result<String> {
+        let response = self
+            .http
+            .get(self.url(&format!("/logs/{}", job_id)))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(error(response).await);
+        }
+        Ok(response.text().await?)
+    }
+
+    // Poll a job until it is no longer in progress
+    pub async fn wait_for_job(
+        &self,
+        job_id: &str,
+        config: &PollConfig,
+    ) -> Result<JobVerificationResponse> {
+        let started = tokio::time::Instant::now();
+        let mut delay = config.initial_delay;
+        loop {
+            let job = self.job(job_id).await?;
+            if job.status != JobStatus::InProgress {
+                return Ok(job);
+            }
+            if started.elapsed() + delay > config.timeout {
+                return Err(Error::Timeout(job_id.to_string()));
+            }
+            tokio::time::sleep(delay).await;
+            delay = config.next_delay(delay);
+        }
+    }
+
+    // Queue a verification and wait for its result
+    pub async fn verify_and_wait(
+        &self,
+        params: &SolanaProgramBuildParams,
+        config: &PollConfig,
+    ) -> Result<JobVerificationResponse> {
+        let queued = self.verify(params).await?;
+        self.wait_for_job(&queued.request_id, config).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
+
+async fn parse<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let status = response.status();
+    if !status.is_success() {
+        return Err(error(response).await);
+    }
+
+    let body = response.text().await?;
+    match serde_json::from_str(&body) {
+        Ok(ApiResponse::Success(value)) => Ok(value),
+        Ok(ApiResponse::Error(response)) => Err(Error::Api { status, response }),
+        Err(_) => Err(Error::UnexpectedResponse { status, body }),
+    }
+}
+
+async fn error(response: Response) -> Error {
+    let status = response.status();
+    match response.text().await {
+        Ok(body) => match serde_json::from_str(&body) {
+            Ok(response) => Error::Api { status, response },
+            Err(_) => Error::UnexpectedResponse { status, body },
+        },
+        Err(err) => err.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poll_backoff() {
+        let config = PollConfig {
+            initial_delay: Duration::from_secs(4),
+            max_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+            timeout: Duration::from_secs(60),
+        };
+        let delay = config.next_delay(config.initial_delay);
+        assert_eq!(delay, Duration::from_secs(8));
+        assert_eq!(config.next_delay(delay), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_parse_api_response() {
+        let error: ApiResponse<StatusResponse> = serde_json::from_str(
+            r#"{"status": "error", "error": "address must be a base58 encoded public key", "code": "invalid_address", "field": "address"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            error,
+            ApiResponse::Error(ErrorResponse { code: Some(code), .. }) if code == "invalid_address"
+        ));
+
+        let status: ApiResponse<StatusResponse> = serde_json::from_str(
+            r#"{"is_verified": true, "message": "On chain program verified", "on_chain_hash": "a", "executable_hash": "a", "last_verified_at": null, "repo_url": "https://github.com/o/r"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            status,
+            ApiResponse::Success(StatusResponse {
+                is_verified: true,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+// Request and response types of the verified programs API
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SolanaProgramBuildParams {
+    pub repository: String,
+    pub program_id: String,
+    pub commit_hash: Option<String>,
+    pub lib_name: Option<String>,
+    pub bpf_flag: Option<bool>,
+    pub base_image: Option<String>,
+    pub mount_path: Option<String>,
+    pub cargo_args: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Success,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobStatus {
+    #[serde(rename = "in_progress")]
+    InProgress,
+    #[serde(rename = "completed")]
+    Completed,
+    #[serde(rename = "failed")]
+    Failed,
+    #[serde(rename = "unknown")]
+    Unknown,
+}
+
+// Error body of a failed request. Requests rejected by validation also name the invalid field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub status: Status,
+    pub error: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    // When a new build is allowed, UTC. Set when a build quota is exceeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reset_at: Option<String>,
+}
+
+// Response of POST /verify
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyResponse {
+    pub status: JobStatus,
+    pub request_id: String,
+    pub message: String,
+}
+
+// Response of GET /status/:address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusResponse {
+    pub is_verified: bool,
+    pub message: String,
+    pub on_chain_hash: String,
+    pub executable_hash: String,
+    pub last_verified_at: Option<String>,
+    pub repo_url: String,
+    // Display name of the program, from its security.txt
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub is_closed: bool,
+    // "program closed", "hash mismatch after upgrade" or "never verified", None when verified
+    #[serde(default)]
+    pub status_reason: Option<String>,
+    // "never_verified", "verification_failed", "unverified_hash_mismatch", "verified" or
+    // "program_closed", None from servers that don't report it
+    #[serde(default)]
+    pub state: Option<String>,
+}
+
+// Response of GET /job/:job_id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobVerificationResponse {
+    pub status: JobStatus,
+    pub message: String,
+    pub on_chain_hash: String,
+    pub executable_hash: String,
+    pub repo_url: String,
+    // Estimated completion time of a job in progress, UTC
+    #[serde(default)]
+    pub estimated_completion_at: Option<String>,
+    // How long the job waited for a build slot and how long its last build ran
+    #[serde(default)]
+    pub queue_wait_seconds: Option<f64>,
+    #[serde(default)]
+    pub build_duration_seconds: Option<f64>,
+}
+
+// Response of GET /repro/:address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReproResponse {
+    pub program_id: String,
+    pub build_id: String,
+    // solana-verify command reproducing the verification, ready to run in a shell
+    pub command: String,
+    pub solana_verify_version: Option<String>,
+    pub base_image: Option<String>,
+    // Hashes the command is expected to print
+    pub on_chain_hash: String,
+    pub executable_hash: String,
+    // UTC
+    pub verified_at: String,
+}
+
+// Response of GET /authority/:address/history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorityHistoryResponse {
+    pub program_id: String,
+    // Newest first
+    pub changes: Vec<AuthorityChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorityChange {
+    // changed, frozen or closed
+    pub change: String,
+    pub old_authority: Option<String>,
+    pub new_authority: Option<String>,
+    // Last deployment of the program when the change was observed
+    pub slot: Option<i64>,
+    // UTC
+    pub observed_at: String,
+}
+
+// Response of GET /verified-programs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifiedProgramListResponse {
+    pub verified_programs: Vec<String>,
+    // The same programs with their display names
+    #[serde(default)]
+    pub programs: Vec<VerifiedProgramSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifiedProgramSummary {
+    pub program_id: String,
+    pub name: Option<String>,
+}
+
+// Some endpoints answer errors with a 200 and an error body
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ApiResponse<T> {
+    Success(T),
+    Error(ErrorResponse),
+}
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX crawl_targets_status_index;
+DROP TABLE crawl_targets;
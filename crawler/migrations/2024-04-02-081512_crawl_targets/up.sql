@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- Create a table to hand crawled source code links over to verification
+CREATE TABLE crawl_targets (
+    id SERIAL PRIMARY KEY,
+    source_code VARCHAR UNIQUE NOT NULL,
+    status VARCHAR(20) DEFAULT 'pending' NOT NULL,
+    error TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- Create an index on the status column to fetch pending targets
+CREATE INDEX crawl_targets_status_index ON crawl_targets (status);
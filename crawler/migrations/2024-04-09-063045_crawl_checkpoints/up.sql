@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- Create a table to track crawl progress so an interrupted crawl can resume
+CREATE TABLE crawl_checkpoints (
+    id SERIAL PRIMARY KEY,
+    last_program_address VARCHAR,
+    started_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    completed_at TIMESTAMP
+);
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE mainnet_programs DROP COLUMN repo_host;
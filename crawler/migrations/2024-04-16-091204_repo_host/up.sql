@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Record which git host the source_code link of a program points to
+ALTER TABLE mainnet_programs ADD COLUMN repo_host VARCHAR;
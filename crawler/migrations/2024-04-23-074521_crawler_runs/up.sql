@@ -0,0 +1,15 @@
+-- Your SQL goes here
+-- Create a table to store a summary of every crawler run
+CREATE TABLE crawler_runs (
+    id SERIAL PRIMARY KEY,
+    kind VARCHAR NOT NULL,
+    started_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP,
+    programs_scanned INTEGER DEFAULT 0 NOT NULL,
+    security_txt_found INTEGER DEFAULT 0 NOT NULL,
+    verification_json_found INTEGER DEFAULT 0 NOT NULL,
+    verifications_submitted INTEGER DEFAULT 0 NOT NULL,
+    failures JSONB DEFAULT '{}' NOT NULL
+);
+
+CREATE INDEX crawler_runs_started_at_index ON crawler_runs (started_at);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX mainnet_programs_last_error_index;
+ALTER TABLE mainnet_programs DROP COLUMN last_error_at;
+ALTER TABLE mainnet_programs DROP COLUMN last_error_message;
+ALTER TABLE mainnet_programs DROP COLUMN last_error;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- Record why a program was skipped or failed during the last crawl
+ALTER TABLE mainnet_programs ADD COLUMN last_error VARCHAR;
+ALTER TABLE mainnet_programs ADD COLUMN last_error_message TEXT;
+ALTER TABLE mainnet_programs ADD COLUMN last_error_at TIMESTAMP;
+
+CREATE INDEX mainnet_programs_last_error_index ON mainnet_programs (last_error);
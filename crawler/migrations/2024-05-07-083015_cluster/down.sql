@@ -0,0 +1,15 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE crawler_runs DROP COLUMN cluster;
+
+ALTER TABLE crawl_checkpoints DROP COLUMN cluster;
+
+DELETE FROM crawl_targets WHERE cluster <> 'mainnet';
+DROP INDEX crawl_targets_source_code_cluster_uindex;
+ALTER TABLE crawl_targets DROP COLUMN cluster;
+ALTER TABLE crawl_targets ADD CONSTRAINT crawl_targets_source_code_key UNIQUE (source_code);
+
+DELETE FROM mainnet_programs WHERE cluster <> 'mainnet';
+DROP INDEX mainnet_programs_program_address_cluster_uindex;
+ALTER TABLE mainnet_programs DROP COLUMN cluster;
+ALTER TABLE mainnet_programs ADD CONSTRAINT mainnet_programs_program_address_key UNIQUE (program_address);
+CREATE UNIQUE INDEX mainnet_programs_program_address_uindex ON mainnet_programs (program_address);
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+-- Tag every crawled row with its cluster so devnet and testnet crawlers can share the database
+ALTER TABLE mainnet_programs ADD COLUMN cluster VARCHAR(20) DEFAULT 'mainnet' NOT NULL;
+ALTER TABLE mainnet_programs DROP CONSTRAINT mainnet_programs_program_address_key;
+DROP INDEX mainnet_programs_program_address_uindex;
+CREATE UNIQUE INDEX mainnet_programs_program_address_cluster_uindex ON mainnet_programs (program_address, cluster);
+
+ALTER TABLE crawl_targets ADD COLUMN cluster VARCHAR(20) DEFAULT 'mainnet' NOT NULL;
+ALTER TABLE crawl_targets DROP CONSTRAINT crawl_targets_source_code_key;
+CREATE UNIQUE INDEX crawl_targets_source_code_cluster_uindex ON crawl_targets (source_code, cluster);
+
+ALTER TABLE crawl_checkpoints ADD COLUMN cluster VARCHAR(20) DEFAULT 'mainnet' NOT NULL;
+
+ALTER TABLE crawler_runs ADD COLUMN cluster VARCHAR(20) DEFAULT 'mainnet' NOT NULL;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+-- Create a table for programs and repositories submitted through POST /admin/crawl
+CREATE TABLE crawl_requests (
+    id SERIAL PRIMARY KEY,
+    target VARCHAR NOT NULL,
+    cluster VARCHAR(20) DEFAULT 'mainnet' NOT NULL,
+    status VARCHAR(20) DEFAULT 'pending' NOT NULL,
+    error TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- Create an index to fetch the pending requests of a cluster
+CREATE INDEX crawl_requests_status_cluster_index ON crawl_requests (status, cluster);
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- Create a table for the upgrade authority changes observed by the crawler. The slot is the last
+-- deployment of the program when the change was observed, closures have none.
+CREATE TABLE authority_history (
+    id SERIAL PRIMARY KEY,
+    program_address VARCHAR NOT NULL,
+    cluster VARCHAR(20) DEFAULT 'mainnet' NOT NULL,
+    change VARCHAR(20) NOT NULL,
+    old_authority VARCHAR,
+    new_authority VARCHAR,
+    slot BIGINT,
+    observed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- Create an index to fetch the history of a program
+CREATE INDEX authority_history_program_index ON authority_history (program_address, cluster);
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Key checkpoints by crawl mode so incremental and full crawls never resume each other
+ALTER TABLE crawl_checkpoints ADD COLUMN mode VARCHAR(20) DEFAULT 'incremental' NOT NULL;
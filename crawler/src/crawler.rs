@@ -1,24 +1,69 @@
+use std::collections::HashMap;
+use std::env;
 use std::str::FromStr;
 use std::time::Duration;
 
-use solana_account_decoder::UiAccountEncoding;
+use anyhow::{bail, format_err, Result};
+use futures::stream::{self, StreamExt};
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::client_error::Result as ClientResult;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
 use solana_client::rpc_filter::RpcFilterType;
-use solana_client::{
-    rpc_client::RpcClient,
-    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
-};
+use solana_sdk::account::Account;
 use solana_sdk::account_utils::StateMut;
 use solana_sdk::bpf_loader_upgradeable::UpgradeableLoaderState;
 use solana_sdk::pubkey::Pubkey;
 
-use crate::db::client::DbClient;
+use crate::db::{client::DbClient, models::CrawlTargetStatus};
 use crate::errors;
+use crate::helper::verify_target;
+use crate::repo::RepoUrl;
+use crate::report::{FailureCategory, RunReport};
+use crate::rpc::RpcConfig;
 
-// Crawl the mainnet programs and write them to a file
-pub async fn crawl_mainnet_programs(db: &DbClient, rpc_url: &str) {
+// getMultipleAccounts accepts at most 100 addresses per request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+const DEFAULT_CRAWL_CONCURRENCY: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrawlMode {
+    // Process every program, even if it was not redeployed since the last crawl
+    Full,
+    // Only process programs deployed after the slot stored in mainnet_programs
+    Incremental,
+}
+
+impl CrawlMode {
+    pub fn from_env() -> Self {
+        match env::var("CRAWL_MODE").as_deref() {
+            Ok("full") => CrawlMode::Full,
+            Ok("incremental") | Ok("") | Err(_) => CrawlMode::Incremental,
+            Ok(mode) => panic!("CRAWL_MODE must be full or incremental, got {}", mode),
+        }
+    }
+}
+
+impl From<CrawlMode> for String {
+    fn from(mode: CrawlMode) -> Self {
+        match mode {
+            CrawlMode::Full => "full".to_string(),
+            CrawlMode::Incremental => "incremental".to_string(),
+        }
+    }
+}
+
+// Crawl the mainnet programs and queue their source code for verification
+pub async fn crawl_mainnet_programs(
+    db: &DbClient,
+    rpc_config: &RpcConfig,
+    mode: CrawlMode,
+    report: &RunReport,
+) {
     let timeout = Duration::from_secs(3600);
 
-    let client = RpcClient::new_with_timeout(rpc_url, timeout);
+    let client = rpc_config.client(timeout);
 
     // Only bpf_loader_upgradeable programs have support for security.txt
     let pubkey = Pubkey::from_str("BPFLoaderUpgradeab1e11111111111111111111111").unwrap();
@@ -26,73 +71,425 @@ pub async fn crawl_mainnet_programs(db: &DbClient, rpc_url: &str) {
     // filter account with size 36
     let filters = Some(vec![RpcFilterType::DataSize(36)]);
 
-    let response = client.get_program_accounts_with_config(
-        &pubkey,
-        RpcProgramAccountsConfig {
-            filters,
-            account_config: RpcAccountInfoConfig {
-                encoding: Some(UiAccountEncoding::Base64),
-                ..RpcAccountInfoConfig::default()
-            },
-            ..RpcProgramAccountsConfig::default()
+    let config = RpcProgramAccountsConfig {
+        filters,
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(rpc_config.commitment),
+            ..RpcAccountInfoConfig::default()
         },
-    );
+        ..RpcProgramAccountsConfig::default()
+    };
+
+    let response = rpc_config
+        .retry
+        .run(|| client.get_program_accounts_with_config(&pubkey, config.clone()))
+        .await;
 
     let response = response.unwrap();
 
     tracing::info!("Found {} accounts", response.len());
 
-    for account in response {
-        // test
-        if let Ok(UpgradeableLoaderState::Program {
-            programdata_address,
-        }) = account.1.state()
-        {
-            tracing::info!("Fetching : {:?}", programdata_address);
-            let result = crate::helper::get_program_security_text(
-                &account.0,
-                &programdata_address,
-                db,
-                rpc_url,
-            )
-            .await;
-            // Check if security text is available
-            if let Ok(security_txt) = result {
-                // Check if source code is available
-                if let Some(source_code) = security_txt.source_code {
-                    tracing::info!("{}'s Source code: {}", account.0, source_code);
-                    let _ = crate::helper::write_file(&source_code);
-                    db.update_program_info(
-                        &account.0.to_string(),
-                        &source_code,
-                        &security_txt.name,
-                    )
-                    .await
-                    .unwrap();
+    let mut programs = response
+        .iter()
+        .filter_map(|(pubkey, account)| {
+            if let Ok(UpgradeableLoaderState::Program {
+                programdata_address,
+            }) = account.state()
+            {
+                Some((*pubkey, programdata_address))
+            } else {
+                tracing::error!(
+                    "Failed to get program data address for pubkey: {}",
+                    pubkey.to_string()
+                );
+                None
+            }
+        })
+        .collect::<Vec<(Pubkey, Pubkey)>>();
+
+    // Process programs in a stable order so an interrupted crawl can resume where it stopped
+    programs.sort();
+    let checkpoint = db
+        .get_or_create_checkpoint(&String::from(mode))
+        .await
+        .expect("Failed to load crawl checkpoint");
+    if let Some(last_program) = checkpoint
+        .last_program_address
+        .as_deref()
+        .and_then(|address| Pubkey::from_str(address).ok())
+    {
+        programs.retain(|(program, _)| *program > last_program);
+        tracing::info!(
+            "Resuming crawl after {}. {} programs left",
+            last_program,
+            programs.len()
+        );
+    }
+
+    let deployed_programs = match mode {
+        CrawlMode::Full => HashMap::new(),
+        CrawlMode::Incremental => db
+            .get_deployed_programs()
+            .await
+            .expect("Failed to load deployed programs"),
+    };
+
+    let concurrency = crawl_concurrency();
+    // Once a chunk fails the checkpoint stays before it, so the next crawl retries it
+    let mut chunk_failed = false;
+
+    // Fetch program data accounts in batches instead of one RPC call per program
+    for chunk in programs.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for _ in chunk {
+            report.program_scanned();
+        }
+
+        let batch = match mode {
+            CrawlMode::Full => chunk.to_vec(),
+            CrawlMode::Incremental => {
+                match filter_updated_programs(&client, rpc_config, chunk, &deployed_programs).await
+                {
+                    Ok(updated) => updated,
+                    Err(err) => {
+                        tracing::error!("Failed to fetch program data slots: {}", err);
+                        report.failure(FailureCategory::Rpc);
+                        chunk_failed = true;
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let programdata_addresses = batch
+            .iter()
+            .map(|(_, programdata_address)| *programdata_address)
+            .collect::<Vec<Pubkey>>();
+
+        let program_data_accounts = match rpc_config
+            .retry
+            .run(|| {
+                if programdata_addresses.is_empty() {
+                    Ok(Vec::new())
                 } else {
-                    tracing::error!(
-                        "Failed to get source_code from security.txt for pubkey: {}",
-                        account.0
-                    );
+                    client.get_multiple_accounts(&programdata_addresses)
                 }
-            } else {
-                tracing::error!("Failed to get security text for pubkey: {}", account.0);
+            })
+            .await
+        {
+            Ok(accounts) => accounts,
+            Err(err) => {
+                tracing::error!("Failed to fetch program data accounts: {}", err);
+                report.failure(FailureCategory::Rpc);
+                chunk_failed = true;
+                continue;
+            }
+        };
 
-                // Match the error and update the status in the database
-                if let Err(err) = result {
-                    crate::errors::handle_crawler_errors(
-                        err.downcast_ref::<errors::CrawlerErrors>(),
+        // Parse security.txt and queue the source code of several programs at once
+        stream::iter(batch.iter().copied().zip(program_data_accounts))
+            .map(
+                |((program, programdata_address), program_data_account)| async move {
+                    process_program(
                         db,
-                        &account.0,
+                        &program,
+                        &programdata_address,
+                        program_data_account,
+                        mode,
+                        report,
                     )
-                    .await;
-                }
+                    .await
+                },
+            )
+            .buffer_unordered(concurrency)
+            .collect::<Vec<()>>()
+            .await;
+
+        if chunk_failed {
+            continue;
+        }
+        if let Some((last_program, _)) = chunk.last() {
+            if let Err(err) = db
+                .update_checkpoint(checkpoint.id, &last_program.to_string())
+                .await
+            {
+                tracing::error!("Failed to save crawl checkpoint: {}", err);
+            }
+        }
+    }
+
+    if chunk_failed {
+        tracing::warn!("Crawl checkpoint left open to retry the chunks that failed");
+    } else if let Err(err) = db.complete_checkpoint(checkpoint.id).await {
+        tracing::error!("Failed to complete crawl checkpoint: {}", err);
+    }
+}
+
+// Number of programs processed at the same time, read from CRAWL_CONCURRENCY
+pub fn crawl_concurrency() -> usize {
+    env::var("CRAWL_CONCURRENCY")
+        .ok()
+        .filter(|concurrency| !concurrency.is_empty())
+        .map_or(DEFAULT_CRAWL_CONCURRENCY, |concurrency| {
+            concurrency
+                .parse::<usize>()
+                .expect("CRAWL_CONCURRENCY must be a positive integer")
+                .max(1)
+        })
+}
+
+async fn process_program(
+    db: &DbClient,
+    program: &Pubkey,
+    programdata_address: &Pubkey,
+    program_data_account: Option<Account>,
+    mode: CrawlMode,
+    report: &RunReport,
+) {
+    tracing::info!("Fetching : {:?}", programdata_address);
+    let result = crate::helper::get_program_security_text(
+        program,
+        programdata_address,
+        program_data_account,
+        db,
+        mode,
+    )
+    .await;
+    // Check if security text is available
+    if let Ok(security_txt) = result {
+        report.security_txt_found();
+        // Check if source code is available
+        if let Some(source_code) = security_txt.source_code {
+            tracing::info!("{}'s Source code: {}", program, source_code);
+            if let Err(err) = db.insert_crawl_target(&source_code).await {
+                tracing::error!("Failed to queue {} for verification: {}", source_code, err);
+                report.failure(FailureCategory::Database);
+            }
+            let host = RepoUrl::parse(&source_code).map(|repo| repo.host);
+            if host.is_none() {
+                tracing::warn!("Unrecognized source code URL: {}", source_code);
+                report.failure(FailureCategory::InvalidSourceUrl);
+                record_error(
+                    db,
+                    program,
+                    FailureCategory::InvalidSourceUrl,
+                    &format!("Unrecognized source code URL: {}", source_code),
+                )
+                .await;
+            } else if let Err(err) = db.clear_program_error(&program.to_string()).await {
+                tracing::error!("Failed to clear the error of {}: {}", program, err);
+            }
+            if let Err(err) = db
+                .update_program_info(&program.to_string(), &source_code, host, &security_txt.name)
+                .await
+            {
+                tracing::error!("Failed to update program info for {}: {}", program, err);
+                report.failure(FailureCategory::Database);
             }
         } else {
             tracing::error!(
-                "Failed to get program data address for pubkey: {}",
-                account.0.to_string()
+                "Failed to get source_code from security.txt for pubkey: {}",
+                program
             );
+            report.failure(FailureCategory::MissingSourceCode);
+            record_error(
+                db,
+                program,
+                FailureCategory::MissingSourceCode,
+                "security.txt has no source_code",
+            )
+            .await;
+        }
+    } else {
+        tracing::error!("Failed to get security text for pubkey: {}", program);
+
+        // Match the error and update the status in the database
+        if let Err(err) = result {
+            if !matches!(
+                err.downcast_ref::<errors::CrawlerErrors>(),
+                Some(errors::CrawlerErrors::ProgramNotUpdated)
+            ) {
+                let category = FailureCategory::of(&err);
+                report.failure(category);
+                record_error(db, program, category, &err.to_string()).await;
+            }
+            crate::errors::handle_crawler_errors(
+                err.downcast_ref::<errors::CrawlerErrors>(),
+                db,
+                program,
+            )
+            .await;
+        }
+    }
+
+    if let Err(err) = db.set_is_processed(&program.to_string()).await {
+        tracing::error!("Failed to mark {} as processed: {}", program, err);
+    }
+}
+
+// Record why a program was skipped on its mainnet_programs row
+async fn record_error(db: &DbClient, program: &Pubkey, category: FailureCategory, message: &str) {
+    if let Err(err) = db
+        .set_program_error(&program.to_string(), category, message)
+        .await
+    {
+        tracing::error!("Failed to record the error of {}: {}", program, err);
+    }
+}
+
+// Run the crawl pipeline for the programs and repositories submitted through the admin API
+pub async fn crawl_requested_targets(
+    db: &DbClient,
+    rpc_config: &RpcConfig,
+    report: &RunReport,
+) -> Result<()> {
+    let requests = db.get_pending_crawl_requests().await?;
+    tracing::info!("Found {} pending crawl requests", requests.len());
+
+    for request in requests {
+        let (status, error) =
+            match crawl_requested_target(db, rpc_config, &request.target, report).await {
+                Ok(()) => (CrawlTargetStatus::Completed, None),
+                Err(err) => {
+                    tracing::error!("Failed to crawl {}: {}", request.target, err);
+                    (CrawlTargetStatus::Failed, Some(err.to_string()))
+                }
+            };
+        db.update_crawl_request_status(request.id, status, error)
+            .await?;
+    }
+
+    Ok(())
+}
+
+// A target is either a program id or the URL of its repository
+async fn crawl_requested_target(
+    db: &DbClient,
+    rpc_config: &RpcConfig,
+    target: &str,
+    report: &RunReport,
+) -> Result<()> {
+    let source_code = match Pubkey::from_str(target) {
+        Ok(program) => crawl_program(db, rpc_config, &program, report).await?,
+        Err(_) => {
+            RepoUrl::parse(target)
+                .ok_or_else(|| errors::CrawlerErrors::InvalidSourceUrl(target.to_string()))?;
+            target.to_string()
         }
+    };
+
+    let crawl_target = db.insert_crawl_target(&source_code).await?;
+    match verify_target(db, &crawl_target, report).await? {
+        Some(err) => bail!(err),
+        None => Ok(()),
     }
 }
+
+// Read the security.txt of a single program and return its source code link
+async fn crawl_program(
+    db: &DbClient,
+    rpc_config: &RpcConfig,
+    program: &Pubkey,
+    report: &RunReport,
+) -> Result<String> {
+    let client = rpc_config.client(Duration::from_secs(60));
+
+    let program_account = rpc_config
+        .retry
+        .run(|| client.get_account_with_commitment(program, rpc_config.commitment))
+        .await?
+        .value
+        .ok_or_else(|| errors::CrawlerErrors::ProgramClosed(program.to_string()))?;
+    let Ok(UpgradeableLoaderState::Program {
+        programdata_address,
+    }) = program_account.state()
+    else {
+        bail!("{} is not an upgradeable program", program);
+    };
+
+    let program_data_account = rpc_config
+        .retry
+        .run(|| client.get_account_with_commitment(&programdata_address, rpc_config.commitment))
+        .await?
+        .value;
+
+    report.program_scanned();
+    process_program(
+        db,
+        program,
+        &programdata_address,
+        program_data_account,
+        CrawlMode::Full,
+        report,
+    )
+    .await;
+
+    let crawled = db
+        .get_program(&program.to_string())
+        .await?
+        .ok_or_else(|| format_err!("{} was not recorded", program))?;
+    if let Some(message) = crawled.last_error_message {
+        bail!(message);
+    }
+    crawled
+        .github_repo
+        .ok_or_else(|| format_err!("{} has no source code link", program))
+}
+
+// Read only the program data metadata of a batch and keep the programs that were redeployed
+// (or closed) since the last crawl
+async fn filter_updated_programs(
+    client: &RpcClient,
+    rpc_config: &RpcConfig,
+    programs: &[(Pubkey, Pubkey)],
+    deployed_programs: &HashMap<String, (i64, Option<String>)>,
+) -> ClientResult<Vec<(Pubkey, Pubkey)>> {
+    let programdata_addresses = programs
+        .iter()
+        .map(|(_, programdata_address)| *programdata_address)
+        .collect::<Vec<Pubkey>>();
+
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        data_slice: Some(UiDataSliceConfig {
+            offset: 0,
+            length: UpgradeableLoaderState::size_of_programdata_metadata(),
+        }),
+        commitment: Some(rpc_config.commitment),
+        ..RpcAccountInfoConfig::default()
+    };
+
+    let metadata_accounts = rpc_config
+        .retry
+        .run(|| client.get_multiple_accounts_with_config(&programdata_addresses, config.clone()))
+        .await?
+        .value;
+
+    let updated = programs
+        .iter()
+        .zip(metadata_accounts)
+        .filter(|((program, _), account)| {
+            // Authority changes and freezes keep the slot, so the authority is compared too
+            let state = account.as_ref().and_then(|account| match account.state() {
+                Ok(UpgradeableLoaderState::ProgramData {
+                    slot,
+                    upgrade_authority_address,
+                }) => Some((
+                    slot as i64,
+                    upgrade_authority_address.map(|authority| authority.to_string()),
+                )),
+                _ => None,
+            });
+            state.is_none() || deployed_programs.get(&program.to_string()) != state.as_ref()
+        })
+        .map(|(program, _)| *program)
+        .collect::<Vec<(Pubkey, Pubkey)>>();
+
+    tracing::info!(
+        "{} of {} programs changed since the last crawl",
+        updated.len(),
+        programs.len()
+    );
+
+    Ok(updated)
+}
@@ -1,18 +1,26 @@
-use crate::db::models::MainnetProgram;
+use crate::db::models::{
+    AuthorityChange, CrawlCheckpoint, CrawlRequest, CrawlTarget, CrawlTargetStatus, MainnetProgram,
+};
+use crate::repo::RepoHost;
+use crate::report::{FailureCategory, RunReport};
+use crate::rpc::Cluster;
 use anyhow::Result;
 use diesel::{expression_methods::ExpressionMethods, query_dsl::QueryDsl};
 use diesel_async::pooled_connection::AsyncDieselConnectionManager;
 use diesel_async::RunQueryDsl;
 use diesel_async::{pooled_connection::deadpool::Pool, AsyncPgConnection};
 use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
 
+// Every query is scoped to the cluster the crawler runs against
 #[derive(Clone)]
 pub struct DbClient {
     pub db_pool: Pool<AsyncPgConnection>,
+    cluster_name: String,
 }
 
 impl DbClient {
-    pub fn new(db_url: &str) -> Self {
+    pub fn new(db_url: &str, crawled_cluster: Cluster) -> Self {
         let config = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(db_url);
         let postgres_pool = Pool::builder(config)
             .build()
@@ -20,6 +28,7 @@ impl DbClient {
 
         Self {
             db_pool: postgres_pool,
+            cluster_name: String::from(crawled_cluster),
         }
     }
 
@@ -45,8 +54,9 @@ impl DbClient {
                 updated_at.eq(chrono::Utc::now().naive_utc()),
                 last_deployed_slot.eq(None::<i64>),
                 update_authority.eq(None::<String>),
+                cluster.eq(&self.cluster_name),
             ))
-            .on_conflict(program_address)
+            .on_conflict((program_address, cluster))
             .do_update()
             .set(is_processed.eq(false))
             .get_result::<MainnetProgram>(conn)
@@ -55,6 +65,47 @@ impl DbClient {
         Ok(inserted)
     }
 
+    pub async fn get_program(&self, program_id: &str) -> Result<Option<MainnetProgram>> {
+        use crate::schema::mainnet_programs::dsl::*;
+        use diesel::OptionalExtension;
+
+        let conn = &mut self.db_pool.get().await?;
+        let program = mainnet_programs
+            .filter(program_address.eq(program_id))
+            .filter(cluster.eq(&self.cluster_name))
+            .first::<MainnetProgram>(conn)
+            .await
+            .optional()?;
+
+        Ok(program)
+    }
+
+    // Get the last deployed slot of every known program
+    // Last deployment slot and upgrade authority of every crawled program
+    pub async fn get_deployed_programs(&self) -> Result<HashMap<String, (i64, Option<String>)>> {
+        use crate::schema::mainnet_programs::dsl::*;
+        use diesel::NullableExpressionMethods;
+
+        let conn = &mut self.db_pool.get().await?;
+        let programs = mainnet_programs
+            .filter(last_deployed_slot.is_not_null())
+            .filter(cluster.eq(&self.cluster_name))
+            .select((
+                program_address,
+                last_deployed_slot.assume_not_null(),
+                update_authority,
+            ))
+            .load::<(String, i64, Option<String>)>(conn)
+            .await?;
+
+        Ok(programs
+            .into_iter()
+            .map(|(address, slot, authority)| (address, (slot, authority)))
+            .collect())
+    }
+
+    // Store the upgrade authority and last deployment slot of a program, recording the authority
+    // change in the history when it has one
     pub async fn update_authority_and_slot(
         &self,
         program_id: &str,
@@ -63,47 +114,179 @@ impl DbClient {
     ) -> Result<()> {
         use crate::schema::mainnet_programs::dsl::*;
 
+        let stored = self
+            .get_program(program_id)
+            .await?
+            .and_then(|program| program.update_authority);
+        let on_chain = authority.map(|authority| authority.to_string());
+
         let conn = &mut self.db_pool.get().await?;
+        diesel::update(
+            mainnet_programs
+                .filter(program_address.eq(program_id))
+                .filter(cluster.eq(&self.cluster_name)),
+        )
+        .set((
+            update_authority.eq(&on_chain),
+            last_deployed_slot.eq(slot as i64),
+        ))
+        .execute(conn)
+        .await?;
 
-        match authority {
-            Some(authority) => {
-                diesel::update(mainnet_programs.filter(program_address.eq(program_id)))
-                    .set((
-                        update_authority.eq(authority.to_string()),
-                        last_deployed_slot.eq(slot as i64),
-                    ))
-                    .execute(conn)
-                    .await?;
-            }
-            None => {
-                diesel::update(mainnet_programs.filter(program_address.eq(program_id)))
-                    .set(last_deployed_slot.eq(slot as i64))
-                    .execute(conn)
-                    .await?;
-            }
+        if let Some(change) = AuthorityChange::between(stored.as_deref(), on_chain.as_deref()) {
+            self.record_authority_change(program_id, change, stored, on_chain, Some(slot as i64))
+                .await?;
         }
 
         Ok(())
     }
 
+    pub async fn record_authority_change(
+        &self,
+        program_id: &str,
+        authority_change: AuthorityChange,
+        old: Option<String>,
+        new: Option<String>,
+        deployed_slot: Option<i64>,
+    ) -> Result<()> {
+        use crate::schema::authority_history::dsl::*;
+        use diesel::insert_into;
+
+        tracing::info!(
+            "Authority of {} {}: {:?} -> {:?}",
+            program_id,
+            String::from(authority_change),
+            old,
+            new
+        );
+        let conn = &mut self.db_pool.get().await?;
+        insert_into(authority_history)
+            .values((
+                program_address.eq(program_id),
+                cluster.eq(&self.cluster_name),
+                change.eq(String::from(authority_change)),
+                old_authority.eq(old),
+                new_authority.eq(new),
+                slot.eq(deployed_slot),
+            ))
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
     // Update github_repo and project_name with program address
     pub async fn update_program_info(
         &self,
         program_id: &str,
         github_url: &str,
+        host: Option<RepoHost>,
         name: &str,
     ) -> Result<()> {
         use crate::schema::mainnet_programs::dsl::*;
         let conn = &mut self.db_pool.get().await?;
-        diesel::update(mainnet_programs.filter(program_address.eq(program_id)))
-            .set((
-                github_repo.eq(github_url),
-                project_name.eq(name),
-                has_security_txt.eq(true),
-                is_success.eq(true),
-            ))
-            .execute(conn)
-            .await?;
+        diesel::update(
+            mainnet_programs
+                .filter(program_address.eq(program_id))
+                .filter(cluster.eq(&self.cluster_name)),
+        )
+        .set((
+            github_repo.eq(github_url),
+            repo_host.eq(host.map(String::from)),
+            project_name.eq(name),
+            has_security_txt.eq(true),
+            is_success.eq(true),
+        ))
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // Record the latest skip or failure reason of a program
+    pub async fn set_program_error(
+        &self,
+        program_id: &str,
+        category: FailureCategory,
+        message: &str,
+    ) -> Result<()> {
+        use crate::schema::mainnet_programs::dsl::*;
+        let conn = &mut self.db_pool.get().await?;
+        diesel::update(
+            mainnet_programs
+                .filter(program_address.eq(program_id))
+                .filter(cluster.eq(&self.cluster_name)),
+        )
+        .set((
+            last_error.eq(String::from(category)),
+            last_error_message.eq(message),
+            last_error_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn clear_program_error(&self, program_id: &str) -> Result<()> {
+        use crate::schema::mainnet_programs::dsl::*;
+        let conn = &mut self.db_pool.get().await?;
+        diesel::update(
+            mainnet_programs
+                .filter(program_address.eq(program_id))
+                .filter(cluster.eq(&self.cluster_name)),
+        )
+        .set((
+            last_error.eq(None::<String>),
+            last_error_message.eq(None::<String>),
+            last_error_at.eq(None::<chrono::NaiveDateTime>),
+        ))
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // Record a verification failure on every program linking to the repository
+    pub async fn set_repo_error(
+        &self,
+        source_code: &str,
+        category: FailureCategory,
+        message: &str,
+    ) -> Result<()> {
+        use crate::schema::mainnet_programs::dsl::*;
+        let conn = &mut self.db_pool.get().await?;
+        diesel::update(
+            mainnet_programs
+                .filter(github_repo.eq(source_code))
+                .filter(cluster.eq(&self.cluster_name)),
+        )
+        .set((
+            last_error.eq(String::from(category)),
+            last_error_message.eq(message),
+            last_error_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn clear_repo_error(&self, source_code: &str) -> Result<()> {
+        use crate::schema::mainnet_programs::dsl::*;
+        let conn = &mut self.db_pool.get().await?;
+        diesel::update(
+            mainnet_programs
+                .filter(github_repo.eq(source_code))
+                .filter(cluster.eq(&self.cluster_name)),
+        )
+        .set((
+            last_error.eq(None::<String>),
+            last_error_message.eq(None::<String>),
+            last_error_at.eq(None::<chrono::NaiveDateTime>),
+        ))
+        .execute(conn)
+        .await?;
 
         Ok(())
     }
@@ -112,27 +295,46 @@ impl DbClient {
     pub async fn update_program_status(&self, program_id: &str, status: bool) -> Result<()> {
         use crate::schema::mainnet_programs::dsl::*;
         let conn = &mut self.db_pool.get().await?;
-        diesel::update(mainnet_programs.filter(program_address.eq(program_id)))
-            .set(is_success.eq(status))
-            .execute(conn)
-            .await?;
+        diesel::update(
+            mainnet_programs
+                .filter(program_address.eq(program_id))
+                .filter(cluster.eq(&self.cluster_name)),
+        )
+        .set(is_success.eq(status))
+        .execute(conn)
+        .await?;
 
         Ok(())
     }
 
-    // Set is_closed status of the program
+    // Set is_closed status of the program, recording the closure in the authority history
     pub async fn set_is_closed(&self, program_id: &str, status: bool) -> Result<()> {
         use crate::schema::mainnet_programs::dsl::*;
         let conn = &mut self.db_pool.get().await?;
-        diesel::update(
+        let closed = diesel::update(
             mainnet_programs
                 .filter(program_address.eq(program_id))
+                .filter(cluster.eq(&self.cluster_name))
                 .filter(is_closed.eq(false)),
         )
         .set(is_closed.eq(status))
-        .execute(conn)
+        .returning(update_authority)
+        .get_results::<Option<String>>(conn)
         .await?;
 
+        if status {
+            for authority in closed {
+                self.record_authority_change(
+                    program_id,
+                    AuthorityChange::Closed,
+                    authority,
+                    None,
+                    None,
+                )
+                .await?;
+            }
+        }
+
         Ok(())
     }
 
@@ -140,8 +342,218 @@ impl DbClient {
     pub async fn update_security_txt_status(&self, program_id: &str, status: bool) -> Result<()> {
         use crate::schema::mainnet_programs::dsl::*;
         let conn = &mut self.db_pool.get().await?;
-        diesel::update(mainnet_programs.filter(program_address.eq(program_id)))
-            .set(has_security_txt.eq(status))
+        diesel::update(
+            mainnet_programs
+                .filter(program_address.eq(program_id))
+                .filter(cluster.eq(&self.cluster_name)),
+        )
+        .set(has_security_txt.eq(status))
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // Queue a source code link for verification, re-queueing it if it was seen before
+    pub async fn insert_crawl_target(&self, source: &str) -> Result<CrawlTarget> {
+        use crate::schema::crawl_targets::dsl::*;
+        use diesel::insert_into;
+
+        let conn = &mut self.db_pool.get().await?;
+        let target = insert_into(crawl_targets)
+            .values((
+                source_code.eq(source),
+                status.eq(String::from(CrawlTargetStatus::Pending)),
+                cluster.eq(&self.cluster_name),
+            ))
+            .on_conflict((source_code, cluster))
+            .do_update()
+            .set((
+                status.eq(String::from(CrawlTargetStatus::Pending)),
+                error.eq(None::<String>),
+                updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .get_result::<CrawlTarget>(conn)
+            .await?;
+
+        Ok(target)
+    }
+
+    // Get all source code links waiting for verification
+    pub async fn get_pending_crawl_targets(&self) -> Result<Vec<CrawlTarget>> {
+        use crate::schema::crawl_targets::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        let targets = crawl_targets
+            .filter(status.eq(String::from(CrawlTargetStatus::Pending)))
+            .filter(cluster.eq(&self.cluster_name))
+            .order(id.asc())
+            .load::<CrawlTarget>(conn)
+            .await?;
+
+        Ok(targets)
+    }
+
+    // Update the verification status of a source code link
+    pub async fn update_crawl_target_status(
+        &self,
+        target_id: i32,
+        target_status: CrawlTargetStatus,
+        target_error: Option<String>,
+    ) -> Result<()> {
+        use crate::schema::crawl_targets::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::update(crawl_targets.filter(id.eq(target_id)))
+            .set((
+                status.eq(String::from(target_status)),
+                error.eq(target_error),
+                updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    // Get the programs and repositories submitted through the admin API
+    pub async fn get_pending_crawl_requests(&self) -> Result<Vec<CrawlRequest>> {
+        use crate::schema::crawl_requests::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        let requests = crawl_requests
+            .filter(status.eq(String::from(CrawlTargetStatus::Pending)))
+            .filter(cluster.eq(&self.cluster_name))
+            .order(id.asc())
+            .load::<CrawlRequest>(conn)
+            .await?;
+
+        Ok(requests)
+    }
+
+    pub async fn update_crawl_request_status(
+        &self,
+        request_id: i32,
+        request_status: CrawlTargetStatus,
+        request_error: Option<String>,
+    ) -> Result<()> {
+        use crate::schema::crawl_requests::dsl::*;
+
+        let conn = &mut self.db_pool.get().await?;
+        diesel::update(crawl_requests.filter(id.eq(request_id)))
+            .set((
+                status.eq(String::from(request_status)),
+                error.eq(request_error),
+                updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    // Mark the program as processed in the current crawl
+    pub async fn set_is_processed(&self, program_id: &str) -> Result<()> {
+        use crate::schema::mainnet_programs::dsl::*;
+        let conn = &mut self.db_pool.get().await?;
+        diesel::update(
+            mainnet_programs
+                .filter(program_address.eq(program_id))
+                .filter(cluster.eq(&self.cluster_name)),
+        )
+        .set(is_processed.eq(true))
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    // Get the checkpoint of an interrupted crawl of the same mode or start a new one
+    pub async fn get_or_create_checkpoint(&self, crawl_mode: &str) -> Result<CrawlCheckpoint> {
+        use crate::schema::crawl_checkpoints::dsl::*;
+        use diesel::{insert_into, OptionalExtension};
+
+        let conn = &mut self.db_pool.get().await?;
+        let unfinished = crawl_checkpoints
+            .filter(completed_at.is_null())
+            .filter(cluster.eq(&self.cluster_name))
+            .filter(mode.eq(crawl_mode))
+            .order(id.desc())
+            .first::<CrawlCheckpoint>(conn)
+            .await
+            .optional()?;
+
+        if let Some(checkpoint) = unfinished {
+            return Ok(checkpoint);
+        }
+
+        let checkpoint = insert_into(crawl_checkpoints)
+            .values((
+                last_program_address.eq(None::<String>),
+                cluster.eq(&self.cluster_name),
+                mode.eq(crawl_mode),
+            ))
+            .get_result::<CrawlCheckpoint>(conn)
+            .await?;
+
+        Ok(checkpoint)
+    }
+
+    // Record the start of a crawler run and return its id
+    pub async fn insert_crawler_run(&self, run_kind: &str) -> Result<i32> {
+        use crate::schema::crawler_runs::dsl::*;
+        let conn = &mut self.db_pool.get().await?;
+        let run_id = diesel::insert_into(crawler_runs)
+            .values((kind.eq(run_kind), cluster.eq(&self.cluster_name)))
+            .returning(id)
+            .get_result::<i32>(conn)
+            .await?;
+
+        Ok(run_id)
+    }
+
+    // Store the summary of a finished crawler run
+    pub async fn finish_crawler_run(&self, run_id: i32, report: &RunReport) -> Result<()> {
+        use crate::schema::crawler_runs::dsl::*;
+        let conn = &mut self.db_pool.get().await?;
+        let (scanned, security_txt, verification_json, submitted) = report.counts();
+        diesel::update(crawler_runs.filter(id.eq(run_id)))
+            .set((
+                finished_at.eq(chrono::Utc::now().naive_utc()),
+                programs_scanned.eq(scanned),
+                security_txt_found.eq(security_txt),
+                verification_json_found.eq(verification_json),
+                verifications_submitted.eq(submitted),
+                failures.eq(report.failures()),
+            ))
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    // Record the last program address handled by the crawl
+    pub async fn update_checkpoint(&self, checkpoint_id: i32, program_id: &str) -> Result<()> {
+        use crate::schema::crawl_checkpoints::dsl::*;
+        let conn = &mut self.db_pool.get().await?;
+        diesel::update(crawl_checkpoints.filter(id.eq(checkpoint_id)))
+            .set((
+                last_program_address.eq(program_id),
+                updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    // Mark the crawl as finished so the next run starts from the beginning
+    pub async fn complete_checkpoint(&self, checkpoint_id: i32) -> Result<()> {
+        use crate::schema::crawl_checkpoints::dsl::*;
+        let conn = &mut self.db_pool.get().await?;
+        let now = chrono::Utc::now().naive_utc();
+        diesel::update(crawl_checkpoints.filter(id.eq(checkpoint_id)))
+            .set((updated_at.eq(now), completed_at.eq(now)))
             .execute(conn)
             .await?;
 
@@ -17,4 +17,117 @@ pub struct MainnetProgram {
     pub updated_at: NaiveDateTime,
     pub last_deployed_slot: Option<i64>,
     pub update_authority: Option<String>,
+    pub repo_host: Option<String>,
+    pub last_error: Option<String>,
+    pub last_error_message: Option<String>,
+    pub last_error_at: Option<NaiveDateTime>,
+    pub cluster: String,
+}
+
+#[derive(Queryable, Selectable, PartialEq, Debug)]
+#[diesel(table_name = crate::schema::crawl_checkpoints)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CrawlCheckpoint {
+    pub id: i32,
+    pub last_program_address: Option<String>,
+    pub started_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub completed_at: Option<NaiveDateTime>,
+    pub cluster: String,
+    pub mode: String,
+}
+
+#[derive(Queryable, Selectable, PartialEq, Debug)]
+#[diesel(table_name = crate::schema::crawl_targets)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CrawlTarget {
+    pub id: i32,
+    pub source_code: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub cluster: String,
+}
+
+// Program id or repository URL submitted through the admin API, using the crawl target statuses
+#[derive(Queryable, Selectable, PartialEq, Debug)]
+#[diesel(table_name = crate::schema::crawl_requests)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CrawlRequest {
+    pub id: i32,
+    pub target: String,
+    pub cluster: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug)]
+pub enum CrawlTargetStatus {
+    Pending,
+    Completed,
+    Failed,
+}
+
+impl From<CrawlTargetStatus> for String {
+    fn from(status: CrawlTargetStatus) -> Self {
+        match status {
+            CrawlTargetStatus::Pending => "pending".to_string(),
+            CrawlTargetStatus::Completed => "completed".to_string(),
+            CrawlTargetStatus::Failed => "failed".to_string(),
+        }
+    }
+}
+
+// Change of the upgrade authority of a program observed by the crawler
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthorityChange {
+    Changed,
+    Frozen,
+    Closed,
+}
+
+impl AuthorityChange {
+    // Change between the authority stored for a program and the one found on chain. A program
+    // without a stored authority was frozen already or is seen for the first time.
+    pub fn between(stored: Option<&str>, on_chain: Option<&str>) -> Option<Self> {
+        match (stored, on_chain) {
+            (Some(stored), Some(on_chain)) if stored != on_chain => Some(AuthorityChange::Changed),
+            (Some(_), None) => Some(AuthorityChange::Frozen),
+            _ => None,
+        }
+    }
+}
+
+impl From<AuthorityChange> for String {
+    fn from(change: AuthorityChange) -> Self {
+        match change {
+            AuthorityChange::Changed => "changed".to_string(),
+            AuthorityChange::Frozen => "frozen".to_string(),
+            AuthorityChange::Closed => "closed".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authority_change_between() {
+        assert_eq!(
+            AuthorityChange::between(Some("a"), Some("b")),
+            Some(AuthorityChange::Changed)
+        );
+        assert_eq!(
+            AuthorityChange::between(Some("a"), None),
+            Some(AuthorityChange::Frozen)
+        );
+        assert_eq!(AuthorityChange::between(Some("a"), Some("a")), None);
+        // First observation, or a program frozen before
+        assert_eq!(AuthorityChange::between(None, Some("a")), None);
+        assert_eq!(AuthorityChange::between(None, None), None);
+    }
 }
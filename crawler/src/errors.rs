@@ -21,8 +21,18 @@ pub enum CrawlerErrors {
     ProgramNotUpdated,
     #[error("Default branch not found")]
     DefaultBranchNotFound,
-    #[error("Invalid Json File contents")]
-    InvalidJsonFileContents,
+    #[error("Invalid source code URL: {0}")]
+    InvalidSourceUrl(String),
+    #[error("Repository {0} not found")]
+    RepositoryNotFound(String),
+    #[error("Rate limited by {0}")]
+    RateLimited(String),
+    #[error("Failed to submit verification: {0}")]
+    SubmissionFailed(String),
+    #[error("No verification file found in the repository")]
+    VerificationJsonNotFound,
+    #[error("Invalid verification file {0}: {1}")]
+    InvalidVerificationJson(String, String),
 }
 
 // Function to hanle the error cases when fetching the program account's security.txt
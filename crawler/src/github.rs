@@ -1,9 +1,15 @@
 // Github Client
 
 use anyhow::Result;
+use reqwest::header::{HeaderMap, AUTHORIZATION};
+use reqwest::StatusCode;
 use serde_json::Value;
+use std::env;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crate::errors;
+use crate::repo::check_status;
 
 pub struct GithubClient {
     owner: String,
@@ -12,28 +18,170 @@ pub struct GithubClient {
 }
 static USER_AGENT: &str = "GitHub-otter-sec";
 
+// Back off used when GitHub rejects a request without telling us when to retry
+const DEFAULT_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(60);
+
+// Client shared by every repository so connections to the git hosts are reused
+pub fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new)
+}
+
+// Token pool shared by every repository, read from the comma separated GITHUB_TOKENS
+fn token_pool() -> &'static TokenPool {
+    static POOL: OnceLock<TokenPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let tokens = env::var("GITHUB_TOKENS").unwrap_or_default();
+        TokenPool::new(
+            tokens
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(str::to_string)
+                .collect(),
+        )
+    })
+}
+
+struct TokenState {
+    // None is used for anonymous requests when no token is configured
+    token: Option<String>,
+    remaining: u64,
+    reset_at: Instant,
+}
+
+// Rotates GitHub API requests over a set of tokens and tracks the rate limit of each one
+// from the response headers
+pub struct TokenPool {
+    tokens: Mutex<Vec<TokenState>>,
+    next: Mutex<usize>,
+}
+
+impl TokenPool {
+    pub fn new(tokens: Vec<String>) -> Self {
+        let now = Instant::now();
+        let mut tokens = tokens.into_iter().map(Some).collect::<Vec<_>>();
+        if tokens.is_empty() {
+            tokens.push(None);
+        }
+
+        Self {
+            tokens: Mutex::new(
+                tokens
+                    .into_iter()
+                    .map(|token| TokenState {
+                        token,
+                        // Unknown until the first response tells us
+                        remaining: u64::MAX,
+                        reset_at: now,
+                    })
+                    .collect(),
+            ),
+            next: Mutex::new(0),
+        }
+    }
+
+    // Pick the next token with requests left, or the time until one becomes available
+    fn try_acquire(&self) -> std::result::Result<(usize, Option<String>), Duration> {
+        let now = Instant::now();
+        let mut tokens = self.tokens.lock().unwrap();
+        let mut next = self.next.lock().unwrap();
+
+        for offset in 0..tokens.len() {
+            let index = (*next + offset) % tokens.len();
+            let state = &mut tokens[index];
+            if state.remaining == 0 && state.reset_at <= now {
+                state.remaining = u64::MAX;
+            }
+            if state.remaining > 0 {
+                state.remaining = state.remaining.saturating_sub(1);
+                *next = index + 1;
+                return Ok((index, state.token.clone()));
+            }
+        }
+
+        Err(tokens
+            .iter()
+            .map(|state| state.reset_at.saturating_duration_since(now))
+            .min()
+            .unwrap_or_default())
+    }
+
+    // Wait until a token is available instead of sending requests that are bound to fail
+    async fn acquire(&self) -> (usize, Option<String>) {
+        loop {
+            match self.try_acquire() {
+                Ok(token) => return token,
+                Err(wait) => {
+                    tracing::warn!("All GitHub tokens are rate limited, waiting {:?}", wait);
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+
+    // Record the rate limit reported by GitHub. Returns true if the request was rejected
+    // because of the rate limit and should be retried with another token.
+    fn update(&self, index: usize, status: StatusCode, headers: &HeaderMap) -> bool {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+        };
+        let now = Instant::now();
+        let mut tokens = self.tokens.lock().unwrap();
+        let state = &mut tokens[index];
+
+        if let Some(remaining) = header("x-ratelimit-remaining") {
+            state.remaining = remaining;
+        }
+        if let Some(reset) = header("x-ratelimit-reset") {
+            let epoch = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            state.reset_at = now + Duration::from_secs(reset.saturating_sub(epoch));
+        }
+
+        let rate_limited = matches!(
+            status,
+            StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+        ) && (state.remaining == 0 || headers.contains_key("retry-after"));
+
+        if rate_limited {
+            state.remaining = 0;
+            // Secondary rate limits only tell us how long to back off
+            if let Some(retry_after) = header("retry-after") {
+                state.reset_at = now + Duration::from_secs(retry_after);
+            } else if state.reset_at <= now {
+                state.reset_at = now + DEFAULT_RATE_LIMIT_BACKOFF;
+            }
+        }
+
+        rate_limited
+    }
+}
+
 // impl GithubClient
 impl GithubClient {
     pub fn new(owner: &str, repo: &str) -> Self {
         Self {
             owner: owner.to_string(),
             repo: repo.to_string(),
-            client: reqwest::Client::new(),
+            client: http_client().clone(),
         }
     }
 
     pub async fn fetch_default_branch(&self) -> Result<String> {
         let url = format!("https://api.github.com/repos/{}/{}", self.owner, self.repo);
 
-        let response = self
-            .client
-            .get(&url)
-            .header(reqwest::header::USER_AGENT, USER_AGENT)
-            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
-            .send()
-            .await?
-            .json::<Value>()
-            .await?;
+        let response = self.send_api_request(&url).await?;
+        let repo = format!("https://github.com/{}/{}", self.owner, self.repo);
+        if response.status() == reqwest::StatusCode::FORBIDDEN {
+            return Err(errors::CrawlerErrors::RateLimited("api.github.com".to_string()).into());
+        }
+        let response = check_status(response, &repo)?.json::<Value>().await?;
 
         if let Some(default_branch) = response["default_branch"].as_str() {
             Ok(default_branch.to_string())
@@ -43,34 +191,55 @@ impl GithubClient {
         }
     }
 
-    pub async fn get_verification_json(
-        &self,
-    ) -> Result<serde_json::Map<String, serde_json::Value>> {
-        let branch = self.fetch_default_branch().await?;
-
-        // Source code/.verified-build.json
+    // Names of the directories inside a directory of the repository
+    pub async fn list_directories(&self, path: &str, branch: &str) -> Result<Vec<String>> {
         let url = format!(
-            "https://raw.githubusercontent.com//{}/{}/{}/.verified-build.json",
-            self.owner, self.repo, branch
+            "https://api.github.com/repos/{}/{}/contents/{}?ref={}",
+            self.owner, self.repo, path, branch
         );
 
-        // Create a reqwest client
-        let content = self
-            .client
-            .get(&url)
-            .send()
+        let response = self
+            .send_api_request(&url)
             .await?
-            .json::<serde_json::Value>()
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to fetch JSON: {}", e);
-                errors::CrawlerErrors::InvalidJsonFileContents
-            })?;
-
-        content
-            .as_object()
-            .cloned()
-            .ok_or_else(|| anyhow::format_err!("Failed to parse JSON due to an invalid format."))
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+
+        Ok(response
+            .as_array()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|entry| entry["type"] == "dir")
+                    .filter_map(|entry| entry["name"].as_str())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    // Send a GitHub API request through the token pool, retrying rate limited responses
+    // with the next available token
+    async fn send_api_request(&self, url: &str) -> Result<reqwest::Response> {
+        let pool = token_pool();
+        loop {
+            let (index, token) = pool.acquire().await;
+
+            let mut request = self
+                .client
+                .get(url)
+                .header(reqwest::header::USER_AGENT, USER_AGENT)
+                .header(reqwest::header::ACCEPT, "application/vnd.github+json");
+            if let Some(token) = token {
+                request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+            }
+
+            let response = request.send().await?;
+            if !pool.update(index, response.status(), response.headers()) {
+                return Ok(response);
+            }
+            tracing::warn!("GitHub rate limit hit for {}, retrying", url);
+        }
     }
 }
 
@@ -78,6 +247,36 @@ impl GithubClient {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_token_pool() {
+        let pool = TokenPool::new(vec!["first".to_string(), "second".to_string()]);
+
+        // Tokens are used in turn
+        let (first, token) = pool.try_acquire().unwrap();
+        assert_eq!(token.as_deref(), Some("first"));
+        let (_, token) = pool.try_acquire().unwrap();
+        assert_eq!(token.as_deref(), Some("second"));
+
+        // A rate limited token is skipped until it resets
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+        assert!(pool.update(first, StatusCode::FORBIDDEN, &headers));
+        let (second, token) = pool.try_acquire().unwrap();
+        assert_eq!(token.as_deref(), Some("second"));
+
+        // Once every token is exhausted the caller has to wait
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", "60".parse().unwrap());
+        assert!(pool.update(second, StatusCode::TOO_MANY_REQUESTS, &headers));
+        assert!(pool.try_acquire().is_err());
+
+        // Regular errors are not treated as rate limits
+        let pool = TokenPool::new(Vec::new());
+        let (index, token) = pool.try_acquire().unwrap();
+        assert_eq!(token, None);
+        assert!(!pool.update(index, StatusCode::FORBIDDEN, &HeaderMap::new()));
+    }
+
     #[tokio::test]
     async fn test_fetch_default_branch() {
         let client = GithubClient::new("Ellipsis-Labs", "phoenix-v1");
@@ -85,14 +284,4 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "master");
     }
-
-    #[tokio::test]
-    async fn test_get_verification_json() {
-        let client = GithubClient::new("Ellipsis-Labs", "phoenix-v1");
-        let result = client.get_verification_json().await;
-        assert!(result.is_ok());
-        assert!(result
-            .unwrap()
-            .contains_key("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY"));
-    }
 }
@@ -1,58 +1,42 @@
 use anyhow::{bail, Result};
-use serde_json::Value;
-use solana_client::rpc_client::RpcClient;
+use futures::stream::{self, StreamExt};
 use solana_sdk::{
-    account_utils::StateMut, bpf_loader_upgradeable::UpgradeableLoaderState, pubkey::Pubkey,
+    account::Account, account_utils::StateMut, bpf_loader_upgradeable::UpgradeableLoaderState,
+    pubkey::Pubkey,
 };
 use solana_security_txt::SecurityTxt;
-use std::{fs::OpenOptions, io::Write};
 
 use crate::{
     api::{
         client::verify_build,
         models::{BuildCommandArgs, SolanaProgramBuildParams},
     },
-    db::client::DbClient,
+    crawler::{crawl_concurrency, CrawlMode},
+    db::{
+        client::DbClient,
+        models::{CrawlTarget, CrawlTargetStatus},
+    },
     errors::CrawlerErrors,
-    github::GithubClient,
+    repo::RepoUrl,
+    report::{FailureCategory, RunReport},
 };
 
-// Constants
-pub const OUTPUT_FILENAME: &str = "verification_targets.txt";
-
-// Function to write github source_code link to a json file
-pub fn write_file(data: &str) -> Result<()> {
-    // Open the file with append mode or create it if it doesn't exist
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(OUTPUT_FILENAME)?;
-
-    // Append content followed by a new line
-    writeln!(file, "{}", data)?;
-
-    Ok(())
-}
-
 pub async fn get_program_security_text(
     pubkey: &Pubkey,
     program_data_address: &Pubkey,
+    program_data_account: Option<Account>,
     db: &DbClient,
-    rpc_url: &str,
+    mode: CrawlMode,
 ) -> Result<SecurityTxt> {
-    let client = RpcClient::new(rpc_url);
-
     // Insert the program into the database
     let program = db
         .insert_program(&pubkey.to_string(), &program_data_address.to_string())
         .await?;
 
-    let program_data_account = client
-        .get_account(program_data_address)
-        .map_err(|_| CrawlerErrors::ProgramClosed(pubkey.to_string()))?;
+    let program_data_account =
+        program_data_account.ok_or_else(|| CrawlerErrors::ProgramClosed(pubkey.to_string()))?;
 
-    let offset = UpgradeableLoaderState::programdata_data_offset()
-        .map_err(CrawlerErrors::FailedToGetProgramDataOffset)?;
+    let offset = UpgradeableLoaderState::size_of_programdata_metadata();
 
     // Get ProgramData Slot from the account
     if let Ok(UpgradeableLoaderState::ProgramData {
@@ -63,12 +47,17 @@ pub async fn get_program_security_text(
         tracing::info!("slot: {}", slot);
         tracing::info!("upgrade_authority_address: {:?}", upgrade_authority_address);
 
-        if program.last_deployed_slot == Some(slot as i64) {
-            bail!(CrawlerErrors::ProgramNotUpdated)
-        } else {
+        // A new upgrade authority or a freeze doesn't redeploy the program, so it's recorded
+        // even when the program itself is unchanged
+        let redeployed = program.last_deployed_slot != Some(slot as i64);
+        let authority = upgrade_authority_address.map(|authority| authority.to_string());
+        if mode == CrawlMode::Full || redeployed || program.update_authority != authority {
             db.update_authority_and_slot(&pubkey.to_string(), &upgrade_authority_address, slot)
                 .await?;
         }
+        if mode == CrawlMode::Incremental && !redeployed {
+            bail!(CrawlerErrors::ProgramNotUpdated)
+        }
     } else {
         return Err(CrawlerErrors::ProgramClosed(pubkey.to_string()).into());
     }
@@ -85,22 +74,55 @@ pub async fn get_program_security_text(
     Ok(security_txt)
 }
 
-// Read file line by line and return it
-pub async fn verify_programs(filename: &str) -> Result<()> {
-    let file = std::fs::read_to_string(filename)?;
-    let lines: Vec<String> = file.lines().map(|s| s.to_string()).collect();
-    for line in lines {
-        start_verification(&line).await?;
-    }
+// Verify every pending crawl target and record the outcome
+pub async fn verify_programs(db: &DbClient, report: &RunReport) -> Result<()> {
+    let targets = db.get_pending_crawl_targets().await?;
+    tracing::info!("Found {} pending crawl targets", targets.len());
+
+    stream::iter(targets)
+        .map(|target| async move { verify_target(db, &target, report).await.map(|_| ()) })
+        .buffer_unordered(crawl_concurrency())
+        .collect::<Vec<Result<()>>>()
+        .await
+        .into_iter()
+        .collect::<Result<()>>()?;
 
     Ok(())
 }
 
-// Split the string by space and get all args
-pub fn extract_build_params(input: &BuildCommandArgs) -> SolanaProgramBuildParams {
+// Verify a single crawl target and record the outcome. Returns the verification error, database
+// errors are returned as Err.
+pub async fn verify_target(
+    db: &DbClient,
+    target: &CrawlTarget,
+    report: &RunReport,
+) -> Result<Option<String>> {
+    let (status, error) = match start_verification(&target.source_code, report).await {
+        Ok(()) => {
+            db.clear_repo_error(&target.source_code).await?;
+            (CrawlTargetStatus::Completed, None)
+        }
+        Err(err) => {
+            tracing::error!("Failed to verify {}: {}", target.source_code, err);
+            let category = FailureCategory::of(&err);
+            report.failure(category);
+            db.set_repo_error(&target.source_code, category, &err.to_string())
+                .await?;
+            (CrawlTargetStatus::Failed, Some(err.to_string()))
+        }
+    };
+    db.update_crawl_target_status(target.id, status, error.clone())
+        .await?;
+
+    Ok(error)
+}
+
+// Parse the arguments of `solana-verify verify-from-repo` into build params. Accepts long and
+// short flags, `--flag=value`, and arguments holding several shell quoted words.
+pub fn extract_build_params(input: &BuildCommandArgs) -> Result<SolanaProgramBuildParams> {
     let mut params = SolanaProgramBuildParams {
-        repository: String::new(),
-        program_id: String::new(),
+        repository: input.repo.to_string(),
+        program_id: input.program_id.to_string(),
         commit_hash: None,
         lib_name: None,
         bpf_flag: None,
@@ -109,86 +131,110 @@ pub fn extract_build_params(input: &BuildCommandArgs) -> SolanaProgramBuildParam
         cargo_args: None,
     };
 
-    let mut cargo_args = Vec::new();
-    let mut is_cargo_args = false;
-
-    // Use iterator on Vec<&str> to get the next token
-    let mut tokens = input.command.iter();
+    let words = input
+        .command
+        .iter()
+        .map(|arg| split_words(arg))
+        .collect::<Result<Vec<_>>>()?
+        .concat();
+    let mut tokens = words.into_iter();
 
     while let Some(token) = tokens.next() {
-        if is_cargo_args {
-            cargo_args.push(token.to_string());
-            continue;
+        // Everything after `--` is passed to cargo
+        if token == "--" {
+            params.cargo_args = Some(tokens.by_ref().collect());
+            break;
         }
+        let (flag, inline_value) = match token.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+            _ => (token.clone(), None),
+        };
+        let mut value = || match inline_value {
+            Some(value) if !value.is_empty() => Ok(value.to_string()),
+            Some(_) => bail!("{} is missing a value", flag),
+            None => flag_value(&mut tokens, &flag),
+        };
 
-        match token.as_str() {
-            "solana-verify" | "verify-from-repo" => {} // Ignore command and repo
-            "--commit-hash" => {
-                params.commit_hash = Some(tokens.next().unwrap().to_string());
-            }
-            "--mount-path" => {
-                params.mount_path = Some(tokens.next().unwrap().to_string());
-            }
-            "--base-image" => {
-                params.base_image = Some(tokens.next().unwrap().to_string());
-            }
-            "--library-name" => {
-                params.lib_name = Some(tokens.next().unwrap().to_string());
-            }
-            "--bpf" => {
-                params.bpf_flag = Some(true);
-            }
-            "--" => {
-                is_cargo_args = true;
-            }
-            _ => {
-                // unknown flag
+        match flag.as_str() {
+            "--commit-hash" => params.commit_hash = Some(value()?),
+            "--mount-path" => params.mount_path = Some(value()?),
+            "--base-image" | "-b" => params.base_image = Some(value()?),
+            "--library-name" | "-l" => params.lib_name = Some(value()?),
+            "--bpf" => params.bpf_flag = Some(true),
+            // The API only tells apart bpf and the default sbf builds, a program built for another
+            // architecture wouldn't match
+            "--arch" => match value()?.as_str() {
+                "bpf" => params.bpf_flag = Some(true),
+                "sbf" => {}
+                arch => bail!("--arch {} is not supported", arch),
+            },
+            // Options of the CLI that don't change the build
+            "--url" | "-u" | "--program-id" => {
+                value()?;
             }
+            "--remote" | "-y" | "--skip-prompt" | "-um" | "--current-dir" => {}
+            flag if flag.starts_with('-') => bail!("unknown argument {}", flag),
+            // The command itself and the repository URL
+            _ => {}
         }
     }
-    params.program_id = input.program_id.to_string();
-    params.repository = input.repo.to_string();
-    if is_cargo_args {
-        params.cargo_args = Some(cargo_args);
-    }
-    params
+
+    Ok(params)
 }
 
-fn extract_owner_and_repo(url: &str) -> Option<(String, String)> {
-    let parts: Vec<&str> = url.trim_end_matches('/').split('/').collect();
-    if parts.len() >= 3 && parts[0] == "https:" && parts[1].is_empty() && parts[2] == "github.com" {
-        Some((parts[3].to_string(), parts[4].to_string()))
-    } else {
-        None
+fn flag_value(tokens: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+    tokens
+        .next()
+        .filter(|value| !value.starts_with('-'))
+        .ok_or_else(|| anyhow::format_err!("{} is missing a value", flag))
+}
+
+// Split an argument into words the way a shell would, so `--library-name "my lib"` given as a
+// single argument becomes two
+fn split_words(arg: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = arg.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| anyhow::format_err!("unterminated escape in {}", arg))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
     }
+    if quote.is_some() {
+        bail!("unterminated quote in {}", arg)
+    }
+    words.extend(word);
+    Ok(words)
 }
 
 // Start Verification and get Result
-pub async fn start_verification(source_code: &str) -> Result<()> {
-    // if source_code is end with / remove it
-    let (owner, repo) = extract_owner_and_repo(source_code)
-        .ok_or_else(|| anyhow::format_err!("Invalid source code URL."))?;
-
-    let github_client = GithubClient::new(&owner, &repo);
-    let json_params = github_client.get_verification_json().await?;
-
-    for (key, arr) in json_params {
-        let params = if let Value::Array(arr) = arr {
-            arr.iter()
-                .map(|v| v.as_str().unwrap_or_default().to_owned())
-                .collect::<Vec<String>>()
-        } else {
-            Vec::new()
-        };
-
-        let params = BuildCommandArgs {
-            repo: source_code.to_string(),
-            program_id: key.to_string(),
-            command: params,
-        };
-
-        let build_params = extract_build_params(&params);
-        verify_build(build_params).await?;
+pub async fn start_verification(source_code: &str, report: &RunReport) -> Result<()> {
+    let repo = RepoUrl::parse(source_code)
+        .ok_or_else(|| CrawlerErrors::InvalidSourceUrl(source_code.to_string()))?;
+
+    let programs = repo.get_verification_params().await?;
+    report.verification_json_found();
+
+    for build_params in programs {
+        verify_build(build_params)
+            .await
+            .map_err(|err| CrawlerErrors::SubmissionFailed(err.to_string()))?;
+        report.verification_submitted();
     }
     Ok(())
 }
@@ -196,30 +242,67 @@ pub async fn start_verification(source_code: &str) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn parse(command: &[&str]) -> Result<SolanaProgramBuildParams> {
+        extract_build_params(&BuildCommandArgs {
+            repo: "https://github.com/Ellipsis-Labs/phoenix-v1".to_string(),
+            program_id: "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY".to_string(),
+            command: command.iter().map(ToString::to_string).collect(),
+        })
+    }
+
+    #[test]
+    fn test_extract_build_params_flag_styles() {
+        let params = parse(&[
+            "--commit-hash=8d2cd726afdc800f89c841ff3cf1968980719df0",
+            "-l",
+            "phoenix",
+            "-b",
+            "ellipsislabs/solana:1.14.29",
+            "--arch",
+            "sbf",
+            "--mount-path 'programs/phoenix v1'",
+            "--",
+            "--features",
+            "mainnet",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            params.commit_hash.as_deref(),
+            Some("8d2cd726afdc800f89c841ff3cf1968980719df0")
+        );
+        assert_eq!(params.lib_name.as_deref(), Some("phoenix"));
+        assert_eq!(
+            params.base_image.as_deref(),
+            Some("ellipsislabs/solana:1.14.29")
+        );
+        assert_eq!(params.mount_path.as_deref(), Some("programs/phoenix v1"));
+        assert_eq!(
+            params.cargo_args,
+            Some(vec!["--features".to_string(), "mainnet".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_extract_build_params_malformed() {
+        assert!(parse(&["--library-name"]).is_err());
+        assert!(parse(&["--commit-hash="]).is_err());
+        assert!(parse(&["--library-name", "--bpf"]).is_err());
+        assert!(parse(&["--mount-path 'programs"]).is_err());
+        assert!(parse(&["--arch", "v1"]).is_err());
+        assert_eq!(parse(&["--arch=bpf"]).unwrap().bpf_flag, Some(true));
+        assert!(parse(&["--unknown"]).is_err());
+    }
+
     #[tokio::test]
     async fn test_extract_build_params() {
         let github_repo = "https://github.com/Ellipsis-Labs/phoenix-v1";
-        let client = GithubClient::new("Ellipsis-Labs", "phoenix-v1");
-        let json_params = client.get_verification_json().await.unwrap();
-
-        for (key, arr) in json_params {
-            let params = if let Value::Array(arr) = arr {
-                arr.iter()
-                    .map(|v| v.as_str().unwrap_or_default().to_owned())
-                    .collect::<Vec<String>>()
-            } else {
-                Vec::new()
-            };
-            let params = BuildCommandArgs {
-                repo: github_repo.to_string(),
-                program_id: key.to_string(),
-                command: params,
-            };
-
-            let build_params = extract_build_params(&params);
+        let repo = RepoUrl::parse(github_repo).unwrap();
+        let programs = repo.get_verification_params().await.unwrap();
 
+        for build_params in programs {
             assert_eq!(build_params.repository, github_repo);
-            assert_eq!(build_params.program_id, key.to_string());
             assert!(build_params.commit_hash.is_none());
             assert!(build_params.lib_name.is_none());
             assert!(build_params.bpf_flag.is_none());
@@ -0,0 +1,216 @@
+// Infer build params from Anchor.toml and Cargo.toml when a repository has no verification file
+
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::api::models::SolanaProgramBuildParams;
+use crate::repo::{Checkout, RepoUrl};
+
+// Candidate build params for every program declared in the repository
+pub async fn infer_build_params(
+    repo: &RepoUrl,
+    checkout: &Checkout,
+) -> Result<Vec<SolanaProgramBuildParams>> {
+    // library name -> program id
+    let mut programs = BTreeMap::new();
+
+    if let Some(anchor_toml) = repo.read_file(checkout, "Anchor.toml").await? {
+        programs.extend(anchor_programs(&anchor_toml));
+    }
+
+    if programs.is_empty() {
+        if let Some(cargo_toml) = repo.read_file(checkout, "Cargo.toml").await? {
+            let mut crates = Vec::new();
+            for member in workspace_members(&cargo_toml) {
+                match member.strip_suffix("/*") {
+                    Some(parent) => {
+                        for dir in repo.list_dir(checkout, parent).await?.unwrap_or_default() {
+                            crates.push(format!("{}/{}", parent, dir));
+                        }
+                    }
+                    None => crates.push(member),
+                }
+            }
+            // A repository without a workspace is a single crate
+            if crates.is_empty() {
+                crates.push(String::new());
+            }
+
+            for dir in crates {
+                let prefix = if dir.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}/", dir)
+                };
+                let Some(manifest) = repo
+                    .read_file(checkout, &format!("{}Cargo.toml", prefix))
+                    .await?
+                else {
+                    continue;
+                };
+                let Some(lib) = lib_name(&manifest) else {
+                    continue;
+                };
+                let Some(lib_rs) = repo
+                    .read_file(checkout, &format!("{}src/lib.rs", prefix))
+                    .await?
+                else {
+                    continue;
+                };
+                if let Some(program_id) = declared_id(&lib_rs) {
+                    programs.insert(lib, program_id);
+                }
+            }
+        }
+    }
+
+    Ok(programs
+        .into_iter()
+        .map(|(lib, program_id)| SolanaProgramBuildParams {
+            repository: repo.url(),
+            program_id,
+            commit_hash: None,
+            lib_name: Some(lib),
+            bpf_flag: None,
+            base_image: None,
+            mount_path: None,
+            cargo_args: None,
+        })
+        .collect())
+}
+
+// Programs listed under [programs.mainnet], or [programs.localnet] when mainnet isn't listed
+fn anchor_programs(content: &str) -> Vec<(String, String)> {
+    let Ok(manifest) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    let clusters = manifest
+        .get("programs")
+        .and_then(|programs| programs.as_table());
+
+    ["mainnet", "localnet"]
+        .iter()
+        .filter_map(|cluster| clusters?.get(*cluster)?.as_table())
+        .map(|programs| {
+            programs
+                .iter()
+                .filter_map(|(name, program_id)| {
+                    let program_id = program_id.as_str()?;
+                    Pubkey::from_str(program_id).ok()?;
+                    Some((name.replace('-', "_"), program_id.to_string()))
+                })
+                .collect::<Vec<(String, String)>>()
+        })
+        .find(|programs| !programs.is_empty())
+        .unwrap_or_default()
+}
+
+fn workspace_members(content: &str) -> Vec<String> {
+    let Ok(manifest) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+
+    manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get("members"))
+        .and_then(|members| members.as_array())
+        .map(|members| {
+            members
+                .iter()
+                .filter_map(|member| member.as_str())
+                .map(|member| member.trim_end_matches('/').to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Library name of a crate, only for crates building a cdylib like on-chain programs do
+fn lib_name(content: &str) -> Option<String> {
+    let manifest = content.parse::<toml::Table>().ok()?;
+    let lib = manifest.get("lib")?;
+
+    let crate_types = lib.get("crate-type")?.as_array()?;
+    if !crate_types
+        .iter()
+        .any(|crate_type| crate_type.as_str() == Some("cdylib"))
+    {
+        return None;
+    }
+
+    let name = match lib.get("name").and_then(|name| name.as_str()) {
+        Some(name) => name,
+        None => manifest.get("package")?.get("name")?.as_str()?,
+    };
+    Some(name.replace('-', "_"))
+}
+
+// Program id from `declare_id!("...")` in the crate root
+fn declared_id(content: &str) -> Option<String> {
+    let start = content.find("declare_id!(")? + "declare_id!(".len();
+    let rest = &content[start..];
+    let program_id = rest[..rest.find(')')?].trim().trim_matches('"');
+    Pubkey::from_str(program_id).ok()?;
+    Some(program_id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anchor_programs() {
+        let programs = anchor_programs(
+            r#"
+            [programs.localnet]
+            my-program = "11111111111111111111111111111111"
+
+            [programs.mainnet]
+            my-program = "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY"
+            other = "not a pubkey"
+            "#,
+        );
+        assert_eq!(
+            programs,
+            vec![(
+                "my_program".to_string(),
+                "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY".to_string()
+            )]
+        );
+
+        let programs = anchor_programs(
+            "[programs.localnet]\nmy_program = \"11111111111111111111111111111111\"\n",
+        );
+        assert_eq!(programs[0].1, "11111111111111111111111111111111");
+
+        assert!(anchor_programs("[provider]\ncluster = \"mainnet\"\n").is_empty());
+    }
+
+    #[test]
+    fn test_cargo_metadata() {
+        assert_eq!(
+            workspace_members("[workspace]\nmembers = [\"programs/*\", \"cli/\"]\n"),
+            vec!["programs/*".to_string(), "cli".to_string()]
+        );
+        assert!(workspace_members("[package]\nname = \"phoenix\"\n").is_empty());
+
+        assert_eq!(
+            lib_name(
+                "[package]\nname = \"phoenix-v1\"\n\n[lib]\ncrate-type = [\"cdylib\", \"lib\"]\n"
+            ),
+            Some("phoenix_v1".to_string())
+        );
+        assert_eq!(
+            lib_name("[package]\nname = \"phoenix-v1\"\n\n[lib]\nname = \"phoenix\"\ncrate-type = [\"cdylib\"]\n"),
+            Some("phoenix".to_string())
+        );
+        assert_eq!(lib_name("[package]\nname = \"cli\"\n"), None);
+
+        assert_eq!(
+            declared_id("use anchor_lang::prelude::*;\n\ndeclare_id!(\"PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY\");\n"),
+            Some("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY".to_string())
+        );
+        assert_eq!(declared_id("declare_id!(crate::ID);"), None);
+    }
+}
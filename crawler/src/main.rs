@@ -1,6 +1,8 @@
+// solana_client's ClientError is large and returned as-is from RPC call closures
+#![allow(clippy::result_large_err)]
+
 use dotenv::dotenv;
 use std::env;
-use std::fs::OpenOptions;
 
 mod api;
 mod crawler;
@@ -8,15 +10,20 @@ mod db;
 mod errors;
 mod github;
 mod helper;
+mod infer;
+mod repo;
+mod report;
+mod rpc;
+mod scheduler;
 mod schema;
+mod verify_json;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    let rpc_url =
-        env::var("RPC_URL").unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
+    let rpc_config = rpc::RpcConfig::from_env();
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let db_client = db::client::DbClient::new(&database_url);
+    let db_client = db::client::DbClient::new(&database_url, rpc_config.cluster);
 
     tracing_subscriber::fmt()
         .pretty()
@@ -26,18 +33,30 @@ async fn main() {
         .with_target(false)
         .init();
 
-    OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(helper::OUTPUT_FILENAME)
-        .unwrap();
+    // Keep running and schedule every phase internally instead of relying on cron
+    if env::args().any(|arg| arg == "--daemon") {
+        scheduler::run(db_client, rpc_config).await;
+        return;
+    }
 
-    // Crawl the mainnet programs and write github source links to a file
-    crate::crawler::crawl_mainnet_programs(&db_client, &rpc_url).await;
+    // Crawl the programs of the configured cluster and queue github source links for verification
+    let crawl_mode = crawler::CrawlMode::from_env();
+    let run_report = report::RunReport::default();
+    let run_id = report::start_run(&db_client, "oneshot").await;
+    crate::crawler::crawl_mainnet_programs(&db_client, &rpc_config, crawl_mode, &run_report).await;
+
+    // Crawl the targets submitted through the admin API
+    if let Err(err) =
+        crate::crawler::crawl_requested_targets(&db_client, &rpc_config, &run_report).await
+    {
+        tracing::error!("Failed to crawl requested targets: {}", err);
+    }
 
     // Verify the programs
-    let _ = helper::verify_programs(helper::OUTPUT_FILENAME).await;
+    if let Err(err) = helper::verify_programs(&db_client, &run_report).await {
+        tracing::error!("Failed to verify crawl targets: {}", err);
+    }
+    report::finish_run(&db_client, run_id, &run_report).await;
 }
 
 #[cfg(test)]
@@ -60,7 +79,7 @@ mod tests {
                 "--bpf".to_string(),
             ],
         };
-        let build_params = helper::extract_build_params(&args);
+        let build_params = helper::extract_build_params(&args).unwrap();
 
         let result = api::client::verify_build(build_params).await;
         assert!(result.is_ok());
@@ -68,7 +87,8 @@ mod tests {
 
     #[tokio::test] // Need to Hard code the github url to test
     async fn test_verification() {
-        helper::start_verification("https://github.com/Ellipsis-Labs/phoenix-v1/")
+        let run_report = report::RunReport::default();
+        helper::start_verification("https://github.com/Ellipsis-Labs/phoenix-v1/", &run_report)
             .await
             .unwrap();
     }
@@ -100,7 +120,7 @@ mod tests {
                 command: params,
             };
 
-            let build_params = extract_build_params(&params);
+            let build_params = extract_build_params(&params).unwrap();
             assert!(build_params.commit_hash.is_some());
         }
     }
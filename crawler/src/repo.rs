@@ -0,0 +1,339 @@
+// Source code repositories linked from security.txt
+
+use anyhow::{format_err, Result};
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::env;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+
+use crate::api::models::SolanaProgramBuildParams;
+use crate::errors;
+use crate::github::{http_client, GithubClient};
+use crate::infer::infer_build_params;
+use crate::verify_json::{parse_verification_file, VERIFICATION_FILES};
+
+const DEFAULT_GIT_HOST_CONCURRENCY: usize = 4;
+
+// Bound the lookups in flight against git hosts, whatever the crawl concurrency is
+fn git_host_permits() -> &'static Semaphore {
+    static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+    PERMITS.get_or_init(|| {
+        let permits = env::var("GIT_HOST_CONCURRENCY")
+            .ok()
+            .filter(|permits| !permits.is_empty())
+            .map_or(DEFAULT_GIT_HOST_CONCURRENCY, |permits| {
+                permits
+                    .parse::<usize>()
+                    .expect("GIT_HOST_CONCURRENCY must be a positive integer")
+                    .max(1)
+            });
+        Semaphore::new(permits)
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RepoHost {
+    Github,
+    Gitlab,
+    Bitbucket,
+    // Any other git server. Only reachable through git itself.
+    SelfHosted,
+}
+
+impl From<RepoHost> for String {
+    fn from(host: RepoHost) -> Self {
+        match host {
+            RepoHost::Github => "github".to_string(),
+            RepoHost::Gitlab => "gitlab".to_string(),
+            RepoHost::Bitbucket => "bitbucket".to_string(),
+            RepoHost::SelfHosted => "self_hosted".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RepoUrl {
+    pub host: RepoHost,
+    // Scheme and domain, e.g. https://gitlab.com
+    pub base_url: String,
+    // Everything before the repository name. GitLab groups can be nested.
+    pub owner: String,
+    pub repo: String,
+}
+
+impl RepoUrl {
+    // Parse a source_code link, ignoring a trailing `.git` and links to a tree or file
+    pub fn parse(url: &str) -> Option<Self> {
+        let (scheme, rest) = url.trim().split_once("://")?;
+        if scheme != "https" && scheme != "http" {
+            return None;
+        }
+
+        let (domain, path) = rest.split_once('/')?;
+        let domain = domain.to_lowercase();
+        let host = match domain.trim_start_matches("www.") {
+            "github.com" => RepoHost::Github,
+            "gitlab.com" => RepoHost::Gitlab,
+            "bitbucket.org" => RepoHost::Bitbucket,
+            _ => RepoHost::SelfHosted,
+        };
+
+        let mut segments = path
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default()
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<&str>>();
+
+        match host {
+            // github.com/<owner>/<repo>/tree/...
+            // bitbucket.org/<owner>/<repo>/src/...
+            RepoHost::Github | RepoHost::Bitbucket => segments.truncate(2),
+            // gitlab.com/<group>/<subgroup>/<repo>/-/tree/...
+            RepoHost::Gitlab | RepoHost::SelfHosted => {
+                if let Some(position) = segments.iter().position(|segment| *segment == "-") {
+                    segments.truncate(position);
+                }
+            }
+        }
+
+        let (repo, owner) = segments.split_last()?;
+        if owner.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            host,
+            base_url: format!("{}://{}", scheme, domain),
+            owner: owner.join("/"),
+            repo: repo.trim_end_matches(".git").to_string(),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("{}/{}/{}", self.base_url, self.owner, self.repo)
+    }
+
+    // Find and validate the verification file on the default branch of the repository
+    pub async fn get_verification_params(&self) -> Result<Vec<SolanaProgramBuildParams>> {
+        let _permit = git_host_permits().acquire().await?;
+        let checkout = self.checkout().await?;
+
+        for path in VERIFICATION_FILES {
+            let Some(content) = self.read_file(&checkout, path).await? else {
+                continue;
+            };
+            if let Some(params) = parse_verification_file(&self.url(), path, &content)? {
+                tracing::info!("Found verification params in {}/{}", self.url(), path);
+                return Ok(params);
+            }
+        }
+
+        // Fall back to the programs declared in Anchor.toml or Cargo.toml
+        let params = infer_build_params(self, &checkout).await?;
+        if params.is_empty() {
+            return Err(errors::CrawlerErrors::VerificationJsonNotFound.into());
+        }
+        tracing::info!(
+            "Inferred build params for {} programs in {}",
+            params.len(),
+            self.url()
+        );
+
+        Ok(params)
+    }
+
+    // Resolve the default branch, or clone it when the host has no raw file API
+    async fn checkout(&self) -> Result<Checkout> {
+        let branch = match self.host {
+            RepoHost::Github => {
+                GithubClient::new(&self.owner, &self.repo)
+                    .fetch_default_branch()
+                    .await?
+            }
+            RepoHost::Gitlab => {
+                let project = format!("{}/{}", self.owner, self.repo).replace('/', "%2F");
+                let project = http_client()
+                    .get(format!("{}/api/v4/projects/{}", self.base_url, project))
+                    .send()
+                    .await?;
+                let project = check_status(project, &self.url())?.json::<Value>().await?;
+                project["default_branch"]
+                    .as_str()
+                    .ok_or(errors::CrawlerErrors::DefaultBranchNotFound)?
+                    .to_string()
+            }
+            RepoHost::Bitbucket => {
+                let repository = http_client()
+                    .get(format!(
+                        "https://api.bitbucket.org/2.0/repositories/{}/{}",
+                        self.owner, self.repo
+                    ))
+                    .send()
+                    .await?;
+                let repository = check_status(repository, &self.url())?
+                    .json::<Value>()
+                    .await?;
+                repository["mainbranch"]["name"]
+                    .as_str()
+                    .ok_or(errors::CrawlerErrors::DefaultBranchNotFound)?
+                    .to_string()
+            }
+            RepoHost::SelfHosted => return self.clone_default_branch().await,
+        };
+
+        Ok(Checkout::Remote(branch))
+    }
+
+    // There is no common raw file API across self-hosted servers, so shallow clone the
+    // default branch and read files from disk
+    async fn clone_default_branch(&self) -> Result<Checkout> {
+        let dir = TempDir(env::temp_dir().join(format!("crawler-{}", rand::random::<u64>())));
+
+        let output = Command::new("git")
+            .args(["clone", "--depth", "1", "--quiet", &self.url()])
+            .arg(&dir.0)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(format_err!(
+                "Failed to clone {}: {}",
+                self.url(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(Checkout::Local(dir))
+    }
+
+    // Read a file from the checkout, None if it doesn't exist
+    pub async fn read_file(&self, checkout: &Checkout, path: &str) -> Result<Option<String>> {
+        let branch = match checkout {
+            Checkout::Local(dir) => {
+                return match tokio::fs::read_to_string(dir.0.join(path)).await {
+                    Ok(content) => Ok(Some(content)),
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                    Err(err) => Err(err.into()),
+                };
+            }
+            Checkout::Remote(branch) => branch,
+        };
+
+        let url = match self.host {
+            RepoHost::Github => format!(
+                "https://raw.githubusercontent.com/{}/{}/{}/{}",
+                self.owner, self.repo, branch, path
+            ),
+            RepoHost::Gitlab => format!("{}/-/raw/{}/{}", self.url(), branch, path),
+            RepoHost::Bitbucket | RepoHost::SelfHosted => {
+                format!("{}/raw/{}/{}", self.url(), branch, path)
+            }
+        };
+
+        let response = http_client().get(&url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(check_status(response, &self.url())?.text().await?))
+    }
+
+    // Names of the directories inside a directory of the checkout. None when the host can't
+    // list directories.
+    pub async fn list_dir(&self, checkout: &Checkout, path: &str) -> Result<Option<Vec<String>>> {
+        match (checkout, self.host) {
+            (Checkout::Local(dir), _) => {
+                let mut entries = match tokio::fs::read_dir(dir.0.join(path)).await {
+                    Ok(entries) => entries,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                    Err(err) => return Err(err.into()),
+                };
+                let mut dirs = Vec::new();
+                while let Some(entry) = entries.next_entry().await? {
+                    if entry.file_type().await?.is_dir() {
+                        dirs.push(entry.file_name().to_string_lossy().to_string());
+                    }
+                }
+                Ok(Some(dirs))
+            }
+            (Checkout::Remote(branch), RepoHost::Github) => {
+                GithubClient::new(&self.owner, &self.repo)
+                    .list_directories(path, branch)
+                    .await
+                    .map(Some)
+            }
+            (Checkout::Remote(_), _) => Ok(None),
+        }
+    }
+}
+
+// Turn missing repositories and rate limits into errors the crawler can categorize
+pub fn check_status(response: reqwest::Response, repo: &str) -> Result<reqwest::Response> {
+    match response.status() {
+        StatusCode::NOT_FOUND => {
+            Err(errors::CrawlerErrors::RepositoryNotFound(repo.to_string()).into())
+        }
+        StatusCode::TOO_MANY_REQUESTS => Err(errors::CrawlerErrors::RateLimited(
+            response.url().host_str().unwrap_or_default().to_string(),
+        )
+        .into()),
+        _ => Ok(response.error_for_status()?),
+    }
+}
+
+// Default branch of a repository, either on the host or cloned to a temporary directory
+pub enum Checkout {
+    Remote(String),
+    Local(TempDir),
+}
+
+// Directory removed once the checkout is dropped
+pub struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repo_url() {
+        let repo = RepoUrl::parse("https://github.com/Ellipsis-Labs/phoenix-v1/").unwrap();
+        assert_eq!(repo.host, RepoHost::Github);
+        assert_eq!(repo.url(), "https://github.com/Ellipsis-Labs/phoenix-v1");
+
+        let repo =
+            RepoUrl::parse("https://github.com/Ellipsis-Labs/phoenix-v1/tree/master/src").unwrap();
+        assert_eq!(repo.repo, "phoenix-v1");
+
+        let repo = RepoUrl::parse("https://gitlab.com/group/subgroup/program.git").unwrap();
+        assert_eq!(repo.host, RepoHost::Gitlab);
+        assert_eq!(repo.owner, "group/subgroup");
+        assert_eq!(repo.repo, "program");
+
+        let repo = RepoUrl::parse("https://gitlab.com/group/program/-/tree/main").unwrap();
+        assert_eq!(repo.url(), "https://gitlab.com/group/program");
+
+        let repo = RepoUrl::parse("https://bitbucket.org/team/program/src/main/").unwrap();
+        assert_eq!(repo.host, RepoHost::Bitbucket);
+        assert_eq!(repo.url(), "https://bitbucket.org/team/program");
+
+        let repo = RepoUrl::parse("https://git.example.com/team/program.git").unwrap();
+        assert_eq!(repo.host, RepoHost::SelfHosted);
+        assert_eq!(repo.url(), "https://git.example.com/team/program");
+
+        assert!(RepoUrl::parse("https://github.com/Ellipsis-Labs").is_none());
+        assert!(RepoUrl::parse("git@github.com:Ellipsis-Labs/phoenix-v1.git").is_none());
+        assert!(RepoUrl::parse("not a url").is_none());
+    }
+}
@@ -0,0 +1,174 @@
+// Per-run summary written to crawler_runs
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+
+use crate::db::client::DbClient;
+use crate::errors::CrawlerErrors;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FailureCategory {
+    Rpc,
+    ProgramClosed,
+    SecurityTxtNotFound,
+    MissingSourceCode,
+    InvalidSourceUrl,
+    RepositoryNotFound,
+    RateLimited,
+    VerificationJsonNotFound,
+    InvalidVerificationJson,
+    SubmissionFailed,
+    Database,
+    Other,
+}
+
+impl From<FailureCategory> for String {
+    fn from(category: FailureCategory) -> Self {
+        match category {
+            FailureCategory::Rpc => "rpc".to_string(),
+            FailureCategory::ProgramClosed => "program_closed".to_string(),
+            FailureCategory::SecurityTxtNotFound => "security_txt_not_found".to_string(),
+            FailureCategory::MissingSourceCode => "missing_source_code".to_string(),
+            FailureCategory::InvalidSourceUrl => "invalid_source_url".to_string(),
+            FailureCategory::RepositoryNotFound => "repository_not_found".to_string(),
+            FailureCategory::RateLimited => "rate_limited".to_string(),
+            FailureCategory::VerificationJsonNotFound => "verification_json_not_found".to_string(),
+            FailureCategory::InvalidVerificationJson => "invalid_verification_json".to_string(),
+            FailureCategory::SubmissionFailed => "submission_failed".to_string(),
+            FailureCategory::Database => "database".to_string(),
+            FailureCategory::Other => "other".to_string(),
+        }
+    }
+}
+
+impl FailureCategory {
+    // Category of an error returned while crawling a program or its repository
+    pub fn of(err: &anyhow::Error) -> Self {
+        match err.downcast_ref::<CrawlerErrors>() {
+            Some(CrawlerErrors::FailedToFetchProgramAccount(_)) => FailureCategory::Rpc,
+            Some(CrawlerErrors::ProgramClosed(_)) => FailureCategory::ProgramClosed,
+            Some(
+                CrawlerErrors::SecurityTextNotFound(_)
+                | CrawlerErrors::ProgramDataAccountSizeTooSmall
+                | CrawlerErrors::FailedToGetProgramDataOffset(_),
+            ) => FailureCategory::SecurityTxtNotFound,
+            Some(
+                CrawlerErrors::DefaultBranchNotFound | CrawlerErrors::VerificationJsonNotFound,
+            ) => FailureCategory::VerificationJsonNotFound,
+            Some(CrawlerErrors::InvalidVerificationJson(..)) => {
+                FailureCategory::InvalidVerificationJson
+            }
+            Some(CrawlerErrors::InvalidSourceUrl(_)) => FailureCategory::InvalidSourceUrl,
+            Some(CrawlerErrors::RepositoryNotFound(_)) => FailureCategory::RepositoryNotFound,
+            Some(CrawlerErrors::RateLimited(_)) => FailureCategory::RateLimited,
+            Some(CrawlerErrors::SubmissionFailed(_)) => FailureCategory::SubmissionFailed,
+            Some(CrawlerErrors::ProgramNotUpdated) | None => FailureCategory::Other,
+        }
+    }
+}
+
+// Counters shared by every task of a run
+#[derive(Default, Debug)]
+pub struct RunReport {
+    programs_scanned: AtomicI32,
+    security_txt_found: AtomicI32,
+    verification_json_found: AtomicI32,
+    verifications_submitted: AtomicI32,
+    failures: Mutex<HashMap<FailureCategory, i32>>,
+}
+
+impl RunReport {
+    pub fn program_scanned(&self) {
+        self.programs_scanned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn security_txt_found(&self) {
+        self.security_txt_found.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn verification_json_found(&self) {
+        self.verification_json_found.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn verification_submitted(&self) {
+        self.verifications_submitted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn failure(&self, category: FailureCategory) {
+        *self.failures.lock().unwrap().entry(category).or_default() += 1;
+    }
+
+    // (programs scanned, security.txt found, verification JSONs found, verifications submitted)
+    pub fn counts(&self) -> (i32, i32, i32, i32) {
+        (
+            self.programs_scanned.load(Ordering::Relaxed),
+            self.security_txt_found.load(Ordering::Relaxed),
+            self.verification_json_found.load(Ordering::Relaxed),
+            self.verifications_submitted.load(Ordering::Relaxed),
+        )
+    }
+
+    // Failure counts keyed by category
+    pub fn failures(&self) -> Value {
+        let failures = self.failures.lock().unwrap();
+        Value::Object(
+            failures
+                .iter()
+                .map(|(category, count)| (String::from(*category), Value::from(*count)))
+                .collect::<Map<String, Value>>(),
+        )
+    }
+}
+
+// Insert the run, logging instead of failing the crawl if the report can't be written
+pub async fn start_run(db: &DbClient, kind: &str) -> Option<i32> {
+    match db.insert_crawler_run(kind).await {
+        Ok(run_id) => Some(run_id),
+        Err(err) => {
+            tracing::error!("Failed to record {} run: {}", kind, err);
+            None
+        }
+    }
+}
+
+pub async fn finish_run(db: &DbClient, run_id: Option<i32>, report: &RunReport) {
+    let (scanned, security_txt, verification_json, submitted) = report.counts();
+    tracing::info!(
+        "Run summary: {} programs scanned, {} security.txt found, {} verification JSONs found, {} verifications submitted, failures: {}",
+        scanned,
+        security_txt,
+        verification_json,
+        submitted,
+        report.failures()
+    );
+
+    if let Some(run_id) = run_id {
+        if let Err(err) = db.finish_crawler_run(run_id, report).await {
+            tracing::error!("Failed to record run summary: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_report() {
+        let report = RunReport::default();
+        report.program_scanned();
+        report.program_scanned();
+        report.security_txt_found();
+        report.failure(FailureCategory::ProgramClosed);
+        report.failure(FailureCategory::Rpc);
+        report.failure(FailureCategory::Rpc);
+
+        assert_eq!(report.counts(), (2, 1, 0, 0));
+        assert_eq!(
+            report.failures(),
+            serde_json::json!({ "rpc": 2, "program_closed": 1 })
+        );
+    }
+}
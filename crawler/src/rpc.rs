@@ -0,0 +1,361 @@
+// RPC client configuration
+
+use anyhow::{format_err, Result};
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::StatusCode;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    rpc_client::{RpcClient, RpcClientConfig},
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_rpc_client::http_sender::HttpSender;
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::{env, str::FromStr, time::Duration};
+
+// Cluster crawled by this instance. Rows of every table are tagged with it so several
+// crawlers can share one database.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cluster {
+    Mainnet,
+    Devnet,
+    Testnet,
+}
+
+impl FromStr for Cluster {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mainnet" | "mainnet-beta" => Ok(Cluster::Mainnet),
+            "devnet" => Ok(Cluster::Devnet),
+            "testnet" => Ok(Cluster::Testnet),
+            _ => Err(format_err!("Unknown cluster: {}", s)),
+        }
+    }
+}
+
+impl From<Cluster> for String {
+    fn from(cluster: Cluster) -> Self {
+        match cluster {
+            Cluster::Mainnet => "mainnet".to_string(),
+            Cluster::Devnet => "devnet".to_string(),
+            Cluster::Testnet => "testnet".to_string(),
+        }
+    }
+}
+
+impl Cluster {
+    pub fn from_env() -> Self {
+        env::var("CLUSTER")
+            .ok()
+            .filter(|cluster| !cluster.is_empty())
+            .map_or(Cluster::Mainnet, |cluster| {
+                Cluster::from_str(&cluster)
+                    .expect("CLUSTER must be one of mainnet, devnet or testnet")
+            })
+    }
+
+    fn default_rpc_url(self) -> &'static str {
+        match self {
+            Cluster::Mainnet => "https://api.mainnet-beta.solana.com",
+            Cluster::Devnet => "https://api.devnet.solana.com",
+            Cluster::Testnet => "https://api.testnet.solana.com",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RpcConfig {
+    pub cluster: Cluster,
+    pub url: String,
+    pub commitment: CommitmentConfig,
+    pub retry: RetryPolicy,
+    headers: HeaderMap,
+}
+
+impl RpcConfig {
+    pub fn from_env() -> Self {
+        let cluster = Cluster::from_env();
+        let url = env::var("RPC_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| cluster.default_rpc_url().to_string());
+
+        // Commitment used for every on-chain read so program and program data are seen at the same state
+        let commitment = env::var("COMMITMENT")
+            .ok()
+            .filter(|level| !level.is_empty())
+            .map(|level| {
+                CommitmentConfig::from_str(&level)
+                    .expect("COMMITMENT must be one of processed, confirmed or finalized")
+            })
+            .unwrap_or_else(CommitmentConfig::finalized);
+
+        // Provider auth headers are kept out of RPC_URL so they never end up in logs
+        let headers = env::var("RPC_HEADERS")
+            .ok()
+            .map(|headers| {
+                parse_headers(&headers)
+                    .expect("RPC_HEADERS must be a comma separated list of `Name: value` pairs")
+            })
+            .unwrap_or_default();
+
+        Self {
+            cluster,
+            url,
+            commitment,
+            retry: RetryPolicy::from_env(),
+            headers,
+        }
+    }
+
+    // Create a client sending the configured headers with every request
+    pub fn client(&self, timeout: Duration) -> RpcClient {
+        let mut headers = HttpSender::default_headers();
+        headers.extend(self.headers.clone());
+
+        let http_client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(timeout)
+            .pool_idle_timeout(timeout)
+            .build()
+            .expect("Failed to build RPC HTTP client");
+
+        RpcClient::new_sender(
+            HttpSender::new_with_client(&self.url, http_client),
+            RpcClientConfig::with_commitment(self.commitment),
+        )
+    }
+}
+
+// Classes of RPC failures that are worth retrying
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetryClass {
+    Timeout,
+    Connect,
+    ServerError,
+    RateLimited,
+    NodeUnhealthy,
+}
+
+impl FromStr for RetryClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "timeout" => Ok(RetryClass::Timeout),
+            "connect" => Ok(RetryClass::Connect),
+            "server_error" => Ok(RetryClass::ServerError),
+            "rate_limited" => Ok(RetryClass::RateLimited),
+            "node_unhealthy" => Ok(RetryClass::NodeUnhealthy),
+            _ => Err(format_err!("Unknown retry class: {}", s)),
+        }
+    }
+}
+
+impl RetryClass {
+    fn of(err: &ClientError) -> Option<Self> {
+        match err.kind() {
+            ClientErrorKind::Io(_) => Some(RetryClass::Connect),
+            ClientErrorKind::Reqwest(err) if err.is_timeout() => Some(RetryClass::Timeout),
+            ClientErrorKind::Reqwest(err) if err.is_connect() => Some(RetryClass::Connect),
+            ClientErrorKind::Reqwest(err) => match err.status() {
+                Some(StatusCode::TOO_MANY_REQUESTS) => Some(RetryClass::RateLimited),
+                Some(status) if status.is_server_error() => Some(RetryClass::ServerError),
+                _ => None,
+            },
+            ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                data: RpcResponseErrorData::NodeUnhealthy { .. },
+                ..
+            }) => Some(RetryClass::NodeUnhealthy),
+            _ => None,
+        }
+    }
+}
+
+// Exponential backoff with full jitter for transient RPC failures
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub retry_on: Vec<RetryClass>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            retry_on: vec![
+                RetryClass::Timeout,
+                RetryClass::Connect,
+                RetryClass::ServerError,
+                RetryClass::RateLimited,
+                RetryClass::NodeUnhealthy,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let env_u64 = |key: &str| {
+            env::var(key)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .map(|value| {
+                    value
+                        .parse::<u64>()
+                        .unwrap_or_else(|_| panic!("{} must be a positive integer", key))
+                })
+        };
+
+        Self {
+            max_attempts: env_u64("RPC_MAX_ATTEMPTS").map_or(default.max_attempts, |attempts| {
+                attempts.clamp(1, u32::MAX as u64) as u32
+            }),
+            base_delay: env_u64("RPC_RETRY_BASE_DELAY_MS")
+                .map_or(default.base_delay, Duration::from_millis),
+            max_delay: env_u64("RPC_RETRY_MAX_DELAY_MS")
+                .map_or(default.max_delay, Duration::from_millis),
+            retry_on: env::var("RPC_RETRY_ON")
+                .ok()
+                .filter(|classes| !classes.is_empty())
+                .map_or(default.retry_on, |classes| {
+                    classes
+                        .split(',')
+                        .map(|class| {
+                            RetryClass::from_str(class.trim()).unwrap_or_else(|_| {
+                                panic!(
+                                    "RPC_RETRY_ON must be a comma separated list of timeout, \
+                                     connect, server_error, rate_limited or node_unhealthy, got {}",
+                                    classes
+                                )
+                            })
+                        })
+                        .collect()
+                }),
+        }
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+
+    // Run an RPC call, retrying failures of the configured classes
+    pub async fn run<T>(&self, mut call: impl FnMut() -> ClientResult<T>) -> ClientResult<T> {
+        let mut attempt = 0;
+        loop {
+            match call() {
+                Ok(result) => return Ok(result),
+                Err(err) => {
+                    attempt += 1;
+                    let retryable =
+                        RetryClass::of(&err).is_some_and(|class| self.retry_on.contains(&class));
+                    if !retryable || attempt >= self.max_attempts {
+                        return Err(err);
+                    }
+
+                    let delay = self.delay(attempt - 1);
+                    tracing::warn!(
+                        "RPC call failed (attempt {}/{}), retrying in {:?}: {}",
+                        attempt,
+                        self.max_attempts,
+                        delay,
+                        err
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+}
+
+// Parse `Name: value` pairs separated by commas. Values are marked sensitive so they are
+// redacted from any debug output.
+fn parse_headers(input: &str) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+
+    for pair in input.split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (name, value) = pair
+            .split_once(':')
+            .ok_or_else(|| format_err!("Header is missing a value"))?;
+
+        let name = HeaderName::from_str(name.trim())?;
+        let mut value = HeaderValue::from_str(value.trim())?;
+        value.set_sensitive(true);
+
+        headers.insert(name, value);
+    }
+
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headers() {
+        let headers = parse_headers("x-api-key: secret, Authorization: Bearer token").unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["x-api-key"], "secret");
+        assert_eq!(headers["authorization"], "Bearer token");
+        assert!(headers["x-api-key"].is_sensitive());
+        assert!(!format!("{:?}", headers).contains("secret"));
+
+        assert!(parse_headers("").unwrap().is_empty());
+        assert!(parse_headers("x-api-key").is_err());
+    }
+
+    #[test]
+    fn test_cluster() {
+        assert_eq!(Cluster::from_str("mainnet-beta").unwrap(), Cluster::Mainnet);
+        assert_eq!(Cluster::from_str("devnet").unwrap(), Cluster::Devnet);
+        assert_eq!(String::from(Cluster::Testnet), "testnet");
+        assert!(Cluster::from_str("localnet").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+            ..RetryPolicy::default()
+        };
+
+        // Transient failures are retried until the call succeeds
+        let mut calls = 0;
+        let result = policy
+            .run(|| {
+                calls += 1;
+                if calls < 3 {
+                    Err(ClientErrorKind::Io(std::io::ErrorKind::ConnectionReset.into()).into())
+                } else {
+                    Ok(calls)
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 3);
+
+        // Other errors are returned immediately
+        let mut calls = 0;
+        let result: ClientResult<()> = policy
+            .run(|| {
+                calls += 1;
+                Err(ClientErrorKind::Custom("invalid param".to_string()).into())
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
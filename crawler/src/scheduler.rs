@@ -0,0 +1,214 @@
+// Daemon mode: run every crawler phase on its own interval
+
+use anyhow::Result;
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::crawler::{crawl_mainnet_programs, crawl_requested_targets, CrawlMode};
+use crate::db::client::DbClient;
+use crate::helper::verify_programs;
+use crate::report::{self, RunReport};
+use crate::rpc::RpcConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    // Incremental crawl picking up newly deployed programs
+    Crawl,
+    // Full crawl re-reading security.txt of every program
+    Refresh,
+    // Submit pending crawl targets for verification
+    Verify,
+    // Crawl the programs and repositories submitted through POST /admin/crawl
+    Requests,
+}
+
+impl Phase {
+    // Name recorded in crawler_runs
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Crawl => "crawl",
+            Phase::Refresh => "refresh",
+            Phase::Verify => "verify",
+            Phase::Requests => "requests",
+        }
+    }
+
+    fn interval(self) -> Duration {
+        let (key, default) = match self {
+            Phase::Crawl => ("CRAWL_INTERVAL_SECS", 8 * 60 * 60),
+            Phase::Refresh => ("SECURITY_TXT_REFRESH_INTERVAL_SECS", 7 * 24 * 60 * 60),
+            Phase::Verify => ("VERIFY_INTERVAL_SECS", 60 * 60),
+            Phase::Requests => ("CRAWL_REQUESTS_INTERVAL_SECS", 60),
+        };
+        let secs = env::var(key)
+            .ok()
+            .filter(|secs| !secs.is_empty())
+            .map_or(default, |secs| {
+                secs.parse::<u64>()
+                    .unwrap_or_else(|_| panic!("{} must be a positive integer", key))
+            });
+        Duration::from_secs(secs.max(1))
+    }
+}
+
+struct Schedule {
+    phase: Phase,
+    interval: Duration,
+    next_run: Instant,
+    last_success: Option<Instant>,
+    consecutive_failures: u32,
+}
+
+// Run the phases forever. Crawl and Refresh share the crawl checkpoint, so they run one at a
+// time on the same lane. Verify and Requests each run on their own lane, so a long crawl doesn't
+// hold them up.
+pub async fn run(db: DbClient, rpc_config: RpcConfig) {
+    let lanes = [
+        vec![Phase::Crawl, Phase::Refresh],
+        vec![Phase::Verify],
+        vec![Phase::Requests],
+    ]
+    .into_iter()
+    .map(|phases| tokio::spawn(run_lane(db.clone(), rpc_config.clone(), phases)))
+    .collect::<Vec<_>>();
+
+    for lane in lanes {
+        if let Err(err) = lane.await {
+            tracing::error!("Scheduler lane stopped: {}", err);
+        }
+    }
+}
+
+// Run `phases` forever, one at a time
+async fn run_lane(db: DbClient, rpc_config: RpcConfig, phases: Vec<Phase>) {
+    let now = Instant::now();
+    let mut schedules = phases
+        .into_iter()
+        .map(|phase| Schedule {
+            phase,
+            interval: phase.interval(),
+            // The refresh is a full crawl, the first incremental crawl already covers it
+            next_run: if phase == Phase::Refresh {
+                now + phase.interval()
+            } else {
+                now
+            },
+            last_success: None,
+            consecutive_failures: 0,
+        })
+        .collect::<Vec<Schedule>>();
+
+    for schedule in &schedules {
+        tracing::info!(
+            "Scheduled {:?} phase every {:?}",
+            schedule.phase,
+            schedule.interval
+        );
+    }
+
+    loop {
+        let schedule = schedules
+            .iter_mut()
+            .min_by_key(|schedule| schedule.next_run)
+            .expect("At least one phase is scheduled");
+        tokio::time::sleep_until(schedule.next_run.into()).await;
+
+        let phase = schedule.phase;
+        let started = Instant::now();
+
+        let result = match has_work(&db, phase).await {
+            // Requests are polled often, only record a run when there is something to crawl
+            Ok(false) => Ok(Ok(())),
+            Err(err) => Ok(Err(err)),
+            Ok(true) => {
+                tracing::info!("Starting {:?} phase", phase);
+                let run_report = Arc::new(RunReport::default());
+                let run_id = report::start_run(&db, phase.name()).await;
+                // Run the phase in its own task so a panic is reported instead of killing the
+                // daemon. The run is finished out here so a panicking phase still closes it.
+                let result = tokio::spawn(run_phase(
+                    db.clone(),
+                    rpc_config.clone(),
+                    phase,
+                    run_report.clone(),
+                ))
+                .await;
+                report::finish_run(&db, run_id, &run_report).await;
+                result
+            }
+        };
+
+        match result {
+            Ok(Ok(())) => {
+                schedule.last_success = Some(Instant::now());
+                schedule.consecutive_failures = 0;
+                tracing::info!("{:?} phase finished in {:?}", phase, started.elapsed());
+            }
+            Ok(Err(err)) => {
+                schedule.consecutive_failures += 1;
+                tracing::error!("{:?} phase failed: {}", phase, err);
+            }
+            Err(err) => {
+                schedule.consecutive_failures += 1;
+                tracing::error!("{:?} phase panicked: {}", phase, err);
+            }
+        }
+        schedule.next_run = Instant::now() + schedule.interval;
+
+        log_health(&schedules);
+    }
+}
+
+async fn has_work(db: &DbClient, phase: Phase) -> Result<bool> {
+    if phase == Phase::Requests {
+        return Ok(!db.get_pending_crawl_requests().await?.is_empty());
+    }
+    Ok(true)
+}
+
+async fn run_phase(
+    db: DbClient,
+    rpc_config: RpcConfig,
+    phase: Phase,
+    run_report: Arc<RunReport>,
+) -> Result<()> {
+    match phase {
+        Phase::Crawl => {
+            crawl_mainnet_programs(&db, &rpc_config, CrawlMode::Incremental, &run_report).await;
+            Ok(())
+        }
+        Phase::Refresh => {
+            crawl_mainnet_programs(&db, &rpc_config, CrawlMode::Full, &run_report).await;
+            Ok(())
+        }
+        Phase::Verify => verify_programs(&db, &run_report).await,
+        Phase::Requests => crawl_requested_targets(&db, &rpc_config, &run_report).await,
+    }
+}
+
+fn log_health(schedules: &[Schedule]) {
+    let now = Instant::now();
+    for schedule in schedules {
+        let last_success = schedule.last_success.map_or("never".to_string(), |at| {
+            format!("{:?} ago", now.duration_since(at))
+        });
+        let next_run = schedule.next_run.saturating_duration_since(now);
+        if schedule.consecutive_failures > 0 {
+            tracing::warn!(
+                "{:?} phase: last success {}, {} consecutive failures, next run in {:?}",
+                schedule.phase,
+                last_success,
+                schedule.consecutive_failures,
+                next_run
+            );
+        } else {
+            tracing::info!(
+                "{:?} phase: last success {}, next run in {:?}",
+                schedule.phase,
+                last_success,
+                next_run
+            );
+        }
+    }
+}
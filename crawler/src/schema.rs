@@ -1,5 +1,69 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    authority_history (id) {
+        id -> Int4,
+        program_address -> Varchar,
+        cluster -> Varchar,
+        change -> Varchar,
+        old_authority -> Nullable<Varchar>,
+        new_authority -> Nullable<Varchar>,
+        slot -> Nullable<Int8>,
+        observed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    crawl_checkpoints (id) {
+        id -> Int4,
+        last_program_address -> Nullable<Varchar>,
+        started_at -> Timestamp,
+        updated_at -> Timestamp,
+        completed_at -> Nullable<Timestamp>,
+        cluster -> Varchar,
+        mode -> Varchar,
+    }
+}
+
+diesel::table! {
+    crawl_requests (id) {
+        id -> Int4,
+        target -> Varchar,
+        cluster -> Varchar,
+        status -> Varchar,
+        error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    crawl_targets (id) {
+        id -> Int4,
+        source_code -> Varchar,
+        status -> Varchar,
+        error -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        cluster -> Varchar,
+    }
+}
+
+diesel::table! {
+    crawler_runs (id) {
+        id -> Int4,
+        kind -> Varchar,
+        started_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+        programs_scanned -> Int4,
+        security_txt_found -> Int4,
+        verification_json_found -> Int4,
+        verifications_submitted -> Int4,
+        failures -> Jsonb,
+        cluster -> Varchar,
+    }
+}
+
 diesel::table! {
     mainnet_programs (id) {
         id -> Int4,
@@ -14,5 +78,19 @@ diesel::table! {
         updated_at -> Timestamp,
         last_deployed_slot -> Nullable<Int8>,
         update_authority -> Nullable<Varchar>,
+        repo_host -> Nullable<Varchar>,
+        last_error -> Nullable<Varchar>,
+        last_error_message -> Nullable<Text>,
+        last_error_at -> Nullable<Timestamp>,
+        cluster -> Varchar,
     }
-}
\ No newline at end of file
+}
+
+diesel::allow_tables_to_appear_in_same_query!(
+    authority_history,
+    crawl_checkpoints,
+    crawl_requests,
+    crawl_targets,
+    crawler_runs,
+    mainnet_programs,
+);
@@ -0,0 +1,178 @@
+// Verification file discovery and validation
+//
+// A verification file maps program ids to the arguments passed to
+// `solana-verify verify-from-repo`:
+//
+// {
+//     "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY": ["--library-name", "phoenix"]
+// }
+//
+// In Anchor.toml the same mapping lives in a `[verify]` table.
+
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+use crate::api::models::{BuildCommandArgs, SolanaProgramBuildParams};
+use crate::errors::CrawlerErrors;
+use crate::helper::extract_build_params;
+
+// Searched in order on the default branch, the first file with verification params wins
+pub const VERIFICATION_FILES: [&str; 4] = [
+    ".verified-build.json",
+    "verify.json",
+    ".solana-verify.json",
+    "Anchor.toml",
+];
+
+// Parse and validate a verification file. Returns None when the file exists but holds no
+// verification params, so the next location can be tried.
+pub fn parse_verification_file(
+    repo: &str,
+    path: &str,
+    content: &str,
+) -> Result<Option<Vec<SolanaProgramBuildParams>>, CrawlerErrors> {
+    let invalid = |reason: String| CrawlerErrors::InvalidVerificationJson(path.to_string(), reason);
+
+    let value = if path.ends_with(".toml") {
+        let manifest = content
+            .parse::<toml::Table>()
+            .map_err(|err| invalid(err.to_string()))?;
+        match manifest.get("verify") {
+            Some(verify) => serde_json::to_value(verify).map_err(|err| invalid(err.to_string()))?,
+            None => return Ok(None),
+        }
+    } else {
+        serde_json::from_str::<Value>(content).map_err(|err| invalid(err.to_string()))?
+    };
+
+    validate(repo, &value).map(Some).map_err(invalid)
+}
+
+fn validate(repo: &str, value: &Value) -> Result<Vec<SolanaProgramBuildParams>, String> {
+    let programs = value
+        .as_object()
+        .ok_or("expected an object mapping program ids to argument arrays")?;
+    if programs.is_empty() {
+        return Err("no programs listed".to_string());
+    }
+
+    programs
+        .iter()
+        .map(|(program_id, args)| {
+            Pubkey::from_str(program_id)
+                .map_err(|_| format!("{} is not a valid program id", program_id))?;
+
+            let args = args
+                .as_array()
+                .ok_or_else(|| format!("arguments of {} must be an array", program_id))?
+                .iter()
+                .enumerate()
+                .map(|(index, arg)| {
+                    arg.as_str().map(str::to_string).ok_or_else(|| {
+                        format!(
+                            "argument {} of {} must be a string, got {}",
+                            index, program_id, arg
+                        )
+                    })
+                })
+                .collect::<Result<Vec<String>, String>>()?;
+
+            extract_build_params(&BuildCommandArgs {
+                repo: repo.to_string(),
+                program_id: program_id.to_string(),
+                command: args,
+            })
+            .map_err(|err| format!("{}: {}", program_id, err))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPO: &str = "https://github.com/Ellipsis-Labs/phoenix-v1";
+
+    #[test]
+    fn test_parse_verification_json() {
+        let params = parse_verification_file(
+            REPO,
+            ".verified-build.json",
+            r#"{
+                "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY": [
+                    "--commit-hash", "8d2cd726afdc800f89c841ff3cf1968980719df0",
+                    "--library-name", "phoenix",
+                    "--", "--features", "mainnet"
+                ]
+            }"#,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(params.len(), 1);
+        assert_eq!(params[0].repository, REPO);
+        assert_eq!(params[0].lib_name.as_deref(), Some("phoenix"));
+        assert_eq!(
+            params[0].cargo_args,
+            Some(vec!["--features".to_string(), "mainnet".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_verification_json() {
+        let parse = |content: &str| {
+            parse_verification_file(REPO, "verify.json", content)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(parse("not json").contains("verify.json"));
+        assert!(parse("[]").contains("expected an object"));
+        assert!(parse("{}").contains("no programs listed"));
+        assert!(parse(r#"{"not-a-pubkey": []}"#).contains("not a valid program id"));
+        assert!(
+            parse(r#"{"PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY": "--bpf"}"#)
+                .contains("must be an array")
+        );
+        assert!(
+            parse(r#"{"PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY": ["--bpf", 1]}"#)
+                .contains("argument 1")
+        );
+        assert!(
+            parse(r#"{"PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY": ["--library-name"]}"#)
+                .contains("--library-name is missing a value")
+        );
+        assert!(
+            parse(r#"{"PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY": ["--unknown"]}"#)
+                .contains("unknown argument --unknown")
+        );
+    }
+
+    #[test]
+    fn test_parse_anchor_toml() {
+        let params = parse_verification_file(
+            REPO,
+            "Anchor.toml",
+            r#"
+            [programs.mainnet]
+            phoenix = "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY"
+
+            [verify]
+            PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY = ["--library-name", "phoenix", "--bpf"]
+            "#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(params[0].bpf_flag, Some(true));
+
+        // Anchor.toml without verify metadata is skipped
+        let params = parse_verification_file(
+            REPO,
+            "Anchor.toml",
+            "[programs.mainnet]\nphoenix = \"PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY\"\n",
+        )
+        .unwrap();
+        assert!(params.is_none());
+    }
+}